@@ -0,0 +1,97 @@
+use nalgebra as na;
+
+/// How far a box may be inside a block and still count as only touching it
+const COLLISION_EPSILON: f32 = 1e-4;
+
+/// Axis-aligned bounding box in world space
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Aabb {
+    pub min: na::Vector3<f32>,
+    pub max: na::Vector3<f32>,
+}
+
+impl Aabb {
+    /// Box of the block at a world position
+    pub fn block(world_position: na::Vector3<i32>) -> Self {
+        let min = world_position.map(|component| component as f32);
+        Aabb {
+            min,
+            max: min.add_scalar(1.0),
+        }
+    }
+
+    /// Whether the boxes overlap by more than [COLLISION_EPSILON], boxes
+    /// that only touch don't intersect
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| {
+            self.min[axis] < other.max[axis] - COLLISION_EPSILON
+                && self.max[axis] > other.min[axis] + COLLISION_EPSILON
+        })
+    }
+
+    /// The corner furthest along `direction`
+    pub fn furthest_corner(&self, direction: na::Vector3<f32>) -> na::Vector3<f32> {
+        na::Vector3::from_fn(|axis, _| {
+            if direction[axis] >= 0.0 {
+                self.max[axis]
+            } else {
+                self.min[axis]
+            }
+        })
+    }
+
+    /// How far the box can move by `distance` along `axis` before it runs
+    /// into a block `is_solid` accepts. Blocks the box already overlaps are
+    /// ignored so it can always move out of them.
+    ///
+    /// Every block the box passes on the way is checked, so fast movement
+    /// can't skip over thin walls.
+    pub fn sweep(
+        &self,
+        axis: usize,
+        distance: f32,
+        mut is_solid: impl FnMut(na::Vector3<i32>) -> bool,
+    ) -> f32 {
+        if distance == 0.0 {
+            return 0.0;
+        }
+        let mut swept = *self;
+        if distance > 0.0 {
+            swept.max[axis] += distance;
+        } else {
+            swept.min[axis] += distance;
+        }
+
+        let first = swept.min.map(|component| component.floor() as i32);
+        let last = swept.max.map(|component| component.ceil() as i32 - 1);
+        let mut allowed = distance;
+        for x in first.x..=last.x {
+            for y in first.y..=last.y {
+                for z in first.z..=last.z {
+                    let block = na::Vector3::new(x, y, z);
+                    let block_min = block.map(|component| component as f32);
+                    let overlaps_across = (0..3).filter(|&other| other != axis).all(|other| {
+                        self.min[other] < block_min[other] + 1.0 - COLLISION_EPSILON
+                            && self.max[other] > block_min[other] + COLLISION_EPSILON
+                    });
+                    if !overlaps_across || !is_solid(block) {
+                        continue;
+                    }
+
+                    if distance > 0.0 {
+                        let gap = block_min[axis] - self.max[axis];
+                        if gap >= -COLLISION_EPSILON {
+                            allowed = allowed.min(gap.max(0.0));
+                        }
+                    } else {
+                        let gap = block_min[axis] + 1.0 - self.min[axis];
+                        if gap <= COLLISION_EPSILON {
+                            allowed = allowed.max(gap.min(0.0));
+                        }
+                    }
+                }
+            }
+        }
+        allowed
+    }
+}
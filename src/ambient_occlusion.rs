@@ -0,0 +1,77 @@
+use crate::{
+    block_registry::BlockRegistryError,
+    chunk::ChunkNeighborhood,
+    cube::{face_neighbor, Face, FACE_AXES},
+};
+
+/// Brightness of a face corner for each ambient occlusion level, from fully
+/// occluded to open
+pub const AMBIENT_OCCLUSION_BRIGHTNESS: [f32; 4] = [0.45, 0.65, 0.82, 1.0];
+
+/// Ambient occlusion level of each corner of a face, indexed by
+/// `u + 2 * v` where `u` and `v` are the corner's side along the face's
+/// [FACE_AXES]. Levels go from 0, fully occluded, to 3, open.
+pub type FaceOcclusion = [u8; 4];
+
+/// Ambient occlusion of the face `face_bit` of the block at `position`.
+///
+/// Each corner looks at the three blocks touching it in the layer in front of
+/// the face: the two along the face's edges and the one diagonally across.
+/// Two solid edge blocks fully occlude the corner, as the diagonal one can't
+/// be seen between them.
+pub fn face_occlusion(
+    neighborhood: &ChunkNeighborhood,
+    position: [i32; 3],
+    face_bit: u8,
+) -> Result<FaceOcclusion, BlockRegistryError> {
+    let (_, _, u_axis, v_axis) = *FACE_AXES
+        .iter()
+        .find(|(bit, ..)| *bit == face_bit)
+        .expect("Unknown face bit");
+    let front = face_neighbor(position, face_bit);
+    let occludes = |u_offset: i32, v_offset: i32| {
+        let mut block = front;
+        block[u_axis] += u_offset;
+        block[v_axis] += v_offset;
+        neighborhood.occludes(block)
+    };
+
+    let mut occlusion = [0; 4];
+    for (corner, level) in occlusion.iter_mut().enumerate() {
+        let u_offset = if corner & 1 == 0 { -1 } else { 1 };
+        let v_offset = if corner & 2 == 0 { -1 } else { 1 };
+        let side_u = occludes(u_offset, 0)?;
+        let side_v = occludes(0, v_offset)?;
+        let diagonal = occludes(u_offset, v_offset)?;
+        *level = if side_u && side_v {
+            0
+        } else {
+            3 - side_u as u8 - side_v as u8 - diagonal as u8
+        };
+    }
+    Ok(occlusion)
+}
+
+/// Writes the corner brightness of `occlusion` into the first component of
+/// each vertex's `bitangent`, which the chunk shader multiplies the color by.
+/// `origin` is the corner of the face with the lowest coordinates.
+pub fn shade_face(face: &mut Face, face_bit: u8, origin: [f32; 3], occlusion: FaceOcclusion) {
+    let (_, _, u_axis, v_axis) = *FACE_AXES
+        .iter()
+        .find(|(bit, ..)| *bit == face_bit)
+        .expect("Unknown face bit");
+    for vertex in face {
+        let u = (vertex.position[u_axis] > origin[u_axis]) as usize;
+        let v = (vertex.position[v_axis] > origin[v_axis]) as usize;
+        vertex.bitangent[0] = AMBIENT_OCCLUSION_BRIGHTNESS[occlusion[u + 2 * v] as usize];
+    }
+}
+
+/// Whether a shaded face should be split along its `1 - 3` diagonal instead
+/// of `0 - 2`. The diagonal runs through the darker pair of corners, so a
+/// single occluded corner fades evenly into both triangles instead of ending
+/// in a hard line.
+pub fn flip_diagonal(face: &Face) -> bool {
+    let brightness = |vertex: usize| face[vertex].bitangent[0];
+    brightness(0) + brightness(2) > brightness(1) + brightness(3)
+}
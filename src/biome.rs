@@ -0,0 +1,127 @@
+/// Every kind of terrain, picked for each column from its temperature and
+/// humidity, see [Biome::from_climate]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Mountains,
+    Ocean,
+}
+
+pub const BIOMES: [Biome; 5] = [
+    Biome::Plains,
+    Biome::Forest,
+    Biome::Desert,
+    Biome::Mountains,
+    Biome::Ocean,
+];
+
+/// How a biome's terrain is shaped and what it is made of. Block and
+/// structure names are looked up in their registries when the terrain
+/// generator is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeDefinition {
+    /// Surface height where the broad terrain noise is zero
+    pub base_height: f64,
+    /// How far hills rise above and valleys sink below
+    /// [base_height](BiomeDefinition::base_height)
+    pub hill_height: f64,
+    /// Height of the small bumps on top of the hills
+    pub roughness: f64,
+    /// Top block of every column that ends above the water
+    pub surface: &'static str,
+    /// Top block of every column that ends at or under the water
+    pub shore: &'static str,
+    /// Blocks between the surface and the stone
+    pub filler: &'static str,
+    /// Shown in place of the surface when the terrain is colored by biome
+    pub debug_block: &'static str,
+    /// Structures that can start on top of a column, each with its chance of
+    /// starting there. Only columns above the water get one.
+    pub structures: &'static [(&'static str, f64)],
+}
+
+impl Biome {
+    /// Biome for a temperature and humidity, both roughly in `[-1, 1]`. Very
+    /// humid places are under water, hot and dry ones are deserts and cold
+    /// ones are mountains. What is left is forest where it is humid enough
+    /// and plains everywhere else.
+    pub fn from_climate(temperature: f64, humidity: f64) -> Self {
+        if humidity > 0.2 {
+            Biome::Ocean
+        } else if temperature > 0.15 && humidity < -0.05 {
+            Biome::Desert
+        } else if temperature < -0.2 {
+            Biome::Mountains
+        } else if humidity > 0.0 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    pub const fn definition(self) -> BiomeDefinition {
+        match self {
+            Biome::Plains => BiomeDefinition {
+                base_height: 68.0,
+                hill_height: 8.0,
+                roughness: 2.0,
+                surface: "grass",
+                shore: "sand",
+                filler: "dirt",
+                debug_block: "plains_biome",
+                structures: &[("oak_tree", 0.002), ("boulder", 0.001), ("ruin", 0.0004)],
+            },
+            Biome::Forest => BiomeDefinition {
+                base_height: 70.0,
+                hill_height: 14.0,
+                roughness: 4.0,
+                surface: "grass",
+                shore: "dirt",
+                filler: "dirt",
+                debug_block: "forest_biome",
+                structures: &[
+                    ("oak_tree", 0.02),
+                    ("tall_oak_tree", 0.02),
+                    ("boulder", 0.002),
+                ],
+            },
+            Biome::Desert => BiomeDefinition {
+                base_height: 67.0,
+                hill_height: 6.0,
+                roughness: 3.0,
+                surface: "sand",
+                shore: "sand",
+                filler: "sand",
+                debug_block: "desert_biome",
+                structures: &[
+                    ("small_cactus", 0.002),
+                    ("cactus", 0.002),
+                    ("tall_cactus", 0.002),
+                    ("ruin", 0.0008),
+                ],
+            },
+            Biome::Mountains => BiomeDefinition {
+                base_height: 96.0,
+                hill_height: 48.0,
+                roughness: 8.0,
+                surface: "stone",
+                shore: "stone",
+                filler: "stone",
+                debug_block: "mountains_biome",
+                structures: &[("boulder", 0.004)],
+            },
+            Biome::Ocean => BiomeDefinition {
+                base_height: 46.0,
+                hill_height: 8.0,
+                roughness: 3.0,
+                surface: "sand",
+                shore: "sand",
+                filler: "sand",
+                debug_block: "ocean_biome",
+                structures: &[],
+            },
+        }
+    }
+}
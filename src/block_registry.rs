@@ -0,0 +1,168 @@
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::light::MAX_LIGHT_LEVEL;
+
+pub const BLOCK_DEFINITIONS_PATH: &str = "blocks.ron";
+
+lazy_static! {
+    pub static ref BLOCK_REGISTRY: BlockRegistry = BlockRegistry::load(BLOCK_DEFINITIONS_PATH)
+        .unwrap_or_else(|err| panic!("Failed to load {BLOCK_DEFINITIONS_PATH}: {err}"));
+}
+
+/// Atlas tiles used by each face of a block, as `(column, row)`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct FaceTextures {
+    pub top: (u32, u32),
+    pub side: (u32, u32),
+    pub bottom: (u32, u32),
+}
+
+/// How a block's faces are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum RenderLayer {
+    #[default]
+    Opaque,
+    /// Opaque texels with fully see-through holes, like leaves. Drawn after
+    /// the opaque geometry, the holes are alpha tested away.
+    Cutout,
+    /// Blended over whatever is behind it, like water and glass. Drawn after
+    /// the opaque geometry, back to front. Faces between two of the same
+    /// block are culled.
+    Transparent,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BlockDefinition {
+    pub id: u16,
+    pub name: String,
+    /// Blocks without textures, like air, produce no geometry
+    pub textures: Option<FaceTextures>,
+    pub solid: bool,
+    /// Faces of neighboring blocks are only visible through transparent blocks
+    pub transparent: bool,
+    /// Level of block light the block gives off, up to [MAX_LIGHT_LEVEL]
+    #[serde(default)]
+    pub light_emission: u8,
+    /// Blocks in any layer but [RenderLayer::Opaque] must be transparent
+    #[serde(default)]
+    pub render_layer: RenderLayer,
+}
+
+#[derive(Debug)]
+pub enum BlockRegistryError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    DuplicateId(u16),
+    DuplicateName(String),
+    LightEmissionTooHigh(String),
+    SeeThroughBlockNotTransparent(String),
+    UnknownId(u16),
+    UnknownName(String),
+}
+
+impl Display for BlockRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockRegistryError::Io(err) => write!(f, "could not read block definitions: {err}"),
+            BlockRegistryError::Parse(err) => write!(f, "invalid block definitions: {err}"),
+            BlockRegistryError::DuplicateId(id) => write!(f, "block id {id} is defined twice"),
+            BlockRegistryError::DuplicateName(name) => {
+                write!(f, "block name \"{name}\" is defined twice")
+            }
+            BlockRegistryError::LightEmissionTooHigh(name) => write!(
+                f,
+                "block \"{name}\" gives off more light than the maximum of {MAX_LIGHT_LEVEL}"
+            ),
+            BlockRegistryError::SeeThroughBlockNotTransparent(name) => write!(
+                f,
+                "block \"{name}\" isn't opaque, so it has to be transparent"
+            ),
+            BlockRegistryError::UnknownId(id) => write!(f, "no block is registered with id {id}"),
+            BlockRegistryError::UnknownName(name) => {
+                write!(f, "no block is registered with name \"{name}\"")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockRegistryError {}
+
+/// Maps the numeric ids stored in chunks to their block definitions
+#[derive(Debug)]
+pub struct BlockRegistry {
+    /// Indexed by block id
+    blocks: Vec<Option<BlockDefinition>>,
+    ids_by_name: HashMap<String, u16>,
+}
+
+impl BlockRegistry {
+    pub fn new(definitions: Vec<BlockDefinition>) -> Result<Self, BlockRegistryError> {
+        let block_count = definitions
+            .iter()
+            .map(|definition| definition.id as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut blocks: Vec<Option<BlockDefinition>> = vec![None; block_count];
+        let mut ids_by_name = HashMap::with_capacity(definitions.len());
+
+        for definition in definitions {
+            if definition.light_emission > MAX_LIGHT_LEVEL {
+                return Err(BlockRegistryError::LightEmissionTooHigh(definition.name));
+            }
+            if definition.render_layer != RenderLayer::Opaque && !definition.transparent {
+                return Err(BlockRegistryError::SeeThroughBlockNotTransparent(
+                    definition.name,
+                ));
+            }
+            if ids_by_name
+                .insert(definition.name.clone(), definition.id)
+                .is_some()
+            {
+                return Err(BlockRegistryError::DuplicateName(definition.name));
+            }
+            let slot = &mut blocks[definition.id as usize];
+            if slot.is_some() {
+                return Err(BlockRegistryError::DuplicateId(definition.id));
+            }
+            *slot = Some(definition);
+        }
+
+        Ok(BlockRegistry {
+            blocks,
+            ids_by_name,
+        })
+    }
+
+    pub fn parse(source: &str) -> Result<Self, BlockRegistryError> {
+        let definitions: Vec<BlockDefinition> =
+            ron::from_str(source).map_err(BlockRegistryError::Parse)?;
+        Self::new(definitions)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BlockRegistryError> {
+        let source = std::fs::read_to_string(path).map_err(BlockRegistryError::Io)?;
+        Self::parse(&source)
+    }
+
+    pub fn get(&self, id: u16) -> Result<&BlockDefinition, BlockRegistryError> {
+        self.blocks
+            .get(id as usize)
+            .and_then(|definition| definition.as_ref())
+            .ok_or(BlockRegistryError::UnknownId(id))
+    }
+
+    pub fn id_of(&self, name: &str) -> Result<u16, BlockRegistryError> {
+        self.ids_by_name
+            .get(name)
+            .copied()
+            .ok_or_else(|| BlockRegistryError::UnknownName(name.to_string()))
+    }
+
+    #[allow(unused)]
+    pub fn iter(&self) -> impl Iterator<Item = &BlockDefinition> {
+        self.blocks.iter().flatten()
+    }
+}
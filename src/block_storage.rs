@@ -0,0 +1,262 @@
+use std::ops::Index;
+
+use crate::{
+    block_registry::{BlockRegistryError, BLOCK_REGISTRY},
+    chunk::{BLOCK_COUNT, HORIZONTAL_SLICE_SIZE, Y_SIZE},
+};
+
+/// Number of horizontal layers in a [PalettedSection]
+pub const SECTION_HEIGHT: usize = 16;
+pub const SECTION_COUNT: usize = Y_SIZE / SECTION_HEIGHT;
+pub const SECTION_BLOCK_COUNT: usize = HORIZONTAL_SLICE_SIZE * SECTION_HEIGHT;
+
+/// A set of sections with one bit for each, from the bottom up
+pub type SectionMask = u16;
+
+pub const ALL_SECTIONS: SectionMask =
+    SectionMask::MAX >> (SectionMask::BITS as usize - SECTION_COUNT);
+
+/// Sections whose meshes can show the block at height `y`: the block's own
+/// section, and the section above or below when the block is at its edge
+pub fn sections_around(y: usize) -> SectionMask {
+    [y.checked_sub(1), Some(y), Some(y + 1)]
+        .into_iter()
+        .flatten()
+        .filter(|&y| y < Y_SIZE)
+        .fold(0, |mask, y| mask | 1 << (y / SECTION_HEIGHT))
+}
+
+/// What a section holds, as far as meshing is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionFill {
+    /// Only blocks without textures, nothing to mesh
+    Empty,
+    /// Only blocks that aren't transparent, so faces next to the section are
+    /// all hidden
+    Full,
+    Mixed,
+}
+
+/// Blocks of [SECTION_HEIGHT] layers of a chunk.
+///
+/// Every block id that appears in the section is stored once in a palette,
+/// and each block only stores its index into the palette using as few bits as
+/// the palette's size needs. A section of a single block type needs no
+/// indices at all. The palette grows, and the indices are repacked wider,
+/// whenever a new block type is set. Palette entries no block uses anymore
+/// are reused, and a section that is back to a single block type drops its
+/// indices.
+#[derive(Debug, Clone)]
+pub struct PalettedSection {
+    palette: Vec<u16>,
+    /// Number of blocks using each palette entry
+    counts: Vec<u16>,
+    /// Bits per palette index, 0 while the palette holds a single block
+    bits: u32,
+    /// Palette indices packed into words. An index never spans two words.
+    words: Vec<u64>,
+}
+
+impl PalettedSection {
+    pub fn filled(block: u16) -> Self {
+        PalettedSection {
+            palette: vec![block],
+            counts: vec![SECTION_BLOCK_COUNT as u16],
+            bits: 0,
+            words: Vec::new(),
+        }
+    }
+
+    /// Block ids the section can hold without growing its indices, including
+    /// ones no block uses anymore
+    #[allow(unused)]
+    pub fn palette(&self) -> &[u16] {
+        &self.palette
+    }
+
+    /// Every block id used in the section
+    pub fn blocks(&self) -> impl Iterator<Item = u16> + '_ {
+        self.palette
+            .iter()
+            .zip(&self.counts)
+            .filter(|(_, &count)| count > 0)
+            .map(|(&block, _)| block)
+    }
+
+    pub fn fill(&self) -> Result<SectionFill, BlockRegistryError> {
+        let mut empty = true;
+        let mut full = true;
+        for block in self.blocks() {
+            let block = BLOCK_REGISTRY.get(block)?;
+            empty &= block.textures.is_none();
+            full &= !block.transparent;
+        }
+        Ok(if empty {
+            SectionFill::Empty
+        } else if full {
+            SectionFill::Full
+        } else {
+            SectionFill::Mixed
+        })
+    }
+
+    /// Bits each block uses to store its palette index
+    #[allow(unused)]
+    pub fn bits_per_block(&self) -> u32 {
+        self.bits
+    }
+
+    fn palette_index(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits as usize;
+        let shift = (index % per_word) as u32 * self.bits;
+        ((self.words[index / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    fn set_palette_index(&mut self, index: usize, palette_index: usize) {
+        let per_word = 64 / self.bits as usize;
+        let shift = (index % per_word) as u32 * self.bits;
+        let mask = ((1 << self.bits) - 1) << shift;
+        let word = &mut self.words[index / per_word];
+        *word = *word & !mask | (palette_index as u64) << shift;
+    }
+
+    /// Id of the block at `index` inside the section
+    pub fn get(&self, index: usize) -> u16 {
+        self.palette[self.palette_index(index)]
+    }
+
+    pub fn set(&mut self, index: usize, block: u16) {
+        let old_palette_index = self.palette_index(index);
+        if self.palette[old_palette_index] == block {
+            return;
+        }
+        self.counts[old_palette_index] -= 1;
+
+        let palette_index = match self.palette.iter().position(|&entry| entry == block) {
+            Some(palette_index) => palette_index,
+            None => match self.counts.iter().position(|&count| count == 0) {
+                Some(unused) => {
+                    self.palette[unused] = block;
+                    unused
+                }
+                None => {
+                    self.palette.push(block);
+                    self.counts.push(0);
+                    if self.palette.len() > 1 << self.bits {
+                        let bits = usize::BITS - (self.palette.len() - 1).leading_zeros();
+                        self.repack(bits);
+                    }
+                    self.palette.len() - 1
+                }
+            },
+        };
+        self.counts[palette_index] += 1;
+
+        if self.counts[palette_index] as usize == SECTION_BLOCK_COUNT {
+            *self = PalettedSection::filled(block);
+        } else {
+            self.set_palette_index(index, palette_index);
+        }
+    }
+
+    /// Stores every palette index again with `bits` bits
+    fn repack(&mut self, bits: u32) {
+        let palette_indices: Vec<usize> = (0..SECTION_BLOCK_COUNT)
+            .map(|index| self.palette_index(index))
+            .collect();
+        self.bits = bits;
+        self.words = vec![0; SECTION_BLOCK_COUNT.div_ceil(64 / bits as usize)];
+        for (index, palette_index) in palette_indices.into_iter().enumerate() {
+            self.set_palette_index(index, palette_index);
+        }
+    }
+
+    /// Bytes the section keeps on the heap
+    #[allow(unused)]
+    pub fn heap_size(&self) -> usize {
+        (self.palette.capacity() + self.counts.capacity()) * std::mem::size_of::<u16>()
+            + self.words.capacity() * std::mem::size_of::<u64>()
+    }
+}
+
+/// Block ids of a chunk, indexed by [Chunk::block_index](crate::chunk::Chunk::block_index)
+/// and stored as [SECTION_COUNT] [PalettedSection]s from the bottom up.
+///
+/// Reading a block by indexing returns a reference into its section's
+/// palette. Blocks are changed with [set](BlockArray::set).
+#[derive(Debug, Clone)]
+pub struct BlockArray {
+    sections: Vec<PalettedSection>,
+}
+
+impl BlockArray {
+    pub fn filled(block: u16) -> Self {
+        BlockArray {
+            sections: vec![PalettedSection::filled(block); SECTION_COUNT],
+        }
+    }
+
+    /// Packs a flat array of [BLOCK_COUNT] block ids
+    pub fn from_ids(ids: &[u16]) -> Self {
+        assert_eq!(ids.len(), BLOCK_COUNT, "A chunk has {BLOCK_COUNT} blocks");
+        let mut blocks = BlockArray::filled(ids[0]);
+        for (index, &block) in ids.iter().enumerate() {
+            blocks.set(index, block);
+        }
+        blocks
+    }
+
+    pub fn get(&self, index: usize) -> u16 {
+        self.sections[index / SECTION_BLOCK_COUNT].get(index % SECTION_BLOCK_COUNT)
+    }
+
+    pub fn set(&mut self, index: usize, block: u16) {
+        self.sections[index / SECTION_BLOCK_COUNT].set(index % SECTION_BLOCK_COUNT, block);
+    }
+
+    /// Every block id in [Chunk::block_index](crate::chunk::Chunk::block_index) order
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..BLOCK_COUNT).map(|index| self.get(index))
+    }
+
+    #[allow(unused)]
+    pub fn to_vec(&self) -> Vec<u16> {
+        self.iter().collect()
+    }
+
+    pub fn sections(&self) -> &[PalettedSection] {
+        &self.sections
+    }
+
+    /// Bytes used by the blocks, including everything kept on the heap
+    #[allow(unused)]
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.sections.capacity() * std::mem::size_of::<PalettedSection>()
+            + self
+                .sections
+                .iter()
+                .map(PalettedSection::heap_size)
+                .sum::<usize>()
+    }
+}
+
+impl Index<usize> for BlockArray {
+    type Output = u16;
+
+    fn index(&self, index: usize) -> &u16 {
+        let section = &self.sections[index / SECTION_BLOCK_COUNT];
+        &section.palette[section.palette_index(index % SECTION_BLOCK_COUNT)]
+    }
+}
+
+/// Chunks are equal when they hold the same blocks, however their palettes
+/// are ordered
+impl PartialEq for BlockArray {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
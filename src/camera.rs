@@ -0,0 +1,66 @@
+use nalgebra as na;
+
+/// Shape of the camera's view, matching the projection the camera was
+/// created with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Projection {
+    /// Vertical field of view in degrees
+    pub fovy: f32,
+    /// Width of the view divided by its height
+    pub aspect: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection {
+            fovy: 45.0,
+            aspect: 1.5,
+            znear: 0.01,
+            zfar: 1200.0,
+        }
+    }
+}
+
+/// Turns OpenGL's clip space depth of `-1..1`, which nalgebra projects to,
+/// into wgpu's `0..1`
+#[rustfmt::skip]
+const OPENGL_TO_WGPU: na::Matrix4<f32> = na::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
+
+impl Projection {
+    /// Takes world positions into the clip space of a camera at `eye`
+    /// looking along `yaw` and `pitch`, seeing what
+    /// [Frustum::new](crate::frustum::Frustum::new) bounds
+    pub fn view_projection(&self, eye: na::Vector3<f32>, yaw: f32, pitch: f32) -> na::Matrix4<f32> {
+        let (forward, _, up) = camera_axes(yaw, pitch);
+        let view = na::Matrix4::look_at_rh(&eye.into(), &(eye + forward).into(), &up);
+        let projection =
+            na::Perspective3::new(self.aspect, self.fovy.to_radians(), self.znear, self.zfar);
+        OPENGL_TO_WGPU * projection.as_matrix() * view
+    }
+}
+
+/// Direction a camera with the given yaw and pitch, in radians, is looking in
+pub fn look_direction(yaw: f32, pitch: f32) -> na::Vector3<f32> {
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    na::Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw)
+}
+
+/// Forward, right and up directions of a camera looking along `yaw` and
+/// `pitch`, see [look_direction]. Looking straight up or down, right is
+/// still picked from the yaw.
+pub fn camera_axes(yaw: f32, pitch: f32) -> (na::Vector3<f32>, na::Vector3<f32>, na::Vector3<f32>) {
+    let forward = look_direction(yaw, pitch);
+    let right = forward
+        .cross(&na::Vector3::y())
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(|| na::Vector3::new(-yaw.sin(), 0.0, yaw.cos()));
+    (forward, right, right.cross(&forward))
+}
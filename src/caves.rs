@@ -0,0 +1,138 @@
+use nalgebra as na;
+
+use crate::{
+    block_storage::BlockArray,
+    chunk::{Chunk, X_SIZE, Z_SIZE},
+    noise::Fbm,
+    terrain::{FILLER_DEPTH, SEA_LEVEL},
+};
+
+/// Lowest height caves reach, so the bottom of the world stays closed
+pub const MIN_CAVE_HEIGHT: usize = 4;
+
+/// Cheese cave density above which blocks are carved, deep underground
+const CHEESE_THRESHOLD: f64 = 0.3;
+/// How much harder cheese caves get to carve for every block closer than
+/// [CHEESE_FADE_DEPTH] to the surface
+const CHEESE_FADE: f64 = 0.03;
+const CHEESE_FADE_DEPTH: usize = 12;
+/// Distance from the zero surface of both spaghetti noises within which
+/// tunnels are carved
+const SPAGHETTI_WIDTH: f64 = 0.035;
+/// Distance from the zero line of the ravine noise within which ravines are
+/// carved, at their deepest in the middle
+const RAVINE_WIDTH: f64 = 0.012;
+/// Ravine mask value above which ravines exist at all
+const RAVINE_RARITY: f64 = 0.25;
+const RAVINE_DEPTH: f64 = 40.0;
+
+/// Carves caves and ravines out of generated terrain.
+///
+/// Whether a block is carved only depends on its world position and the
+/// surface height of its column, so caves run on seamlessly across chunk
+/// borders.
+/// - Cheese caves are large open caverns where 3D density noise is high. They
+///   get rarer close to the surface but can still break through it, leaving
+///   overhangs.
+/// - Spaghetti caves are winding tunnels along the lines where two 3D noise
+///   fields are both close to zero.
+/// - Ravines are narrow cuts down from the surface along the zero line of a
+///   2D noise field, in the few places a second noise field allows them.
+///
+/// Columns that end under water keep [FILLER_DEPTH] blocks below their
+/// surface, so caves never open up under the water.
+#[derive(Debug, Clone, Copy)]
+pub struct CaveCarver {
+    cheese: Fbm,
+    spaghetti: [Fbm; 2],
+    ravine: Fbm,
+    ravine_mask: Fbm,
+}
+
+impl CaveCarver {
+    pub fn new(seed: u64) -> Self {
+        CaveCarver {
+            cheese: Fbm::new(seed.wrapping_add(10), 2, 1.0 / 64.0),
+            spaghetti: [
+                Fbm::new(seed.wrapping_add(11), 1, 1.0 / 48.0),
+                Fbm::new(seed.wrapping_add(12), 1, 1.0 / 48.0),
+            ],
+            ravine: Fbm::new(seed.wrapping_add(13), 2, 1.0 / 256.0),
+            ravine_mask: Fbm::new(seed.wrapping_add(14), 1, 1.0 / 512.0),
+        }
+    }
+
+    /// Lowest height a ravine through the column cuts down to, `None` if no
+    /// ravine runs through it
+    fn ravine_floor(&self, world_x: i32, world_z: i32, surface_height: usize) -> Option<usize> {
+        let (x, z) = (world_x as f64, world_z as f64);
+        if self.ravine_mask.get_2d(x, z) < RAVINE_RARITY {
+            return None;
+        }
+        let distance = self.ravine.get_2d(x, z).abs();
+        if distance >= RAVINE_WIDTH {
+            return None;
+        }
+        let depth = RAVINE_DEPTH * (1.0 - distance / RAVINE_WIDTH);
+        Some((surface_height.saturating_sub(depth as usize)).max(MIN_CAVE_HEIGHT))
+    }
+
+    /// Whether the block at a world position is carved out of a column whose
+    /// surface is at `surface_height`. Only blocks at or under the surface
+    /// are ever carved.
+    pub fn carves(&self, world_position: [i32; 3], surface_height: usize) -> bool {
+        let [world_x, y, world_z] = world_position;
+        let ceiling = if surface_height <= SEA_LEVEL {
+            surface_height.saturating_sub(FILLER_DEPTH + 1)
+        } else {
+            surface_height
+        };
+        if y < MIN_CAVE_HEIGHT as i32 || y > ceiling as i32 {
+            return false;
+        }
+
+        if surface_height > SEA_LEVEL
+            && self
+                .ravine_floor(world_x, world_z, surface_height)
+                .is_some_and(|floor| y >= floor as i32)
+        {
+            return true;
+        }
+
+        let (x, y_f, z) = (world_x as f64, y as f64, world_z as f64);
+        let depth = surface_height - y as usize;
+        let threshold =
+            CHEESE_THRESHOLD + CHEESE_FADE_DEPTH.saturating_sub(depth) as f64 * CHEESE_FADE;
+        if self.cheese.get_3d(x, y_f, z) > threshold {
+            return true;
+        }
+
+        // Tunnels are stretched out horizontally
+        self.spaghetti
+            .iter()
+            .all(|noise| noise.get_3d(x, y_f * 2.0, z).abs() < SPAGHETTI_WIDTH)
+    }
+
+    /// Replaces every carved block of a chunk with `air`. `surface_heights`
+    /// are indexed by `x * Z_SIZE + z`.
+    pub fn carve(
+        &self,
+        blocks: &mut BlockArray,
+        position: na::Vector2<i32>,
+        surface_heights: &[usize],
+        air: u16,
+    ) {
+        for x in 0..X_SIZE {
+            for z in 0..Z_SIZE {
+                let world_x = position.x * X_SIZE as i32 + x as i32;
+                let world_z = position.y * Z_SIZE as i32 + z as i32;
+                let surface_height = surface_heights[x * Z_SIZE + z];
+                for y in MIN_CAVE_HEIGHT..=surface_height {
+                    if self.carves([world_x, y as i32, world_z], surface_height) {
+                        blocks.set(Chunk::block_index(x, y, z), air);
+                    }
+                }
+            }
+        }
+    }
+}
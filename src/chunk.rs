@@ -1,20 +1,24 @@
-use std::{
-    fmt::Debug,
-    sync::{
-        atomic::{AtomicUsize, Ordering::Relaxed},
-        Arc, Mutex,
-    },
-};
+use std::fmt::Debug;
 
-use gamezap::model::{Mesh, MeshTransform, Vertex};
-use lazy_static::lazy_static;
+use gamezap::model::Vertex;
 use nalgebra as na;
-use threadpool::ThreadPool;
-use wgpu::util::DeviceExt;
 
 use crate::{
-    chunk_loader::{ALL_BLOCK_STATES, RENDERED_CHUNKS_LENGTH},
-    cube::{BlockWrapper, Blocks, Cube, MeshInfo, FACE_INDICES},
+    aabb::Aabb,
+    ambient_occlusion::{face_occlusion, flip_diagonal, shade_face, FaceOcclusion},
+    block_registry::{BlockDefinition, BlockRegistryError, RenderLayer, BLOCK_REGISTRY},
+    block_storage::{
+        BlockArray, SectionFill, SectionMask, ALL_SECTIONS, SECTION_BLOCK_COUNT, SECTION_COUNT,
+        SECTION_HEIGHT,
+    },
+    chunk_loader::{block_states, LOADED_CHUNKS, NEIGHBOR_OFFSETS},
+    cube::{
+        face_neighbor, tiled_face, Cube, Face, MeshInfo, FACE_AXES, FACE_INDICES, FACE_NEIGHBORS,
+        FLIPPED_FACE_INDICES,
+    },
+    fluid::FLUID_REGISTRY,
+    light::{light_face, LightArray, OPEN_SKY},
+    tick::SavedTick,
 };
 
 pub const X_SIZE: usize = 16;
@@ -24,37 +28,454 @@ pub const Z_SIZE: usize = 16;
 pub const HORIZONTAL_SLICE_SIZE: usize = X_SIZE * Z_SIZE;
 pub const BLOCK_COUNT: usize = Z_SIZE * X_SIZE * Y_SIZE;
 
-pub const MAX_VERTICES: usize = (24 * Z_SIZE * X_SIZE * Y_SIZE) / 2;
-pub const MAX_INDICES: usize = (36 * Z_SIZE * X_SIZE * Y_SIZE) / 2;
+/// Which of a chunk and the eight chunks around it holds a horizontal block
+/// position relative to the chunk: 0 for the chunk itself, otherwise one
+/// plus the neighbor's index in [NEIGHBOR_OFFSETS]. Also returns the position
+/// inside that chunk, or `None` if the position is outside all nine chunks.
+pub fn split_neighborhood_position(x: i32, z: i32) -> Option<(usize, usize, usize)> {
+    let side = |position: i32, size: usize| match position {
+        position if position < -(size as i32) || position >= 2 * size as i32 => None,
+        position if position < 0 => Some((-1, position + size as i32)),
+        position if position >= size as i32 => Some((1, position - size as i32)),
+        position => Some((0, position)),
+    };
+    let (x_side, x) = side(x, X_SIZE)?;
+    let (z_side, z) = side(z, Z_SIZE)?;
+
+    let slot = NEIGHBOR_OFFSETS
+        .iter()
+        .position(|&offset| offset == (x_side, z_side))
+        .map_or(0, |neighbor| neighbor + 1);
+    Some((slot, x as usize, z as usize))
+}
+
+/// Whether faces bordering a chunk that isn't loaded are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnloadedNeighbors {
+    /// Treat unloaded chunks as solid. Saves faces that may never be seen,
+    /// but leaves holes at the edge of the loaded area.
+    Hide,
+    /// Treat unloaded chunks as air, so the loaded area is closed off. The
+    /// faces are removed again once the neighbor loads and the chunk is
+    /// remeshed.
+    Show,
+}
+
+/// Blocks of a chunk together with the blocks of the eight chunks around it,
+/// everything meshing needs to know which faces are visible and how they are
+/// shaded. Neighbors that aren't loaded are `None`, see [UnloadedNeighbors].
+///
+/// Faces on the top of the world are always drawn and faces on the bottom of
+/// the world never are.
+#[derive(Clone, Copy)]
+pub struct ChunkNeighborhood<'a> {
+    pub blocks: &'a BlockArray,
+    pub negative_x: Option<&'a BlockArray>,
+    pub positive_x: Option<&'a BlockArray>,
+    pub negative_z: Option<&'a BlockArray>,
+    pub positive_z: Option<&'a BlockArray>,
+    /// Diagonal neighbors in the order `-x -z`, `+x -z`, `-x +z`, `+x +z`,
+    /// only used for ambient occlusion
+    pub corners: [Option<&'a BlockArray>; 4],
+    /// Light levels of the chunk followed by its neighbors in
+    /// [NEIGHBOR_OFFSETS] order. Faces looking into a chunk without light
+    /// levels are lit by open sky.
+    pub light: [Option<&'a LightArray>; 9],
+    pub unloaded_neighbors: UnloadedNeighbors,
+}
+
+impl ChunkNeighborhood<'_> {
+    /// Calls `f` with the neighborhood of the chunk stored at `chunk_index` in
+    /// [ALL_BLOCK_STATES](crate::chunk_loader::ALL_BLOCK_STATES) for
+    /// `position`, keeping every chunk involved read locked until it returns.
+    /// Neighbors are looked up in [LOADED_CHUNKS]. Light levels are copied
+    /// up front, so light updates never wait on meshing.
+    pub fn with_loaded<T>(
+        chunk_index: (u32, u32),
+        position: na::Vector2<i32>,
+        unloaded_neighbors: UnloadedNeighbors,
+        f: impl FnOnce(ChunkNeighborhood) -> T,
+    ) -> T {
+        let blocks = block_states(chunk_index);
+        let (neighbors, light) = {
+            let loaded_chunks = LOADED_CHUNKS.read().unwrap();
+            let chunk_at =
+                |(x, z): (i32, i32)| loaded_chunks.get(&(position + na::Vector2::new(x, z)));
+            let light: [Option<Box<LightArray>>; 9] = std::array::from_fn(|slot| {
+                let offset = slot.checked_sub(1).map_or((0, 0), |i| NEIGHBOR_OFFSETS[i]);
+                chunk_at(offset).map(|chunk| Box::new(*chunk.light.read().unwrap()))
+            });
+            (
+                NEIGHBOR_OFFSETS.map(|offset| chunk_at(offset).map(|chunk| chunk.blocks.clone())),
+                light,
+            )
+        };
+
+        let blocks = blocks.read().unwrap();
+        let [negative_x, positive_x, negative_z, positive_z, corners @ ..] = neighbors
+            .each_ref()
+            .map(|neighbor| neighbor.as_ref().map(|blocks| blocks.read().unwrap()));
+        f(ChunkNeighborhood {
+            blocks: &blocks,
+            negative_x: negative_x.as_deref(),
+            positive_x: positive_x.as_deref(),
+            negative_z: negative_z.as_deref(),
+            positive_z: positive_z.as_deref(),
+            corners: corners.each_ref().map(|corner| corner.as_deref()),
+            light: light.each_ref().map(|light| light.as_deref()),
+            unloaded_neighbors,
+        })
+    }
+
+    /// The chunk holding a horizontal position relative to this chunk, which
+    /// may be up to one block outside of it, and the position inside that
+    /// chunk. See [split_neighborhood_position] for the chunk's slot.
+    fn chunk_at(&self, x: i32, z: i32) -> (Option<&BlockArray>, usize, usize, usize) {
+        let (slot, x, z) =
+            split_neighborhood_position(x, z).expect("Position is outside the neighborhood");
+        let blocks = match slot {
+            0 => Some(self.blocks),
+            1 => self.negative_x,
+            2 => self.positive_x,
+            3 => self.negative_z,
+            4 => self.positive_z,
+            corner => self.corners[corner - 5],
+        };
+        (blocks, slot, x, z)
+    }
+
+    /// Whether the block at `position` darkens the corners of faces next to
+    /// it. Blocks in unloaded chunks and outside the world don't.
+    pub fn occludes(&self, position: [i32; 3]) -> Result<bool, BlockRegistryError> {
+        let [x, y, z] = position;
+        if !(0..Y_SIZE as i32).contains(&y) {
+            return Ok(false);
+        }
+        match self.chunk_at(x, z) {
+            (Some(blocks), _, x, z) => {
+                let block = blocks[Chunk::block_index(x, y as usize, z)];
+                Ok(!BLOCK_REGISTRY.get(block)?.transparent)
+            }
+            (None, ..) => Ok(false),
+        }
+    }
+
+    /// Light level of the block at `position`, which a face looking at it is
+    /// lit by. Blocks above the world are open sky and blocks below it are
+    /// dark.
+    pub fn light_at(&self, position: [i32; 3]) -> u8 {
+        let [x, y, z] = position;
+        if y < 0 {
+            return 0;
+        }
+        if y >= Y_SIZE as i32 {
+            return OPEN_SKY;
+        }
+        let (_, slot, x, z) = self.chunk_at(x, z);
+        self.light[slot].map_or(OPEN_SKY, |light| {
+            light[Chunk::block_index(x, y as usize, z)]
+        })
+    }
+
+    /// Height of the surface of the fluid at `position`, see
+    /// [FluidRegistry::surface_height](crate::fluid::FluidRegistry::surface_height).
+    /// `None` if there is no fluid or its chunk isn't loaded.
+    pub fn fluid_height(&self, position: [i32; 3]) -> Option<f32> {
+        let [x, y, z] = position;
+        if !(0..Y_SIZE as i32).contains(&y) {
+            return None;
+        }
+        let (blocks, _, x, z) = self.chunk_at(x, z);
+        let blocks = blocks?;
+        let y = y as usize;
+        let above = (y + 1 < Y_SIZE).then(|| blocks[Chunk::block_index(x, y + 1, z)]);
+        FLUID_REGISTRY.surface_height(blocks[Chunk::block_index(x, y, z)], above)
+    }
+
+    /// Whether a face of the `block` at `from` looking at `position` is
+    /// visible. Both may be up to one block outside the chunk.
+    ///
+    /// Faces are visible through transparent blocks, except for faces between
+    /// two of the same [RenderLayer::Transparent] block. Between two levels of
+    /// the same fluid only sides rising above a lower surface are visible,
+    /// and the top of a fluid that isn't full is always visible.
+    fn face_visible(
+        &self,
+        block: u16,
+        from: [i32; 3],
+        position: [i32; 3],
+    ) -> Result<bool, BlockRegistryError> {
+        let [x, y, z] = position;
+        if y < 0 {
+            return Ok(false);
+        }
+        if y >= Y_SIZE as i32 {
+            return Ok(true);
+        }
+
+        match self.chunk_at(x, z) {
+            (Some(blocks), _, x, z) => {
+                let neighbor = blocks[Chunk::block_index(x, y as usize, z)];
+                let own_height = self.fluid_height(from);
+                if FLUID_REGISTRY.same_fluid(block, neighbor) {
+                    return Ok(position[1] == from[1] && self.fluid_height(position) < own_height);
+                }
+                if position[1] > from[1] && own_height.is_some_and(|height| height < 1.0) {
+                    return Ok(true);
+                }
+                if neighbor == block
+                    && BLOCK_REGISTRY.get(block)?.render_layer == RenderLayer::Transparent
+                {
+                    return Ok(false);
+                }
+                Ok(BLOCK_REGISTRY.get(neighbor)?.transparent)
+            }
+            (None, ..) => Ok(self.unloaded_neighbors == UnloadedNeighbors::Show),
+        }
+    }
+
+    /// Whether none of a section's faces can be seen without looking at its
+    /// blocks: it holds nothing to mesh, or it is
+    /// [full](SectionFill::Full) and so is every section next to it
+    fn section_hidden(&self, section: usize) -> Result<bool, BlockRegistryError> {
+        let full = |blocks: &BlockArray, section: usize| {
+            Ok::<_, BlockRegistryError>(blocks.sections()[section].fill()? == SectionFill::Full)
+        };
+        match self.blocks.sections()[section].fill()? {
+            SectionFill::Empty => return Ok(true),
+            SectionFill::Mixed => return Ok(false),
+            SectionFill::Full => {}
+        }
+
+        // Faces on the bottom of the world are never drawn, faces on the top
+        // always are
+        if section + 1 == SECTION_COUNT || !full(self.blocks, section + 1)? {
+            return Ok(false);
+        }
+        if section > 0 && !full(self.blocks, section - 1)? {
+            return Ok(false);
+        }
+        for neighbor in [
+            self.negative_x,
+            self.positive_x,
+            self.negative_z,
+            self.positive_z,
+        ] {
+            let hidden = match neighbor {
+                Some(blocks) => full(blocks, section)?,
+                None => self.unloaded_neighbors == UnloadedNeighbors::Hide,
+            };
+            if !hidden {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Visible faces of a chunk, split by how they are drawn. See [RenderLayer].
+#[derive(Debug, Clone, Default)]
+pub struct ChunkFaces {
+    pub opaque: Vec<Face>,
+    pub cutout: Vec<Face>,
+    pub transparent: Vec<Face>,
+}
+
+impl ChunkFaces {
+    /// Every face of `faces`, in order
+    pub fn concat<'a>(faces: impl IntoIterator<Item = &'a ChunkFaces>) -> Self {
+        let mut all = ChunkFaces::default();
+        for faces in faces {
+            all.opaque.extend_from_slice(&faces.opaque);
+            all.cutout.extend_from_slice(&faces.cutout);
+            all.transparent.extend_from_slice(&faces.transparent);
+        }
+        all
+    }
+
+    pub fn to_meshes(&self) -> ChunkMeshes {
+        ChunkMeshes {
+            opaque: ChunkMesh::from_faces(&self.opaque),
+            cutout: ChunkMesh::from_faces(&self.cutout),
+            transparent: ChunkMesh::from_faces(&self.transparent),
+        }
+    }
+
+    fn layer_mut(&mut self, render_layer: RenderLayer) -> &mut Vec<Face> {
+        match render_layer {
+            RenderLayer::Opaque => &mut self.opaque,
+            RenderLayer::Cutout => &mut self.cutout,
+            RenderLayer::Transparent => &mut self.transparent,
+        }
+    }
+}
 
-pub const BLOCK_GROUP_SIZE: usize = 1;
+/// Vertex and index data of a chunk, ready to be uploaded with
+/// [ChunkBuffers::new](crate::chunk_renderer::ChunkBuffers::new)
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
 
-lazy_static! {
-    static ref ALL_INDICES: Box<[u32]> = (0..MAX_INDICES / 6)
-        .flat_map(|face_index| FACE_INDICES.map(|i| i + (4 * face_index as u32)))
-        .collect::<Vec<_>>()
-        .into_boxed_slice();
+/// The meshes of a chunk for each [RenderLayer], see [ChunkFaces]
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMeshes {
+    pub opaque: ChunkMesh,
+    pub cutout: ChunkMesh,
+    pub transparent: ChunkMesh,
 }
 
-pub type BlockArray = [u16; Z_SIZE * X_SIZE * Y_SIZE];
+impl ChunkMesh {
+    pub fn from_faces(faces: &[Face]) -> Self {
+        ChunkMesh {
+            vertices: faces.concat(),
+            indices: faces
+                .iter()
+                .enumerate()
+                .flat_map(|(face_index, face)| {
+                    let indices = if flip_diagonal(face) {
+                        FLIPPED_FACE_INDICES
+                    } else {
+                        FACE_INDICES
+                    };
+                    indices.map(|i| i + 4 * face_index as u32)
+                })
+                .collect(),
+        }
+    }
+
+    /// Reorders the faces so the ones furthest from `eye`, in the same space
+    /// as the vertices, come first. Blended faces have to be drawn in this
+    /// order to show through each other. Faces at the same distance keep
+    /// their order. Returns whether any face moved, so unchanged meshes
+    /// don't have to be uploaded again.
+    pub fn sort_back_to_front(&mut self, eye: na::Vector3<f32>) -> bool {
+        let distance = |face: usize| {
+            let vertices = &self.vertices[4 * face..4 * face + 4];
+            let center = vertices
+                .iter()
+                .map(|vertex| na::Vector3::from(vertex.position))
+                .sum::<na::Vector3<f32>>()
+                / 4.0;
+            (center - eye).norm_squared()
+        };
+        let mut faces: Vec<(usize, f32)> = (0..self.vertices.len() / 4)
+            .map(|face| (face, distance(face)))
+            .collect();
+        faces.sort_by(|a, b| b.1.total_cmp(&a.1));
+        if faces
+            .iter()
+            .enumerate()
+            .all(|(sorted_face, &(face, _))| sorted_face == face)
+        {
+            return false;
+        }
+
+        let vertices = faces
+            .iter()
+            .flat_map(|&(face, _)| self.vertices[4 * face..4 * face + 4].iter().copied())
+            .collect();
+        let indices = faces
+            .iter()
+            .enumerate()
+            .flat_map(|(sorted_face, &(face, _))| {
+                self.indices[6 * face..6 * face + 6]
+                    .iter()
+                    .map(move |index| index - 4 * face as u32 + 4 * sorted_face as u32)
+            })
+            .collect();
+        self.vertices = vertices;
+        self.indices = indices;
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshingMode {
+    /// One quad for every visible block face
+    Naive,
+    /// Coplanar faces of the same block are merged into larger quads, see
+    /// [tiled_face] for how their textures repeat
+    Greedy,
+}
+
+/// Furthest distance from the center of the loaded area, in chunks along
+/// either axis, at which [Lod::Full], [Lod::Half] and [Lod::Quarter] are used
+pub const LOD_DISTANCES: [u32; 3] = [4, 8, 12];
+
+/// How coarsely a chunk is meshed. Every level above [Lod::Full] draws cubes
+/// of [scale](Lod::scale) blocks as single cells, see
+/// [Chunk::gen_lod_faces].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lod {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+    Eighth,
+}
+
+impl Lod {
+    /// Edge length of a cell in blocks
+    pub const fn scale(self) -> usize {
+        match self {
+            Lod::Full => 1,
+            Lod::Half => 2,
+            Lod::Quarter => 4,
+            Lod::Eighth => 8,
+        }
+    }
+
+    /// Level for a chunk `distance` chunks away from the center of the loaded
+    /// area, see [LOD_DISTANCES]
+    pub fn for_distance(distance: u32) -> Self {
+        match LOD_DISTANCES
+            .iter()
+            .position(|&furthest| distance <= furthest)
+        {
+            Some(0) => Lod::Full,
+            Some(1) => Lod::Half,
+            Some(_) => Lod::Quarter,
+            None => Lod::Eighth,
+        }
+    }
+}
 
 pub struct Chunk {
     pub position: na::Vector2<i32>,
     pub chunk_index: (u32, u32),
-    pub atlas_material_index: u32,
-
+    /// Whether the blocks were edited and still have to be written to disk
+    pub dirty: bool,
+    /// Whether the blocks for the current position have been loaded into
+    /// [ALL_BLOCK_STATES](crate::chunk_loader::ALL_BLOCK_STATES)
+    pub loaded: bool,
+    /// Whether the chunk's saved data couldn't be read. Its terrain is
+    /// generated instead and it is never saved, so the data on disk isn't
+    /// overwritten.
+    pub unreadable: bool,
+    /// Faces of every section from the bottom up, each built on its own so
+    /// an edit only remeshes the sections it can change
+    pub section_faces: Vec<ChunkFaces>,
+    /// Level the chunk is meshed at, picked by the chunk loader from its
+    /// distance to the center
+    pub lod: Lod,
+    /// Scheduled ticks loaded with the blocks, handed to the chunk loader's
+    /// [TickThread](crate::tick::TickThread) once the chunk is meshed
+    pub loaded_ticks: Vec<SavedTick>,
 }
 
 impl Chunk {
-    pub fn new(
-        position: na::Vector2<i32>,
-        chunk_index: (u32, u32),
-        atlas_material_index: u32,
-    ) -> Self {
+    pub fn new(position: na::Vector2<i32>, chunk_index: (u32, u32)) -> Self {
         Chunk {
             position,
             chunk_index,
-            atlas_material_index,
+            dirty: false,
+            loaded: false,
+            unreadable: false,
+            section_faces: vec![ChunkFaces::default(); SECTION_COUNT],
+            lod: Lod::Full,
+            loaded_ticks: vec![],
         }
     }
 
@@ -118,193 +539,521 @@ impl Chunk {
         mesh
     }*/
 
-    fn query_block(chunk_index: (u32, u32), x: usize, y: usize, z: usize) -> Blocks {
-        let chunk_block_states = &ALL_BLOCK_STATES[chunk_index.0][chunk_index.1];
-        BlockWrapper[chunk_block_states[y * HORIZONTAL_SLICE_SIZE + x * X_SIZE + z]]
+    /// Position of a block inside a [BlockArray]
+    pub const fn block_index(x: usize, y: usize, z: usize) -> usize {
+        y * HORIZONTAL_SLICE_SIZE + x * X_SIZE + z
     }
 
-    /// Returns a bitmask of which faces have neighboring blocks
-    ///
-    /// From right to left: `1: -z, 2: +z, 4: -x, 8: +x, 16: +y, 32: -y`
-    fn query_neighbors(chunk_index: (u32, u32), x: usize, y: usize, z: usize) -> u8 {
-        let mut neighbors = 0b0000_0000;
+    /// Splits a world block position into the position of the chunk holding it
+    /// and the block's coordinates inside that chunk. Returns `None` above or
+    /// below the world.
+    pub fn split_world_position(
+        world_position: na::Vector3<i32>,
+    ) -> Option<(na::Vector2<i32>, (usize, usize, usize))> {
+        if world_position.y < 0 || world_position.y >= Y_SIZE as i32 {
+            return None;
+        }
+        let chunk_position = na::Vector2::new(
+            world_position.x.div_euclid(X_SIZE as i32),
+            world_position.z.div_euclid(Z_SIZE as i32),
+        );
+        Some((
+            chunk_position,
+            (
+                world_position.x.rem_euclid(X_SIZE as i32) as usize,
+                world_position.y as usize,
+                world_position.z.rem_euclid(Z_SIZE as i32) as usize,
+            ),
+        ))
+    }
 
-        if z > 0 {
-            if Self::query_block(chunk_index, x, y, z - 1) == Blocks::Null {
-                neighbors |= 0b0000_0001;
-            }
-        } else {
-            if chunk_index.1 > 0
-                && Self::query_block((chunk_index.0, chunk_index.1 - 1), x, y, Z_SIZE - 1)
-                    == Blocks::Null
-            {
-                neighbors |= 0b0000_0001;
+    fn query_block(
+        neighborhood: ChunkNeighborhood,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<&'static BlockDefinition, BlockRegistryError> {
+        BLOCK_REGISTRY.get(neighborhood.blocks[Self::block_index(x, y, z)])
+    }
+
+    /// Returns a bitmask of the faces that border a transparent block, see
+    /// [FACE_NEIGHBORS] for the bits
+    pub fn query_neighbors(
+        neighborhood: ChunkNeighborhood,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<u8, BlockRegistryError> {
+        let mut neighbors = 0b0000_0000;
+        let position = [x as i32, y as i32, z as i32];
+        for (face_bit, offset) in FACE_NEIGHBORS {
+            let neighbor = [
+                x as i32 + offset[0],
+                y as i32 + offset[1],
+                z as i32 + offset[2],
+            ];
+            if neighborhood.face_visible(
+                neighborhood.blocks[Self::block_index(x, y, z)],
+                position,
+                neighbor,
+            )? {
+                neighbors |= face_bit;
             }
         }
-        if z < Z_SIZE - 1 {
-            if Self::query_block(chunk_index, x, y, z + 1) == Blocks::Null {
-                neighbors |= 0b0000_0010;
-            }
-        } else {
-            if chunk_index.1 < RENDERED_CHUNKS_LENGTH as u32 - 1
-                && Self::query_block((chunk_index.0, chunk_index.1 + 1), x, y, 0) == Blocks::Null
-            {
-                neighbors |= 0b0000_0010;
+        Ok(neighbors)
+    }
+
+    pub fn gen_block_vertices(
+        neighborhood: ChunkNeighborhood,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<MeshInfo, BlockRegistryError> {
+        let block_type = Self::query_block(neighborhood, x, y, z)?;
+
+        if block_type.textures.is_some() {
+            let face_mask = Self::query_neighbors(neighborhood, x, y, z)?;
+            if face_mask != 0 {
+                // return MeshInfo::full(na::Vector3::new(x as f32, y as f32, z as f32));
+                let mut block = Cube::new(
+                    na::Vector3::new(x as f32, y as f32, z as f32),
+                    0,
+                    block_type,
+                    face_mask,
+                    true,
+                );
+
+                // Cube::new adds the faces in the order of their bits
+                let position = [x as i32, y as i32, z as i32];
+                let visible_faces = FACE_AXES
+                    .iter()
+                    .map(|axes| axes.0)
+                    .filter(|face_bit| face_mask & face_bit != 0);
+                for (face, face_bit) in block.mesh_info.faces.iter_mut().zip(visible_faces) {
+                    let occlusion = face_occlusion(&neighborhood, position, face_bit)?;
+                    shade_face(face, face_bit, [x as f32, y as f32, z as f32], occlusion);
+                    light_face(
+                        face,
+                        neighborhood.light_at(face_neighbor(position, face_bit)),
+                    );
+                }
+
+                // Fluids that aren't full get their top lowered to their surface
+                if let Some(height) = neighborhood
+                    .fluid_height(position)
+                    .filter(|&height| height < 1.0)
+                {
+                    for face in &mut block.mesh_info.faces[..block.mesh_info.face_count] {
+                        for vertex in face {
+                            if vertex.position[1] > y as f32 {
+                                vertex.position[1] = y as f32 + height;
+                            }
+                        }
+                    }
+                }
+
+                return Ok(block.mesh_info);
             }
         }
+        Ok(MeshInfo::init())
+    }
 
-        if x > 0 {
-            if Self::query_block(chunk_index, x - 1, y, z) == Blocks::Null {
-                neighbors |= 0b0000_0100;
-            }
-        } else {
-            if chunk_index.0 > 0
-                && Self::query_block((chunk_index.0 - 1, chunk_index.1), X_SIZE - 1, y, z)
-                    == Blocks::Null
-            {
-                neighbors |= 0b0000_0100;
+    /// One quad for every visible block face in a section
+    fn gen_naive_faces(
+        neighborhood: ChunkNeighborhood,
+        section: usize,
+    ) -> Result<ChunkFaces, BlockRegistryError> {
+        let mut faces = ChunkFaces::default();
+        for block_index in section * SECTION_BLOCK_COUNT..(section + 1) * SECTION_BLOCK_COUNT {
+            let y_pos = block_index / HORIZONTAL_SLICE_SIZE;
+            let x_pos = block_index % HORIZONTAL_SLICE_SIZE / X_SIZE;
+            let z_pos = block_index % HORIZONTAL_SLICE_SIZE % X_SIZE;
+
+            let faces_info = Self::gen_block_vertices(neighborhood, x_pos, y_pos, z_pos)?;
+            if faces_info.face_count > 0 {
+                let render_layer =
+                    Self::query_block(neighborhood, x_pos, y_pos, z_pos)?.render_layer;
+                faces
+                    .layer_mut(render_layer)
+                    .extend_from_slice(&faces_info.faces[..faces_info.face_count]);
             }
         }
-        if x < X_SIZE - 1 {
-            if Self::query_block(chunk_index, x + 1, y, z) == Blocks::Null {
-                neighbors |= 0b0000_1000;
+        Ok(faces)
+    }
+
+    /// Merges coplanar neighboring faces of the same block in a section into
+    /// larger quads.
+    ///
+    /// Each face direction is swept one slice at a time. Visible faces in a
+    /// slice are grown along the `u` axis first and then along `v` for as long
+    /// as every face in the next row belongs to the same block and has the
+    /// same ambient occlusion and light level.
+    fn gen_greedy_faces(
+        neighborhood: ChunkNeighborhood,
+        section: usize,
+    ) -> Result<ChunkFaces, BlockRegistryError> {
+        let block_ids = neighborhood.blocks;
+        let first_block = section * SECTION_BLOCK_COUNT;
+        let bottom = section * SECTION_HEIGHT;
+
+        // Indexed like the blocks, starting at the bottom of the section
+        let mut face_masks = vec![0_u8; SECTION_BLOCK_COUNT];
+        let mut faces = ChunkFaces::default();
+        for (section_index, face_mask) in face_masks.iter_mut().enumerate() {
+            let block_index = first_block + section_index;
+            let block = BLOCK_REGISTRY.get(block_ids[block_index])?;
+            if block.textures.is_none() {
+                continue;
             }
-        } else {
-            if chunk_index.0 < RENDERED_CHUNKS_LENGTH as u32 - 1
-                && Self::query_block((chunk_index.0 + 1, chunk_index.1), 0, y, z) == Blocks::Null
+            let y = block_index / HORIZONTAL_SLICE_SIZE;
+            let x = block_index % HORIZONTAL_SLICE_SIZE / X_SIZE;
+            let z = block_index % HORIZONTAL_SLICE_SIZE % X_SIZE;
+
+            // Fluids below their full height are meshed block by block
+            if neighborhood
+                .fluid_height([x as i32, y as i32, z as i32])
+                .is_some_and(|height| height < 1.0)
             {
-                neighbors |= 0b0000_1000;
+                let block_faces = Self::gen_block_vertices(neighborhood, x, y, z)?;
+                faces
+                    .layer_mut(block.render_layer)
+                    .extend_from_slice(&block_faces.faces[..block_faces.face_count]);
+                continue;
             }
+            *face_mask = Self::query_neighbors(neighborhood, x, y, z)?;
         }
 
-        if y > 0 {
-            if Self::query_block(chunk_index, x, y - 1, z) == Blocks::Null {
-                neighbors |= 0b0001_000;
+        let sizes = [X_SIZE, SECTION_HEIGHT, Z_SIZE];
+
+        for (face_bit, normal_axis, u_axis, v_axis) in FACE_AXES {
+            let (u_size, v_size) = (sizes[u_axis], sizes[v_axis]);
+            let mut slice_mask: Vec<Option<(u16, FaceOcclusion, u8)>> = vec![None; u_size * v_size];
+
+            for slice in 0..sizes[normal_axis] {
+                for v in 0..v_size {
+                    for u in 0..u_size {
+                        let mut position = [0; 3];
+                        position[normal_axis] = slice;
+                        position[u_axis] = u;
+                        position[v_axis] = v;
+                        let section_index =
+                            Self::block_index(position[0], position[1], position[2]);
+
+                        slice_mask[v * u_size + u] = if face_masks[section_index] & face_bit != 0 {
+                            position[1] += bottom;
+                            let position = position.map(|component| component as i32);
+                            Some((
+                                block_ids[first_block + section_index],
+                                face_occlusion(&neighborhood, position, face_bit)?,
+                                neighborhood.light_at(face_neighbor(position, face_bit)),
+                            ))
+                        } else {
+                            None
+                        };
+                    }
+                }
+
+                for v in 0..v_size {
+                    let mut u = 0;
+                    while u < u_size {
+                        let Some(face_key) = slice_mask[v * u_size + u] else {
+                            u += 1;
+                            continue;
+                        };
+
+                        let mut width = 1;
+                        while u + width < u_size
+                            && slice_mask[v * u_size + u + width] == Some(face_key)
+                        {
+                            width += 1;
+                        }
+
+                        let mut height = 1;
+                        while v + height < v_size
+                            && (u..u + width).all(|row_u| {
+                                slice_mask[(v + height) * u_size + row_u] == Some(face_key)
+                            })
+                        {
+                            height += 1;
+                        }
+
+                        for merged_v in v..v + height {
+                            for merged_u in u..u + width {
+                                slice_mask[merged_v * u_size + merged_u] = None;
+                            }
+                        }
+
+                        let mut position = na::Vector3::zeros();
+                        position[normal_axis] = slice as f32;
+                        position[u_axis] = u as f32;
+                        position[v_axis] = v as f32;
+                        position[1] += bottom as f32;
+                        let mut extents = na::Vector3::new(1.0, 1.0, 1.0);
+                        extents[u_axis] = width as f32;
+                        extents[v_axis] = height as f32;
+
+                        let (block_id, occlusion, light) = face_key;
+                        let block = BLOCK_REGISTRY.get(block_id)?;
+                        if let Some(mut face) = tiled_face(face_bit, position, extents, block) {
+                            shade_face(&mut face, face_bit, position.into(), occlusion);
+                            light_face(&mut face, light);
+                            faces.layer_mut(block.render_layer).push(face);
+                        }
+
+                        u += width;
+                    }
+                }
             }
-        } else {
-            neighbors |= 0b0001_0000;
         }
-        if y < Y_SIZE - 1 {
-            if Self::query_block(chunk_index, x, y + 1, z) == Blocks::Null {
-                neighbors |= 0b0010_0000;
+
+        Ok(faces)
+    }
+
+    /// Block drawn for the cube of `scale` blocks whose lowest corner is at
+    /// `origin`: the topmost block that hides what is behind it, or else the
+    /// topmost block with textures. Preferring hiding blocks keeps coarse
+    /// terrain from ever sinking below the blocks it stands for, and keeps
+    /// grass on top of hills. `None` if no block in the cube is drawn.
+    fn lod_cell(
+        blocks: &BlockArray,
+        origin: [usize; 3],
+        scale: usize,
+    ) -> Result<Option<u16>, BlockRegistryError> {
+        let mut see_through = None;
+        for y in (origin[1]..origin[1] + scale).rev() {
+            for x in origin[0]..origin[0] + scale {
+                for z in origin[2]..origin[2] + scale {
+                    let block = blocks[Self::block_index(x, y, z)];
+                    let definition = BLOCK_REGISTRY.get(block)?;
+                    if definition.textures.is_none() {
+                        continue;
+                    }
+                    if !definition.transparent {
+                        return Ok(Some(block));
+                    }
+                    see_through.get_or_insert(block);
+                }
             }
-        } else {
-            neighbors |= 0b0010_0000;
         }
-        neighbors
+        Ok(see_through)
     }
 
-    pub const fn default_blocks() -> BlockArray {
-        let mut blocks = [[[1_u16; Z_SIZE]; X_SIZE]; Y_SIZE];
-        blocks[Y_SIZE - 1] = [[0; Z_SIZE]; X_SIZE];
-        unsafe { std::mem::transmute(blocks) }
-    }
+    /// One quad for every visible cell face in a section meshed at `lod`,
+    /// where every cell is drawn as the block picked by [Chunk::lod_cell].
+    ///
+    /// Faces between cells follow the same rules as faces between blocks.
+    /// Faces on the sides of the chunk are compared against the neighbor's
+    /// actual blocks instead and are drawn if any of them would show the
+    /// face, so they close the seam to neighbors meshed at a finer level.
+    /// Each face is lit by the brightest block in front of it and has no
+    /// ambient occlusion.
+    fn gen_lod_faces(
+        neighborhood: ChunkNeighborhood,
+        section: usize,
+        lod: Lod,
+    ) -> Result<ChunkFaces, BlockRegistryError> {
+        let scale = lod.scale();
+        let cells = [X_SIZE / scale, SECTION_HEIGHT / scale, Z_SIZE / scale];
+        let bottom = section * SECTION_HEIGHT;
 
-    pub fn gen_block_vertices<'a>(
-        chunk_index: (u32, u32),
-        x: usize,
-        y: usize,
-        z: usize,
-    ) -> MeshInfo {
-        // let chunk_index = chunk_index.0 * RENDERED_CHUNKS_LENGTH + chunk_index.1;
-        let block_type = Self::query_block(chunk_index, x, y, z);
+        // Cells of the section along with a layer of the sections above and
+        // below it, layer by layer from the bottom up
+        let layers = cells[1] + 2;
+        let mut grid = Vec::with_capacity(layers * cells[0] * cells[2]);
+        for layer in 0..layers {
+            let y = (bottom + layer * scale).checked_sub(scale);
+            for x in 0..cells[0] {
+                for z in 0..cells[2] {
+                    grid.push(match y {
+                        Some(y) if y < Y_SIZE => {
+                            Self::lod_cell(neighborhood.blocks, [x * scale, y, z * scale], scale)?
+                        }
+                        _ => None,
+                    });
+                }
+            }
+        }
+        let cell_at = |[x, y, z]: [i32; 3]| {
+            grid[((y + 1) as usize * cells[0] + x as usize) * cells[2] + z as usize]
+        };
 
-        if block_type != Blocks::Null {
-            let face_mask = Self::query_neighbors(chunk_index, x, y, z);
-            if face_mask != 0 {
-                // return MeshInfo::full(na::Vector3::new(x as f32, y as f32, z as f32));
-                let block = Cube::new(
-                    na::Vector3::new(x as f32, y as f32, z as f32),
-                    0,
-                    block_type,
-                    face_mask,
-                    true,
-                );
+        let mut faces = ChunkFaces::default();
+        for x in 0..cells[0] as i32 {
+            for y in 0..cells[1] as i32 {
+                for z in 0..cells[2] as i32 {
+                    let Some(block) = cell_at([x, y, z]) else {
+                        continue;
+                    };
+                    let definition = BLOCK_REGISTRY.get(block)?;
+                    let origin = [
+                        x * scale as i32,
+                        bottom as i32 + y * scale as i32,
+                        z * scale as i32,
+                    ];
 
-                return block.mesh_info;
-                // vertices_clone.lock().unwrap().push(
-                //     &block.mesh_info.vertices
-                //         [..block.mesh_info.vertex_count],
-                // );
-            } else {
-                return MeshInfo::init();
-            }
-        } else {
-            return MeshInfo::init();
-        }
-    }
-
-    pub fn create_mesh(&self, device: Arc<wgpu::Device>) -> Arc<Mesh> {
-        // let pool = rayon::ThreadPoolBuilder::new()
-        //     .num_threads(8)
-        //     .build()
-        //     .unwrap();
-        let pool = ThreadPool::new(6);
-
-        let vertices_2d: Arc<Mutex<Vec<[Vertex; 4]>>> =
-            Arc::new(Mutex::new(Vec::with_capacity(MAX_VERTICES)));
-        let faces_count = Arc::new(AtomicUsize::new(0));
-
-        let faces_count_clone = faces_count.clone();
-        (0..BLOCK_COUNT / BLOCK_GROUP_SIZE)
-            .into_iter()
-            .for_each(|blocks_group_start| {
-                let vertices_2d = vertices_2d.clone();
-                let chunk_index = self.chunk_index;
-                let faces_count = faces_count.clone();
-                // pool.execute(move || {
-                for block_index in blocks_group_start * BLOCK_GROUP_SIZE
-                    ..(blocks_group_start + 1) * BLOCK_GROUP_SIZE
-                {
-                    let y_pos = block_index / HORIZONTAL_SLICE_SIZE;
-                    let x_pos = block_index % HORIZONTAL_SLICE_SIZE / X_SIZE;
-                    let z_pos = block_index % HORIZONTAL_SLICE_SIZE % X_SIZE;
-
-                    let faces_info = Self::gen_block_vertices(chunk_index, x_pos, y_pos, z_pos);
-                    for face in &faces_info.faces[..faces_info.face_count] {
-                        vertices_2d.lock().unwrap().push(*face);
-                        faces_count.fetch_add(1, Relaxed);
+                    for (face_bit, normal_axis, u_axis, v_axis) in FACE_AXES {
+                        let neighbor = face_neighbor([x, y, z], face_bit);
+                        let positive = neighbor[normal_axis] > [x, y, z][normal_axis];
+                        let neighbor_y = bottom as i32 + neighbor[1] * scale as i32;
+                        let on_side = !(0..cells[0] as i32).contains(&neighbor[0])
+                            || !(0..cells[2] as i32).contains(&neighbor[2]);
+
+                        let mut visible = if neighbor_y < 0 {
+                            false
+                        } else if neighbor_y >= Y_SIZE as i32 {
+                            true
+                        } else if on_side {
+                            // Decided by the neighbor's blocks below
+                            false
+                        } else {
+                            match cell_at(neighbor) {
+                                Some(neighbor) => {
+                                    let hides_itself = (neighbor == block
+                                        && definition.render_layer == RenderLayer::Transparent)
+                                        || FLUID_REGISTRY.same_fluid(neighbor, block);
+                                    !hides_itself && BLOCK_REGISTRY.get(neighbor)?.transparent
+                                }
+                                None => true,
+                            }
+                        };
+
+                        // The blocks the face looks at, one layer in front of it
+                        let mut light = 0;
+                        for u in 0..scale as i32 {
+                            for v in 0..scale as i32 {
+                                let mut front = origin;
+                                front[normal_axis] += if positive { scale as i32 } else { -1 };
+                                front[u_axis] += u;
+                                front[v_axis] += v;
+                                light = light.max(neighborhood.light_at(front));
+                                if on_side && !visible {
+                                    let mut behind = front;
+                                    behind[normal_axis] += if positive { -1 } else { 1 };
+                                    visible = neighborhood.face_visible(block, behind, front)?;
+                                }
+                            }
+                        }
+                        if !visible {
+                            continue;
+                        }
+
+                        let position = na::Vector3::from(origin.map(|component| component as f32));
+                        let extents = na::Vector3::repeat(scale as f32);
+                        if let Some(mut face) = tiled_face(face_bit, position, extents, definition)
+                        {
+                            shade_face(&mut face, face_bit, position.into(), [3; 4]);
+                            light_face(&mut face, light);
+                            faces.layer_mut(definition.render_layer).push(face);
+                        }
                     }
                 }
-                // })
-            });
-        pool.join();
+            }
+        }
+        Ok(faces)
+    }
 
-        let faces_count = faces_count_clone.clone().load(Relaxed);
-        let vertices_count = faces_count * 4;
+    /// Builds the visible faces of one section of a chunk at `lod`, skipping
+    /// sections whose faces are all hidden. `meshing_mode` only applies to
+    /// [Lod::Full]. Only reads `neighborhood`, so it needs neither the loaded
+    /// chunks nor a GPU.
+    pub fn build_section_faces(
+        neighborhood: ChunkNeighborhood,
+        section: usize,
+        meshing_mode: MeshingMode,
+        lod: Lod,
+    ) -> Result<ChunkFaces, BlockRegistryError> {
+        if neighborhood.section_hidden(section)? {
+            return Ok(ChunkFaces::default());
+        }
+        match (lod, meshing_mode) {
+            (Lod::Full, MeshingMode::Naive) => Self::gen_naive_faces(neighborhood, section),
+            (Lod::Full, MeshingMode::Greedy) => Self::gen_greedy_faces(neighborhood, section),
+            (lod, _) => Self::gen_lod_faces(neighborhood, section, lod),
+        }
+    }
 
-        let position_x = self.position.x as f32;
-        let position_y = self.position.y as f32;
-        let vertices = vertices_2d.lock().unwrap()[..faces_count].concat();
-        let vertices_len = vertices_count;
+    /// Builds the faces of every section in `sections`, see
+    /// [Chunk::build_section_faces]
+    pub fn build_sections(
+        neighborhood: ChunkNeighborhood,
+        sections: SectionMask,
+        meshing_mode: MeshingMode,
+        lod: Lod,
+    ) -> Result<Vec<(usize, ChunkFaces)>, BlockRegistryError> {
+        (0..SECTION_COUNT)
+            .filter(|section| sections & 1 << section != 0)
+            .map(|section| {
+                Ok((
+                    section,
+                    Self::build_section_faces(neighborhood, section, meshing_mode, lod)?,
+                ))
+            })
+            .collect()
+    }
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Chunk 1 vert buff"),
-            usage: wgpu::BufferUsages::VERTEX,
-            contents: bytemuck::cast_slice(&vertices),
-        });
+    /// Builds the visible faces of a whole chunk at full detail, see
+    /// [Chunk::build_section_faces]
+    #[allow(unused)]
+    pub fn build_faces(
+        neighborhood: ChunkNeighborhood,
+        meshing_mode: MeshingMode,
+    ) -> Result<ChunkFaces, BlockRegistryError> {
+        let sections = Self::build_sections(neighborhood, ALL_SECTIONS, meshing_mode, Lod::Full)?;
+        Ok(ChunkFaces::concat(sections.iter().map(|(_, faces)| faces)))
+    }
 
-        let total_index_count = (vertices_len as f32 * 1.5) as usize;
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Chunk 1 index buff"),
-            usage: wgpu::BufferUsages::INDEX,
-            contents: bytemuck::cast_slice(&ALL_INDICES[..total_index_count]),
-        });
+    /// Builds the vertex and index data of a chunk, see [Chunk::build_faces]
+    #[allow(unused)]
+    pub fn build_mesh(
+        neighborhood: ChunkNeighborhood,
+        meshing_mode: MeshingMode,
+    ) -> Result<ChunkMeshes, BlockRegistryError> {
+        Ok(Self::build_faces(neighborhood, meshing_mode)?.to_meshes())
+    }
 
-        let mesh = Arc::new(Mesh::new(
-            &device,
-            "Chunk 1".to_string(),
-            vertex_buffer,
-            index_buffer,
-            total_index_count as u32,
-            MeshTransform::new(
-                na::Vector3::new(position_x * 16.0, 0.0, position_y * 16.0),
-                na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), 0.0),
-            ),
-            0,
-        ));
+    /// Keeps the faces of sections built by [Chunk::build_sections] in
+    /// place of their old faces
+    pub fn replace_sections(&mut self, sections: Vec<(usize, ChunkFaces)>) {
+        for (section, faces) in sections {
+            self.section_faces[section] = faces;
+        }
+    }
 
-        mesh
+    /// Vertex and index data of every section's faces together
+    pub fn meshes(&self) -> ChunkMeshes {
+        ChunkFaces::concat(&self.section_faces).to_meshes()
+    }
+
+    /// World space box around every section with faces, `None` if the chunk
+    /// has none
+    pub fn mesh_bounds(&self) -> Option<Aabb> {
+        let mut sections = self
+            .section_faces
+            .iter()
+            .enumerate()
+            .filter(|(_, faces)| {
+                !faces.opaque.is_empty()
+                    || !faces.cutout.is_empty()
+                    || !faces.transparent.is_empty()
+            })
+            .map(|(section, _)| section);
+        let bottom = sections.next()?;
+        let top = sections.next_back().unwrap_or(bottom);
+
+        let origin = na::Vector3::new(
+            (self.position.x * X_SIZE as i32) as f32,
+            0.0,
+            (self.position.y * Z_SIZE as i32) as f32,
+        );
+        Some(Aabb {
+            min: origin + na::Vector3::new(0.0, (bottom * SECTION_HEIGHT) as f32, 0.0),
+            max: origin
+                + na::Vector3::new(
+                    X_SIZE as f32,
+                    ((top + 1) * SECTION_HEIGHT) as f32,
+                    Z_SIZE as f32,
+                ),
+        })
     }
 }
 
@@ -1,29 +1,185 @@
 use std::{
-    borrow::Borrow,
-    cell::RefMut,
-    sync::{Arc, Mutex},
+    cell::{Cell, RefMut},
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Mutex, RwLock},
 };
 
-use gamezap::{model::MeshManager, FrameDependancy};
+use gamezap::FrameDependancy;
 use lazy_static::lazy_static;
 use nalgebra as na;
+use sdl2::keyboard::Scancode;
 
 use crate::{
-    chunk::{BlockArray, Chunk},
+    aabb::Aabb,
+    block_registry::BLOCK_REGISTRY,
+    block_storage::{sections_around, BlockArray, SectionMask, ALL_SECTIONS},
+    camera::{look_direction, Projection},
+    chunk::{Chunk, ChunkMesh, Lod, MeshingMode, UnloadedNeighbors, BLOCK_COUNT, X_SIZE, Z_SIZE},
+    chunk_mesher::ChunkMesher,
+    chunk_renderer::{ChunkBuffers, ChunkRenderer, VisibleMeshes},
+    frustum::Frustum,
+    light::{LightArray, LightRegion},
+    raycast::raycast,
+    region::{RegionStorage, RegionWriter, SavedChunk, WORLD_DIRECTORY},
     ring_buffer::RingBuffer2D,
+    terrain::{TERRAIN_GENERATOR, WORLD_SEED},
+    tick::TickThread,
 };
 
-pub const RENDER_DISTANCE: usize = 1;
-pub const RENDERED_CHUNKS_LENGTH: usize = 2 * RENDER_DISTANCE + 1;
+pub const DEFAULT_RENDER_DISTANCE: usize = 1;
+pub const MIN_RENDER_DISTANCE: usize = 1;
+pub const MAX_RENDER_DISTANCE: usize = 16;
+
+/// Number of finished chunk meshes uploaded to the GPU per frame
+pub const MESH_UPLOADS_PER_FRAME: usize = 4;
+
+/// How far away blocks can be broken or placed
+pub const REACH_DISTANCE: f32 = 8.0;
+
+/// Key that logs the [CullingStats] of the current frame
+pub const CULLING_STATS_KEY: Scancode = Scancode::F3;
 
 lazy_static! {
-    pub static ref ALL_BLOCK_STATES: RingBuffer2D<BlockArray> = RingBuffer2D::new(
-        (0..RENDERED_CHUNKS_LENGTH)
-            .map(|_| (0..RENDERED_CHUNKS_LENGTH)
-                .map(|_| Chunk::default_blocks())
-                .collect::<Vec<_>>())
-            .collect::<Vec<_>>()
-    );
+    /// Blocks of every loaded chunk, indexed by [Chunk::chunk_index]. Starts out
+    /// filled with air and is replaced whenever the render distance changes.
+    pub static ref ALL_BLOCK_STATES: RwLock<RingBuffer2D<Arc<RwLock<BlockArray>>>> =
+        RwLock::new(air_block_states(2 * DEFAULT_RENDER_DISTANCE + 1));
+
+    /// Every chunk that finished loading, by chunk position
+    pub static ref LOADED_CHUNKS: RwLock<HashMap<na::Vector2<i32>, LoadedChunk>> =
+        RwLock::new(HashMap::new());
+}
+
+#[derive(Clone)]
+pub struct LoadedChunk {
+    /// Shared with [ALL_BLOCK_STATES]
+    pub blocks: Arc<RwLock<BlockArray>>,
+    pub light: Arc<RwLock<LightArray>>,
+}
+
+fn air_block_states(length: usize) -> RingBuffer2D<Arc<RwLock<BlockArray>>> {
+    let air = BLOCK_REGISTRY
+        .id_of("air")
+        .expect("Chunks need an \"air\" block");
+    RingBuffer2D::new(
+        (0..length)
+            .map(|_| {
+                (0..length)
+                    .map(|_| Arc::new(RwLock::new(BlockArray::filled(air))))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Reads a chunk's blocks and scheduled ticks from its region file, or
+/// generates its blocks if the chunk was never saved. Also returns whether
+/// the saved chunk failed to be read, in which case it is generated too.
+pub fn load_chunk(region_storage: &RegionWriter, position: na::Vector2<i32>) -> (SavedChunk, bool) {
+    let generate = || SavedChunk {
+        blocks: TERRAIN_GENERATOR.generate(position),
+        ticks: vec![],
+    };
+    match region_storage.load_chunk(position) {
+        Ok(Some(chunk)) => (chunk, false),
+        Ok(None) => (generate(), false),
+        Err(err) => {
+            log::error!(
+                "Failed to load chunk {position:?}, generating it without saving it: {err}"
+            );
+            (generate(), true)
+        }
+    }
+}
+
+/// Offsets from a chunk to the eight chunks around it
+pub const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+
+/// Blocks of the chunk stored at `chunk_index` in [ALL_BLOCK_STATES]
+pub fn block_states(chunk_index: (u32, u32)) -> Arc<RwLock<BlockArray>> {
+    ALL_BLOCK_STATES.read().unwrap()[chunk_index.0][chunk_index.1].clone()
+}
+
+/// Id of the block at a world position in [LOADED_CHUNKS], `None` if its
+/// chunk isn't loaded or the position is above or below the world
+pub fn loaded_block(world_position: na::Vector3<i32>) -> Option<u16> {
+    let (chunk_position, (x, y, z)) = Chunk::split_world_position(world_position)?;
+    let blocks = LOADED_CHUNKS
+        .read()
+        .unwrap()
+        .get(&chunk_position)?
+        .blocks
+        .clone();
+    let block = blocks.read().unwrap()[Chunk::block_index(x, y, z)];
+    Some(block)
+}
+
+/// Replaces [ALL_BLOCK_STATES] with `length` x `length` chunks of air and
+/// forgets every loaded chunk
+fn reset_block_states(length: usize) {
+    let mut loaded_chunks = LOADED_CHUNKS.write().unwrap();
+    loaded_chunks.clear();
+    *ALL_BLOCK_STATES.write().unwrap() = air_block_states(length);
+}
+
+/// Adds the blocks of the chunk at `position` to [LOADED_CHUNKS] with
+/// unlit light levels, unless [ALL_BLOCK_STATES] was replaced since they were
+/// loaded
+pub fn register_loaded_chunk(
+    chunk_index: (u32, u32),
+    position: na::Vector2<i32>,
+    blocks: &Arc<RwLock<BlockArray>>,
+) {
+    let mut loaded_chunks = LOADED_CHUNKS.write().unwrap();
+    if Arc::ptr_eq(&block_states(chunk_index), blocks) {
+        loaded_chunks.insert(
+            position,
+            LoadedChunk {
+                blocks: blocks.clone(),
+                light: Arc::new(RwLock::new([0; BLOCK_COUNT])),
+            },
+        );
+    }
+}
+
+/// Uploaded opaque and cutout meshes of a chunk, drawn in any order while
+/// the chunk is in view
+struct UnsortedMeshes {
+    /// `None` if the chunk has no opaque faces
+    opaque: Option<Rc<ChunkBuffers>>,
+    /// `None` if the chunk has no cutout faces
+    cutout: Option<Rc<ChunkBuffers>>,
+    /// See [Chunk::mesh_bounds]
+    bounds: Option<Aabb>,
+}
+
+/// Transparent mesh of a chunk, kept around to be sorted again whenever the
+/// camera moves to another block
+struct TransparentMesh {
+    position: na::Vector2<i32>,
+    mesh: ChunkMesh,
+    /// See [Chunk::mesh_bounds]
+    bounds: Aabb,
+    /// `None` until the mesh is sorted and uploaded
+    uploaded: Option<Rc<ChunkBuffers>>,
+}
+
+/// Chunks with faces that were drawn or left out by frustum culling during
+/// the last frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullingStats {
+    pub drawn: usize,
+    pub culled: usize,
 }
 
 /// Chunks [RingBuffer2D] is shaped like this:
@@ -32,129 +188,610 @@ lazy_static! {
 /// .| | | | |
 /// .| | | | |
 /// .| | | | |
-/// (0,0) of the chunks [RingBuffer2D] corresponds to (-[RENDER_DISTANCE], -[RENDER_DISTANCE])
+/// (0,0) of the chunks [RingBuffer2D] corresponds to (-`render_distance`, -`render_distance`)
 pub struct ChunkLoader {
     pub chunks: RingBuffer2D<Arc<Mutex<Chunk>>>,
-    pub atlas_material_index: u32,
+    /// Every chunk in [chunks](ChunkLoader::chunks) by its position, kept up
+    /// to date as chunks move in [recenter](ChunkLoader::recenter)
+    chunks_by_position: HashMap<na::Vector2<i32>, Arc<Mutex<Chunk>>>,
     pub center_chunk_position: na::Vector2<i32>,
-    position_in_mesh_array: usize,
+    pub meshing_mode: MeshingMode,
+    pub unloaded_neighbors: UnloadedNeighbors,
+    /// Saves chunks off the frame thread
+    pub region_storage: RegionWriter,
+    /// Block placed with the right mouse button
+    pub selected_block: u16,
+    /// Box of the player, see
+    /// [Player::shared_aabb](crate::player::Player::shared_aabb). Solid blocks aren't
+    /// placed where they would intersect it.
+    pub player_aabb: Option<Rc<Cell<Aabb>>>,
+    /// Projection of the camera, which chunks outside its view are culled by
+    pub projection: Projection,
+    pub culling_stats: CullingStats,
+    /// Random and scheduled block updates of the loaded chunks, run on their
+    /// own thread
+    pub ticks: TickThread,
+    render_distance: usize,
+    mesher: ChunkMesher,
+    /// Opaque and cutout meshes of the chunks at their current positions by
+    /// [Chunk::chunk_index]
+    unsorted_meshes: HashMap<(u32, u32), UnsortedMeshes>,
+    /// Non-empty transparent meshes by [Chunk::chunk_index]
+    transparent_meshes: HashMap<(u32, u32), TransparentMesh>,
+    /// Block the camera was in when the transparent meshes were last sorted
+    transparent_sorted_from: Option<na::Vector3<i32>>,
+    /// Left and right mouse buttons during the last frame
+    previous_mouse_buttons: (bool, bool),
+    /// Render distance decrease and increase keys during the last frame
+    previous_render_distance_keys: (bool, bool),
+    /// Whether [CULLING_STATS_KEY] was pressed during the last frame
+    previous_culling_stats_key: bool,
+    /// Draws the chunk meshes, `None` without a GPU
+    pub chunk_renderer: Option<Rc<ChunkRenderer>>,
 }
 
 impl ChunkLoader {
-    pub fn new(atlas_material_index: u32, current_mesh_count: usize) -> Self {
-        let chunks = (-(RENDER_DISTANCE as i32)..=RENDER_DISTANCE as i32)
+    pub fn new(render_distance: usize) -> Self {
+        let render_distance = render_distance.clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE);
+        let center_chunk_position = na::Vector2::new(0, 0);
+        reset_block_states(2 * render_distance + 1);
+
+        let chunks = Self::create_chunks(center_chunk_position, render_distance);
+        Self {
+            chunks_by_position: Self::chunks_by_position(&chunks),
+            chunks,
+            center_chunk_position,
+            meshing_mode: MeshingMode::Naive,
+            unloaded_neighbors: UnloadedNeighbors::Show,
+            region_storage: RegionWriter::new(RegionStorage::new(WORLD_DIRECTORY)),
+            selected_block: BLOCK_REGISTRY
+                .id_of("stone")
+                .expect("Block placement needs a \"stone\" block"),
+            player_aabb: None,
+            projection: Projection::default(),
+            culling_stats: CullingStats::default(),
+            ticks: TickThread::spawn(WORLD_SEED),
+            render_distance,
+            mesher: ChunkMesher::new(),
+            unsorted_meshes: HashMap::new(),
+            transparent_meshes: HashMap::new(),
+            transparent_sorted_from: None,
+            previous_mouse_buttons: (false, false),
+            previous_render_distance_keys: (false, false),
+            previous_culling_stats_key: false,
+            chunk_renderer: None,
+        }
+    }
+
+    /// Number of chunks along each side of the loaded square
+    pub fn rendered_chunks_length(&self) -> usize {
+        2 * self.render_distance + 1
+    }
+
+    fn create_chunks(
+        center_chunk_position: na::Vector2<i32>,
+        render_distance: usize,
+    ) -> RingBuffer2D<Arc<Mutex<Chunk>>> {
+        let chunks = (-(render_distance as i32)..=render_distance as i32)
             .map(|x| {
-                (-(RENDER_DISTANCE as i32)..=RENDER_DISTANCE as i32)
+                (-(render_distance as i32)..=render_distance as i32)
                     .map(|y| {
-                        Arc::new(Mutex::new(Chunk::new(
-                            na::Vector2::new(x, y),
+                        let mut chunk = Chunk::new(
+                            center_chunk_position + na::Vector2::new(x, y),
                             (
-                                (x + RENDER_DISTANCE as i32) as u32,
-                                (y + RENDER_DISTANCE as i32) as u32,
+                                (x + render_distance as i32) as u32,
+                                (y + render_distance as i32) as u32,
                             ),
-                            atlas_material_index,
-                        )))
+                        );
+                        chunk.lod = Lod::for_distance(x.unsigned_abs().max(y.unsigned_abs()));
+                        Arc::new(Mutex::new(chunk))
                     })
                     .collect::<Vec<Arc<Mutex<Chunk>>>>()
             })
             .collect::<Vec<Vec<Arc<Mutex<Chunk>>>>>();
-        let chunks = RingBuffer2D::new(chunks);
+        RingBuffer2D::new(chunks)
+    }
 
-        Self {
-            chunks,
-            atlas_material_index,
-            center_chunk_position: na::Vector2::new(0, 0),
-            position_in_mesh_array: current_mesh_count,
+    fn chunks_by_position(
+        chunks: &RingBuffer2D<Arc<Mutex<Chunk>>>,
+    ) -> HashMap<na::Vector2<i32>, Arc<Mutex<Chunk>>> {
+        chunks
+            .flatten()
+            .into_iter()
+            .map(|chunk| (chunk.lock().unwrap().position, chunk.clone()))
+            .collect()
+    }
+
+    /// Reloads every chunk around the current center with a new render
+    /// distance and queues their meshes. Edited chunks are saved first so
+    /// that chunks which stay loaded keep their changes.
+    pub fn set_render_distance(&mut self, render_distance: usize) {
+        let render_distance = render_distance.clamp(MIN_RENDER_DISTANCE, MAX_RENDER_DISTANCE);
+        if render_distance == self.render_distance {
+            return;
         }
+        self.save_dirty_chunks();
+        // Picked up again from the saved chunks as they load
+        for chunk in self.chunks.flatten() {
+            self.ticks.unload_chunk(chunk.lock().unwrap().position);
+        }
+
+        self.render_distance = render_distance;
+        self.chunks = Self::create_chunks(self.center_chunk_position, render_distance);
+        self.chunks_by_position = Self::chunks_by_position(&self.chunks);
+        reset_block_states(self.rendered_chunks_length());
+        self.mesher.forget_all();
+        self.unsorted_meshes.clear();
+        self.transparent_meshes.clear();
+        self.initialize_chunks();
     }
 
-    pub fn initialize_chunks(
-        &self,
-        mesh_manager: Arc<Mutex<MeshManager>>,
-        device: Arc<wgpu::Device>,
-    ) {
-        for chunk_column in &self.chunks {
-            for chunk in chunk_column.into_iter() {
-                let chunk_mesh = chunk.clone().lock().unwrap().create_mesh(device.clone());
-                mesh_manager
-                    .lock()
-                    .unwrap()
-                    .diffuse_pipeline_models
-                    .push(chunk_mesh);
-            }
+    /// A chunk's blocks and scheduled ticks to write to its region file, if
+    /// the blocks changed since it was loaded or it has ticks scheduled.
+    /// Terrain colored by biome is never saved, so the debug blocks don't end
+    /// up in the world, and neither are chunks whose saved data couldn't be
+    /// read, so it isn't overwritten.
+    fn chunk_to_save(&self, chunk: &mut Chunk) -> Option<(na::Vector2<i32>, SavedChunk)> {
+        if TERRAIN_GENERATOR.biome_colors || chunk.unreadable {
+            return None;
         }
+        let mut ticks = self.ticks.saved_ticks(chunk.position);
+        ticks.extend_from_slice(&chunk.loaded_ticks);
+        if !chunk.dirty && ticks.is_empty() {
+            return None;
+        }
+        chunk.dirty = false;
+        let blocks = block_states(chunk.chunk_index).read().unwrap().clone();
+        Some((chunk.position, SavedChunk { blocks, ticks }))
     }
 
-    fn mark_chunks_to_reload(&self, offset: (i32, i32)) -> Vec<(i32, i32)> {
-        match offset {
-            (-1, 0) => {
-                return (0..RENDERED_CHUNKS_LENGTH as i32)
-                    .map(|y| (0, y))
-                    .collect::<Vec<(i32, i32)>>()
-            }
-            (1, 0) => {
-                return (0..RENDERED_CHUNKS_LENGTH as i32)
-                    .map(|y| (-1, y))
-                    .collect::<Vec<(i32, i32)>>()
+    /// Queues saving every chunk that changed, see
+    /// [chunk_to_save](ChunkLoader::chunk_to_save)
+    pub fn save_dirty_chunks(&self) {
+        let chunks = self
+            .chunks
+            .flatten()
+            .iter()
+            .filter_map(|chunk| self.chunk_to_save(&mut chunk.lock().unwrap()))
+            .collect();
+        self.region_storage.save_chunks(chunks);
+    }
+
+    /// Queues loading and meshing every chunk in the background
+    pub fn initialize_chunks(&mut self) {
+        let chunks = self.chunks.flatten();
+        self.queue_chunks(chunks);
+    }
+
+    /// Queues meshing every section of `chunks`
+    fn queue_chunks(&mut self, chunks: Vec<Arc<Mutex<Chunk>>>) {
+        for chunk in chunks {
+            self.queue_sections(chunk, ALL_SECTIONS);
+        }
+    }
+
+    fn queue_sections(&mut self, chunk: Arc<Mutex<Chunk>>, sections: SectionMask) {
+        self.mesher.queue(
+            chunk,
+            sections,
+            self.meshing_mode,
+            self.unloaded_neighbors,
+            &self.region_storage,
+        );
+    }
+
+    /// Replaces the meshes of up to [MESH_UPLOADS_PER_FRAME] chunks that
+    /// finished meshing, combining the new sections with the ones that
+    /// didn't change. The meshes are drawn while they are in view, see
+    /// [meshes_in_view](ChunkLoader::meshes_in_view), and transparent meshes
+    /// are only uploaded once they are sorted. Loaded neighbors of chunks that
+    /// just got their blocks are remeshed, as their border faces may have
+    /// changed.
+    fn upload_ready_meshes(&mut self, device: &wgpu::Device) {
+        let mut chunks_to_remesh = Vec::new();
+        for meshed_chunk in self.mesher.take_ready(MESH_UPLOADS_PER_FRAME) {
+            let (chunk_index, position, meshes, bounds) = {
+                let mut chunk = meshed_chunk.chunk.lock().unwrap();
+                chunk.replace_sections(meshed_chunk.sections);
+                let loaded_ticks = std::mem::take(&mut chunk.loaded_ticks);
+                self.ticks.load_chunk(chunk.position, loaded_ticks);
+                (
+                    chunk.chunk_index,
+                    chunk.position,
+                    chunk.meshes(),
+                    chunk.mesh_bounds(),
+                )
+            };
+            self.unsorted_meshes.insert(
+                chunk_index,
+                UnsortedMeshes {
+                    opaque: ChunkBuffers::new(
+                        device,
+                        position,
+                        &meshes.opaque.vertices,
+                        &meshes.opaque.indices,
+                    )
+                    .map(Rc::new),
+                    cutout: ChunkBuffers::new(
+                        device,
+                        position,
+                        &meshes.cutout.vertices,
+                        &meshes.cutout.indices,
+                    )
+                    .map(Rc::new),
+                    bounds,
+                },
+            );
+
+            match bounds.filter(|_| !meshes.transparent.indices.is_empty()) {
+                Some(bounds) => {
+                    self.transparent_meshes.insert(
+                        chunk_index,
+                        TransparentMesh {
+                            position,
+                            mesh: meshes.transparent,
+                            bounds,
+                            uploaded: None,
+                        },
+                    );
+                }
+                None => {
+                    self.transparent_meshes.remove(&chunk_index);
+                }
             }
-            (0, 1) => {
-                return (0..RENDERED_CHUNKS_LENGTH as i32)
-                    .map(|x| (x, -1))
-                    .collect::<Vec<(i32, i32)>>()
+
+            // The chunk is unlocked first, looking up its neighbors locks
+            // every chunk
+            if meshed_chunk.loaded_blocks {
+                for offset in NEIGHBOR_OFFSETS {
+                    let neighbor_position = position + na::Vector2::new(offset.0, offset.1);
+                    if let Some(neighbor) = self.chunk_at(neighbor_position) {
+                        if neighbor.lock().unwrap().loaded {
+                            chunks_to_remesh.push(neighbor.clone());
+                        }
+                    }
+                }
             }
-            (0, -1) => {
-                return (0..RENDERED_CHUNKS_LENGTH as i32)
-                    .map(|x| (x, 0))
-                    .collect::<Vec<(i32, i32)>>()
+        }
+        self.queue_chunks(chunks_to_remesh);
+    }
+
+    /// The meshes of every chunk inside `frustum`, counting the chunks with
+    /// faces that are drawn and culled in
+    /// [culling_stats](ChunkLoader::culling_stats)
+    fn meshes_in_view(
+        &mut self,
+        eye: na::Vector3<f32>,
+        frustum: &Frustum,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> VisibleMeshes {
+        let mut meshes = VisibleMeshes::default();
+        let mut stats = CullingStats::default();
+        for unsorted in self.unsorted_meshes.values() {
+            let Some(bounds) = &unsorted.bounds else {
+                continue;
+            };
+            if frustum.intersects(bounds) {
+                stats.drawn += 1;
+                meshes.opaque.extend(unsorted.opaque.clone());
+                meshes.cutout.extend(unsorted.cutout.clone());
+            } else {
+                stats.culled += 1;
             }
-            _ => vec![],
         }
+        self.culling_stats = stats;
+        meshes.transparent = self.sorted_transparent_meshes(eye, frustum, device, queue);
+        meshes
     }
 
-    fn create_new_chunk_meshes(
+    /// The transparent meshes inside `frustum`, furthest chunk first. They
+    /// are only sorted
+    /// back to front again when the camera at `eye` moved to another block or
+    /// the mesh changed since the last time, and only uploaded again if their
+    /// faces changed order.
+    fn sorted_transparent_meshes(
         &mut self,
-        offset: (i32, i32),
-        chunk_indices: Vec<(i32, i32)>,
-        device: Arc<wgpu::Device>,
-        mesh_manager: Arc<Mutex<MeshManager>>,
-    ) {
-        for chunk_index in chunk_indices {
-            let mut current_chunk = self.chunks[chunk_index].lock().unwrap();
-            if offset.0 != 0 {
-                current_chunk.position.x =
-                    -1 * self.center_chunk_position.x - offset.0 * (RENDER_DISTANCE as i32 + 1);
+        eye: na::Vector3<f32>,
+        frustum: &Frustum,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Vec<Rc<ChunkBuffers>> {
+        let eye_block = eye.map(|component| component.floor() as i32);
+        let moved = self.transparent_sorted_from != Some(eye_block);
+
+        let chunk_origin = |position: na::Vector2<i32>| {
+            na::Vector3::new(
+                (position.x * X_SIZE as i32) as f32,
+                0.0,
+                (position.y * Z_SIZE as i32) as f32,
+            )
+        };
+        for transparent in self.transparent_meshes.values_mut() {
+            let mesh_eye = eye - chunk_origin(transparent.position);
+            let mesh = &mut transparent.mesh;
+            match &transparent.uploaded {
+                Some(uploaded) => {
+                    if moved && mesh.sort_back_to_front(mesh_eye) {
+                        uploaded.write(queue, &mesh.vertices, &mesh.indices);
+                    }
+                }
+                None => {
+                    mesh.sort_back_to_front(mesh_eye);
+                    transparent.uploaded = ChunkBuffers::new(
+                        device,
+                        transparent.position,
+                        &mesh.vertices,
+                        &mesh.indices,
+                    )
+                    .map(Rc::new);
+                }
+            }
+        }
+        self.transparent_sorted_from = Some(eye_block);
+
+        let chunk_distance = |position: na::Vector2<i32>| {
+            let center = chunk_origin(position)
+                + na::Vector3::new(X_SIZE as f32 / 2.0, eye.y, Z_SIZE as f32 / 2.0);
+            (center - eye).norm_squared()
+        };
+        let mut meshes: Vec<&TransparentMesh> = self
+            .transparent_meshes
+            .values()
+            .filter(|transparent| frustum.intersects(&transparent.bounds))
+            .collect();
+        meshes.sort_by(|a, b| chunk_distance(b.position).total_cmp(&chunk_distance(a.position)));
+        meshes
+            .into_iter()
+            .filter_map(|transparent| transparent.uploaded.clone())
+            .collect()
+    }
+
+    /// The chunk at a chunk position, `None` if it is outside of the loaded
+    /// square
+    pub fn chunk_at(&self, chunk_position: na::Vector2<i32>) -> Option<&Arc<Mutex<Chunk>>> {
+        self.chunks_by_position.get(&chunk_position)
+    }
+
+    /// Id of the block at a world position, `None` if its chunk isn't loaded
+    pub fn get_block(&self, world_position: na::Vector3<i32>) -> Option<u16> {
+        let (chunk_position, (x, y, z)) = Chunk::split_world_position(world_position)?;
+        let chunk = self.chunk_at(chunk_position)?.lock().unwrap();
+        if !chunk.loaded {
+            return None;
+        }
+        let blocks = block_states(chunk.chunk_index);
+        let block = blocks.read().unwrap()[Chunk::block_index(x, y, z)];
+        Some(block)
+    }
+
+    /// Replaces the block at a world position, updates the light around it
+    /// and queues remeshing the sections around it, along with the
+    /// neighboring chunks' sections when the block sits on a chunk border or
+    /// their light changed. Returns `false` if the block's chunk isn't loaded.
+    pub fn set_block(&mut self, world_position: na::Vector3<i32>, block: u16) -> bool {
+        let Some((chunk_position, (x, y, z))) = Chunk::split_world_position(world_position) else {
+            return false;
+        };
+        let Some(chunk) = self.chunk_at(chunk_position) else {
+            return false;
+        };
+
+        {
+            let mut chunk = chunk.lock().unwrap();
+            if !chunk.loaded {
+                return false;
+            }
+            block_states(chunk.chunk_index)
+                .write()
+                .unwrap()
+                .set(Chunk::block_index(x, y, z), block);
+            chunk.dirty = true;
+        }
+
+        // Chunks the block borders on, including diagonal ones whose ambient
+        // occlusion it affects
+        let border_offsets = |position: usize, size: usize| match position {
+            0 => vec![0, -1],
+            position if position == size - 1 => vec![0, 1],
+            _ => vec![0],
+        };
+        let mut sections_to_remesh: HashMap<na::Vector2<i32>, SectionMask> = HashMap::new();
+        let remeshed_offsets = border_offsets(x, X_SIZE)
+            .into_iter()
+            .flat_map(|offset_x| {
+                border_offsets(z, Z_SIZE)
+                    .into_iter()
+                    .map(move |offset_z| ((offset_x, offset_z), sections_around(y)))
+            })
+            .chain(
+                LightRegion::with_loaded(chunk_position, |region| {
+                    // Keep the block change even if its light couldn't be updated
+                    if let Err(err) = region.update_block([x as i32, y as i32, z as i32]) {
+                        log::warn!("Failed to update light around {world_position:?}: {err}");
+                    }
+                    region.changed_sections()
+                })
+                .unwrap_or_default(),
+            );
+        for ((x, z), sections) in remeshed_offsets {
+            *sections_to_remesh
+                .entry(chunk_position + na::Vector2::new(x, z))
+                .or_default() |= sections;
+        }
+        for (chunk_position, sections) in sections_to_remesh {
+            if let Some(chunk) = self.chunk_at(chunk_position).cloned() {
+                self.queue_sections(chunk, sections);
             }
+        }
+        true
+    }
+
+    /// Breaks the block the camera is looking at, or places
+    /// [selected_block](ChunkLoader::selected_block) against the face it is looking at
+    fn interact(&mut self, origin: na::Vector3<f32>, direction: na::Vector3<f32>, place: bool) {
+        let hit = raycast(origin, direction, REACH_DISTANCE, |world_position| {
+            self.get_block(world_position)
+                .and_then(|block| BLOCK_REGISTRY.get(block).ok())
+                .is_some_and(|block| block.solid)
+        });
+        let Some(hit) = hit else {
+            return;
+        };
 
-            if offset.1 != 0 {
-                current_chunk.position.y =
-                    -1 * self.center_chunk_position.y - offset.1 * (RENDER_DISTANCE as i32 + 1);
+        if place {
+            if hit.normal == na::Vector3::zeros() {
+                return;
             }
+            let position = hit.block + hit.normal;
+            if self.intersects_player(position, self.selected_block) {
+                return;
+            }
+            if self.set_block(position, self.selected_block) {
+                self.ticks.block_changed(position);
+            }
+        } else {
+            let air = BLOCK_REGISTRY
+                .id_of("air")
+                .expect("Breaking blocks needs an \"air\" block");
+            if self.set_block(hit.block, air) {
+                self.ticks.block_changed(hit.block);
+            }
+        }
+    }
+
+    /// Whether `block` would be placed inside the player at a world position
+    fn intersects_player(&self, world_position: na::Vector3<i32>, block: u16) -> bool {
+        let solid = BLOCK_REGISTRY
+            .get(block)
+            .is_ok_and(|definition| definition.solid);
+        solid
+            && self
+                .player_aabb
+                .as_ref()
+                .is_some_and(|aabb| aabb.get().intersects(&Aabb::block(world_position)))
+    }
 
-            let new_mesh = current_chunk.create_mesh(device.clone());
-            let linearized_index = self.position_in_mesh_array
-                + match offset {
-                    (-1, 0) => self.chunks.linearize_index((0, chunk_index.1)),
-                    (1, 0) => self.chunks.linearize_index((-1, chunk_index.1)),
-                    (0, 1) => self.chunks.linearize_index((chunk_index.0, -1)),
-                    (0, -1) => self.chunks.linearize_index((chunk_index.0, 0)),
-                    _ => 0,
-                };
-            mesh_manager.lock().unwrap().diffuse_pipeline_models[linearized_index] = new_mesh;
-        }
-        match offset {
-            (-1, 0) => self.chunks.rotate_left(1),
-            (1, 0) => self.chunks.rotate_right(1),
-            (0, 1) => self.chunks.rotate_up(1),
-            (0, -1) => self.chunks.rotate_down(1),
-            _ => {}
+    /// Indices into [chunks](ChunkLoader::chunks) that come into view when
+    /// the center moves by `offset` chunks. Moving further than the window is
+    /// wide exposes every chunk.
+    fn mark_chunks_to_reload(&self, offset: (i32, i32)) -> Vec<(i32, i32)> {
+        let length = self.rendered_chunks_length() as i32;
+        let exposed = |offset: i32, index: i32| {
+            if offset > 0 {
+                index >= length - offset
+            } else {
+                index < -offset
+            }
         };
+
+        (0..length)
+            .flat_map(|x| (0..length).map(move |y| (x, y)))
+            .filter(|&(x, y)| exposed(offset.0, x) || exposed(offset.1, y))
+            .collect()
+    }
+
+    /// Level of detail for the chunk at `chunk_position`, from its distance to
+    /// [center_chunk_position](ChunkLoader::center_chunk_position)
+    pub fn lod_at(&self, chunk_position: na::Vector2<i32>) -> Lod {
+        let offset = chunk_position - self.center_chunk_position;
+        Lod::for_distance(offset.x.unsigned_abs().max(offset.y.unsigned_abs()))
+    }
+
+    /// Gives every chunk the [Lod] for its distance to the center. Returns
+    /// the loaded chunks whose level changed, which need new meshes.
+    pub fn update_lods(&self) -> Vec<Arc<Mutex<Chunk>>> {
+        self.chunks
+            .into_iter()
+            .flatten()
+            .filter(|chunk| {
+                let mut chunk = chunk.lock().unwrap();
+                let lod = self.lod_at(chunk.position);
+                let changed = chunk.lod != lod;
+                chunk.lod = lod;
+                changed && chunk.loaded
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Moves the loaded window so it is centered on `center_chunk_position`.
+    ///
+    /// [chunks](ChunkLoader::chunks) is rotated along both axes so chunks
+    /// that stay in view keep their blocks and meshes. Every chunk that comes
+    /// into view is saved and moved to its new position. Returns the chunks
+    /// that were moved, which still need their blocks loaded and new meshes.
+    pub fn recenter(&mut self, center_chunk_position: na::Vector2<i32>) -> Vec<Arc<Mutex<Chunk>>> {
+        let offset_vec = center_chunk_position - self.center_chunk_position;
+        let offset = (offset_vec.x, offset_vec.y);
+        if offset == (0, 0) {
+            return vec![];
+        }
+        let chunks_to_load = self.mark_chunks_to_reload(offset);
+
+        let length = self.rendered_chunks_length() as i32;
+        let horizontal_rotation = offset.0.rem_euclid(length) as u32;
+        let vertical_rotation = offset.1.rem_euclid(length) as u32;
+        self.chunks.rotate_left(horizontal_rotation);
+        self.chunks.rotate_down(vertical_rotation);
+        self.center_chunk_position = center_chunk_position;
+
+        let render_distance = self.render_distance as i32;
+        // Structures of chunks just past the loaded ones stay queued, so
+        // walking back and forth doesn't plan them again
+        TERRAIN_GENERATOR
+            .structures
+            .forget_outside(center_chunk_position, render_distance + 2);
+        let mut chunks_to_save = vec![];
+        let chunks_to_load = chunks_to_load
+            .into_iter()
+            .map(|chunk_index| {
+                let chunk = self.chunks[chunk_index].clone();
+                {
+                    let mut chunk = chunk.lock().unwrap();
+                    chunks_to_save.extend(self.chunk_to_save(&mut chunk));
+                    self.ticks.unload_chunk(chunk.position);
+                    chunk.loaded_ticks.clear();
+                    self.unsorted_meshes.remove(&chunk.chunk_index);
+                    self.transparent_meshes.remove(&chunk.chunk_index);
+                    if chunk.loaded {
+                        LOADED_CHUNKS.write().unwrap().remove(&chunk.position);
+                    }
+                    // Chunks only move from outside of the new square into
+                    // it, so no other chunk has the position yet
+                    self.chunks_by_position.remove(&chunk.position);
+                    chunk.position = center_chunk_position
+                        + na::Vector2::new(
+                            chunk_index.0 - render_distance,
+                            chunk_index.1 - render_distance,
+                        );
+                    chunk.loaded = false;
+                    chunk.unreadable = false;
+                    self.chunks_by_position
+                        .insert(chunk.position, self.chunks[chunk_index].clone());
+                }
+                chunk
+            })
+            .collect();
+        self.region_storage.save_chunks(chunks_to_save);
+        chunks_to_load
+    }
+}
+
+impl Drop for ChunkLoader {
+    fn drop(&mut self) {
+        self.save_dirty_chunks();
+        let unsaved = self.region_storage.flush();
+        if !unsaved.is_empty() {
+            log::error!("Lost the changes to chunks {unsaved:?}, they couldn't be saved");
+        }
     }
 }
 
 impl FrameDependancy for ChunkLoader {
     fn frame_update(
         &mut self,
-        _engine_details: RefMut<gamezap::EngineDetails>,
+        engine_details: RefMut<gamezap::EngineDetails>,
         renderer: &gamezap::renderer::Renderer,
         _engine_systems: std::cell::Ref<gamezap::EngineSystems>,
     ) {
@@ -164,28 +801,80 @@ impl FrameDependancy for ChunkLoader {
             .as_ref()
             .unwrap()
             .borrow();
-        let position = camera_manager.camera.borrow().position;
-        let chunked_position = na::Vector2::new(position.x as i32 / 16, position.z as i32 / 16);
+        let camera = camera_manager.camera.borrow();
+        let position = camera.position;
+        let look_direction = look_direction(camera.yaw, camera.pitch);
+        let view_projection = self
+            .projection
+            .view_projection(position, camera.yaw, camera.pitch);
+        let frustum = Frustum::new(position, camera.yaw, camera.pitch, self.projection);
+        drop(camera);
 
-        let offset_vec = chunked_position - self.center_chunk_position;
-        let offset = (offset_vec.x, offset_vec.y);
+        let mouse_buttons = engine_details
+            .mouse_state
+            .0
+            .map(|mouse_state| (mouse_state.left(), mouse_state.right()))
+            .unwrap_or_default();
+        let break_clicked = mouse_buttons.0 && !self.previous_mouse_buttons.0;
+        let place_clicked = mouse_buttons.1 && !self.previous_mouse_buttons.1;
+        self.previous_mouse_buttons = mouse_buttons;
 
-        if offset != (0, 0) {
-            let chunks_to_load = self.mark_chunks_to_reload(offset);
-            let mesh_manager = renderer
-                .module_manager
-                .borrow()
-                .mesh_manager
-                .borrow()
-                .as_ref()
-                .unwrap();
-            self.create_new_chunk_meshes(
-                offset,
-                chunks_to_load,
-                renderer.device.clone(),
-                mesh_manager.clone(),
-            );
+        if break_clicked || place_clicked {
+            self.interact(position, look_direction, place_clicked);
+        }
+
+        // Blocks changed by the tick thread go through set_block, which queues
+        // the remeshing
+        let changes = self.ticks.take_changes();
+        if let Some(batch) = changes.last().map(|changes| changes.batch) {
+            for (position, block) in changes.into_iter().flat_map(|changes| changes.blocks) {
+                self.set_block(position, block);
+            }
+            self.ticks.applied(batch);
+        }
+
+        let render_distance_keys = (
+            engine_details.pressed_scancodes.contains(&Scancode::Minus),
+            engine_details.pressed_scancodes.contains(&Scancode::Equals),
+        );
+        let decrease_pressed = render_distance_keys.0 && !self.previous_render_distance_keys.0;
+        let increase_pressed = render_distance_keys.1 && !self.previous_render_distance_keys.1;
+        self.previous_render_distance_keys = render_distance_keys;
+
+        if decrease_pressed || increase_pressed {
+            let render_distance = if increase_pressed {
+                self.render_distance + 1
+            } else {
+                self.render_distance.saturating_sub(1)
+            };
+            self.set_render_distance(render_distance);
+        }
+
+        let chunked_position = na::Vector2::new(
+            (position.x.floor() as i32).div_euclid(X_SIZE as i32),
+            (position.z.floor() as i32).div_euclid(Z_SIZE as i32),
+        );
+        if chunked_position != self.center_chunk_position {
+            let chunks_to_load = self.recenter(chunked_position);
+            let chunks_to_remesh = self.update_lods();
+            self.queue_chunks(chunks_to_load);
+            self.queue_chunks(chunks_to_remesh);
+        }
+
+        self.upload_ready_meshes(&renderer.device);
+        let meshes = self.meshes_in_view(position, &frustum, &renderer.device, &renderer.queue);
+        if let Some(chunk_renderer) = &self.chunk_renderer {
+            chunk_renderer.update_camera(&renderer.queue, view_projection);
+            chunk_renderer.set_meshes(meshes);
+        }
+
+        let culling_stats_key = engine_details
+            .pressed_scancodes
+            .contains(&CULLING_STATS_KEY);
+        if culling_stats_key && !self.previous_culling_stats_key {
+            let CullingStats { drawn, culled } = self.culling_stats;
+            log::info!("Drew {drawn} of {} chunks, culled {culled}", drawn + culled);
         }
-        self.center_chunk_position = chunked_position;
+        self.previous_culling_stats_key = culling_stats_key;
     }
 }
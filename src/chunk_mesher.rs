@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+
+use nalgebra as na;
+use threadpool::ThreadPool;
+
+use crate::{
+    block_registry::BlockRegistryError,
+    block_storage::{SectionMask, ALL_SECTIONS},
+    chunk::{Chunk, ChunkFaces, ChunkNeighborhood, MeshingMode, UnloadedNeighbors},
+    chunk_loader::{block_states, load_chunk, register_loaded_chunk},
+    light::LightRegion,
+    region::RegionWriter,
+};
+
+/// Sections meshed for a chunk on a worker thread
+pub struct MeshedChunk {
+    pub chunk: Arc<Mutex<Chunk>>,
+    /// Faces of every meshed section, see [Chunk::replace_sections]
+    pub sections: Vec<(usize, ChunkFaces)>,
+    /// Whether the chunk's blocks were loaded by this job
+    pub loaded_blocks: bool,
+    position: na::Vector2<i32>,
+    generation: u64,
+}
+
+/// A chunk a worker couldn't light or mesh, sent back instead of a
+/// [MeshedChunk]
+struct FailedChunk {
+    chunk: Arc<Mutex<Chunk>>,
+    error: BlockRegistryError,
+    position: na::Vector2<i32>,
+    generation: u64,
+}
+
+/// Loads and meshes chunks on a pool of worker threads.
+///
+/// Workers only build the vertex and index data, the GPU buffers are created
+/// on the main thread once [take_ready](ChunkMesher::take_ready) hands the
+/// meshes back.
+/// Every queued chunk gets a new generation, so results for a chunk that was
+/// queued again or moved in the meantime are thrown away. Queuing a chunk
+/// again also meshes the sections the thrown away job would have.
+pub struct ChunkMesher {
+    pool: ThreadPool,
+    sender: Sender<Result<MeshedChunk, FailedChunk>>,
+    receiver: Receiver<Result<MeshedChunk, FailedChunk>>,
+    /// Generation of the latest job for every [Chunk::chunk_index]
+    latest_generations: HashMap<(u32, u32), u64>,
+    /// Sections the latest job for every [Chunk::chunk_index] meshes
+    pending_sections: HashMap<(u32, u32), SectionMask>,
+    next_generation: u64,
+}
+
+impl ChunkMesher {
+    pub fn new() -> Self {
+        let thread_count = std::thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(4);
+        let (sender, receiver) = mpsc::channel();
+        ChunkMesher {
+            pool: ThreadPool::with_name("chunk mesher".to_string(), thread_count),
+            sender,
+            receiver,
+            latest_generations: HashMap::new(),
+            pending_sections: HashMap::new(),
+            next_generation: 0,
+        }
+    }
+
+    /// Meshes `sections` of a chunk at its [lod](Chunk::lod) in the
+    /// background, loading and lighting its blocks first if they aren't
+    /// [loaded](Chunk::loaded) yet. Chunks that get their blocks loaded have
+    /// every section meshed.
+    pub fn queue(
+        &mut self,
+        chunk: Arc<Mutex<Chunk>>,
+        sections: SectionMask,
+        meshing_mode: MeshingMode,
+        unloaded_neighbors: UnloadedNeighbors,
+        region_storage: &RegionWriter,
+    ) {
+        let (chunk_index, position) = {
+            let chunk = chunk.lock().unwrap();
+            (chunk.chunk_index, chunk.position)
+        };
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.latest_generations.insert(chunk_index, generation);
+        let sections = *self
+            .pending_sections
+            .entry(chunk_index)
+            .and_modify(|pending| *pending |= sections)
+            .or_insert(sections);
+
+        // Captured now so a job that outlives a render distance change can't
+        // write into the new block states
+        let blocks = block_states(chunk_index);
+        let region_storage = region_storage.clone();
+        let sender = self.sender.clone();
+        self.pool.execute(move || {
+            let needs_blocks = {
+                let chunk = chunk.lock().unwrap();
+                if chunk.position != position {
+                    return;
+                }
+                !chunk.loaded
+            };
+
+            // Loaded without holding the chunk, so the main thread never
+            // waits on generation or the region file. Only swapping the
+            // blocks in locks it.
+            let mut loaded_blocks = false;
+            if needs_blocks {
+                let (saved, unreadable) = load_chunk(&region_storage, position);
+                let mut chunk = chunk.lock().unwrap();
+                if chunk.position != position {
+                    return;
+                }
+                // Another job may have loaded the chunk in the meantime
+                if !chunk.loaded {
+                    *blocks.write().unwrap() = saved.blocks;
+                    chunk.loaded_ticks = saved.ticks;
+                    chunk.unreadable = unreadable;
+                    register_loaded_chunk(chunk_index, position, &blocks);
+                    chunk.loaded = true;
+                    loaded_blocks = true;
+                }
+            }
+            let lod = chunk.lock().unwrap().lod;
+
+            // Lit without holding the chunk, light updates wait on each other
+            // instead
+            let lit = if loaded_blocks {
+                LightRegion::with_loaded(position, |region| region.light_chunk())
+                    .transpose()
+                    .map(|_| ())
+            } else {
+                Ok(())
+            };
+
+            let sections = if loaded_blocks {
+                ALL_SECTIONS
+            } else {
+                sections
+            };
+            let result = lit
+                .and_then(|()| {
+                    ChunkNeighborhood::with_loaded(
+                        chunk_index,
+                        position,
+                        unloaded_neighbors,
+                        |neighborhood| {
+                            Chunk::build_sections(neighborhood, sections, meshing_mode, lod)
+                        },
+                    )
+                })
+                .map(|sections| MeshedChunk {
+                    chunk: chunk.clone(),
+                    sections,
+                    loaded_blocks,
+                    position,
+                    generation,
+                })
+                .map_err(|error| FailedChunk {
+                    chunk,
+                    error,
+                    position,
+                    generation,
+                });
+            // The receiver only goes away when the mesher is dropped
+            let _ = sender.send(result);
+        });
+    }
+
+    /// Drops every queued and finished job, used when the chunks they were
+    /// queued for are replaced
+    pub fn forget_all(&mut self) {
+        self.latest_generations.clear();
+        self.pending_sections.clear();
+    }
+
+    /// Up to `budget` finished chunks that are still current. Chunks that
+    /// failed are logged and keep their old meshes.
+    pub fn take_ready(&mut self, budget: usize) -> Vec<MeshedChunk> {
+        let mut ready = Vec::with_capacity(budget);
+        while ready.len() < budget {
+            let Ok(result) = self.receiver.try_recv() else {
+                break;
+            };
+            let (chunk, position, generation) = match &result {
+                Ok(meshed) => (&meshed.chunk, meshed.position, meshed.generation),
+                Err(failed) => (&failed.chunk, failed.position, failed.generation),
+            };
+
+            let chunk = chunk.lock().unwrap();
+            let is_current = chunk.position == position
+                && self.latest_generations.get(&chunk.chunk_index) == Some(&generation);
+            if is_current {
+                self.latest_generations.remove(&chunk.chunk_index);
+                self.pending_sections.remove(&chunk.chunk_index);
+            }
+            drop(chunk);
+
+            match result {
+                Ok(meshed_chunk) if is_current => ready.push(meshed_chunk),
+                Err(failed) if is_current => {
+                    log::error!("Failed to mesh chunk {position:?}: {}", failed.error)
+                }
+                _ => {}
+            }
+        }
+        ready
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    cell::RefCell,
+    mem::{offset_of, size_of},
+    rc::Rc,
+};
+
+use gamezap::model::Vertex;
+use nalgebra as na;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    block_registry::RenderLayer,
+    chunk::{X_SIZE, Z_SIZE},
+    cube::FACE_TEXTURE_OFFSET,
+};
+
+/// Source of the chunk shader. The edge of an atlas tile is prepended as
+/// `TILE_SIZE`, so the shader always agrees with [FACE_TEXTURE_OFFSET].
+pub fn chunk_shader() -> String {
+    format!(
+        "const TILE_SIZE: f32 = {FACE_TEXTURE_OFFSET:?};\n{}",
+        include_str!("shaders/chunk.wgsl")
+    )
+}
+
+/// Formats of the frames the engine draws. The chunk pipeline draws into the
+/// same color and depth targets, so it has to match them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameFormat {
+    pub color: wgpu::TextureFormat,
+    pub depth: wgpu::TextureFormat,
+    /// Samples per pixel, more than one when the engine antialiases
+    pub sample_count: u32,
+}
+
+/// Attributes the chunk shader reads from each [Vertex]. The engine's vertex
+/// has no fields for them, so `tangent` carries the corner of the face's atlas
+/// tile, see [Face](crate::cube::Face), and the first component of
+/// `bitangent` the vertex's ambient occlusion, see
+/// [shade_face](crate::ambient_occlusion::shade_face).
+pub const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 4] = [
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32x3,
+        offset: offset_of!(Vertex, position) as u64,
+        shader_location: 0,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32x2,
+        offset: offset_of!(Vertex, tex_coords) as u64,
+        shader_location: 1,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32x2,
+        offset: offset_of!(Vertex, tangent) as u64,
+        shader_location: 2,
+    },
+    wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32,
+        offset: offset_of!(Vertex, bitangent) as u64,
+        shader_location: 4,
+    },
+];
+
+/// Corner of the chunk a mesh belongs to, read once per draw from
+/// [ChunkBuffers]
+pub const ORIGIN_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![3 => Float32x3];
+
+/// The vertex buffer layouts of the chunk pipeline: the mesh's vertices and
+/// its chunk's origin
+pub fn vertex_layouts() -> [wgpu::VertexBufferLayout<'static>; 2] {
+    [
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Vertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &VERTEX_ATTRIBUTES,
+        },
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<[f32; 3]>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ORIGIN_ATTRIBUTES,
+        },
+    ]
+}
+
+/// How [ChunkRenderer] draws the faces of a [RenderLayer]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerPipeline {
+    /// Fragment stage of [chunk_shader]
+    pub fragment_entry_point: &'static str,
+    pub blend: wgpu::BlendState,
+    pub depth_write_enabled: bool,
+}
+
+/// Pipeline state of each layer. Cutout faces discard their see-through
+/// texels in the shader, and blended faces leave the depth buffer alone so
+/// the blended faces behind them, drawn first, aren't hidden.
+pub fn layer_pipeline(render_layer: RenderLayer) -> LayerPipeline {
+    match render_layer {
+        RenderLayer::Opaque => LayerPipeline {
+            fragment_entry_point: "fs_main",
+            blend: wgpu::BlendState::REPLACE,
+            depth_write_enabled: true,
+        },
+        RenderLayer::Cutout => LayerPipeline {
+            fragment_entry_point: "fs_cutout",
+            blend: wgpu::BlendState::REPLACE,
+            depth_write_enabled: true,
+        },
+        RenderLayer::Transparent => LayerPipeline {
+            fragment_entry_point: "fs_main",
+            blend: wgpu::BlendState::ALPHA_BLENDING,
+            depth_write_enabled: false,
+        },
+    }
+}
+
+/// A chunk mesh on the GPU
+pub struct ChunkBuffers {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    origin_buffer: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl ChunkBuffers {
+    /// Uploads the mesh of the chunk at `chunk_position`. `None` if the mesh
+    /// has no faces, as wgpu doesn't allow binding empty buffers.
+    pub fn new(
+        device: &wgpu::Device,
+        chunk_position: na::Vector2<i32>,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Option<Self> {
+        if indices.is_empty() {
+            return None;
+        }
+        let origin = [
+            (chunk_position.x * X_SIZE as i32) as f32,
+            0.0,
+            (chunk_position.y * Z_SIZE as i32) as f32,
+        ];
+        Some(ChunkBuffers {
+            vertex_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Chunk vertex buffer"),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                contents: bytemuck::cast_slice(vertices),
+            }),
+            index_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Chunk index buffer"),
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+                contents: bytemuck::cast_slice(indices),
+            }),
+            origin_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Chunk origin buffer"),
+                usage: wgpu::BufferUsages::VERTEX,
+                contents: bytemuck::cast_slice(&origin),
+            }),
+            index_count: indices.len() as u32,
+        })
+    }
+
+    /// Replaces the uploaded mesh with one of the same size, like the mesh
+    /// sorted again with
+    /// [sort_back_to_front](crate::chunk::ChunkMesh::sort_back_to_front)
+    pub fn write(&self, queue: &wgpu::Queue, vertices: &[Vertex], indices: &[u32]) {
+        debug_assert_eq!(indices.len(), self.index_count as usize);
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(indices));
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.origin_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
+
+/// Meshes of the chunks in view for each [RenderLayer], see
+/// [ChunkRenderer::set_meshes]
+#[derive(Default)]
+pub struct VisibleMeshes {
+    pub opaque: Vec<Rc<ChunkBuffers>>,
+    pub cutout: Vec<Rc<ChunkBuffers>>,
+    /// Furthest chunk first
+    pub transparent: Vec<Rc<ChunkBuffers>>,
+}
+
+/// Draws the chunk meshes with [chunk_shader]. The engine's diffuse pipeline
+/// stretches a texture across each quad, so the larger quads of greedy
+/// meshing couldn't repeat their block's tile.
+///
+/// Shared between the chunk loader, which picks the meshes and moves the
+/// camera every frame, and the engine, which calls
+/// [render](ChunkRenderer::render) while drawing the frame.
+pub struct ChunkRenderer {
+    opaque_pipeline: wgpu::RenderPipeline,
+    cutout_pipeline: wgpu::RenderPipeline,
+    transparent_pipeline: wgpu::RenderPipeline,
+    atlas_bind_group: wgpu::BindGroup,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    /// Meshes drawn in the next frame
+    meshes: RefCell<VisibleMeshes>,
+}
+
+impl ChunkRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        atlas: &image::RgbaImage,
+        frame_format: FrameFormat,
+    ) -> Self {
+        let atlas_size = wgpu::Extent3d {
+            width: atlas.width(),
+            height: atlas.height(),
+            depth_or_array_layers: 1,
+        };
+        let atlas_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Chunk atlas"),
+                size: atlas_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            atlas.as_raw(),
+        );
+        // Blocks keep their pixels sharp up close
+        let atlas_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Chunk atlas sampler"),
+            ..Default::default()
+        });
+        let atlas_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Chunk atlas layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Chunk atlas bind group"),
+            layout: &atlas_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &atlas_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&atlas_sampler),
+                },
+            ],
+        });
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Chunk camera buffer"),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            contents: bytemuck::cast_slice(na::Matrix4::<f32>::identity().as_slice()),
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Chunk camera layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Chunk camera bind group"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Chunk shader"),
+            source: wgpu::ShaderSource::Wgsl(chunk_shader().into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Chunk pipeline layout"),
+            bind_group_layouts: &[&atlas_layout, &camera_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |render_layer: RenderLayer| {
+            let LayerPipeline {
+                fragment_entry_point,
+                blend,
+                depth_write_enabled,
+            } = layer_pipeline(render_layer);
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("Chunk {render_layer:?} pipeline")),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &vertex_layouts(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: fragment_entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: frame_format.color,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                // The face templates don't share a winding, so both sides are drawn
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: frame_format.depth,
+                    depth_write_enabled,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: frame_format.sample_count,
+                    ..Default::default()
+                },
+                multiview: None,
+            })
+        };
+
+        ChunkRenderer {
+            opaque_pipeline: create_pipeline(RenderLayer::Opaque),
+            cutout_pipeline: create_pipeline(RenderLayer::Cutout),
+            transparent_pipeline: create_pipeline(RenderLayer::Transparent),
+            atlas_bind_group,
+            camera_buffer,
+            camera_bind_group,
+            meshes: RefCell::new(VisibleMeshes::default()),
+        }
+    }
+
+    /// Moves the camera the next frame is drawn from, see
+    /// [Projection::view_projection](crate::camera::Projection::view_projection)
+    pub fn update_camera(&self, queue: &wgpu::Queue, view_projection: na::Matrix4<f32>) {
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(view_projection.as_slice()),
+        );
+    }
+
+    /// Replaces the meshes drawn in the next frame. The opaque meshes are
+    /// drawn first and the transparent ones last, in order.
+    pub fn set_meshes(&self, meshes: VisibleMeshes) {
+        *self.meshes.borrow_mut() = meshes;
+    }
+
+    /// Draws the meshes over the frame the engine drew into `view` and
+    /// `depth_view`, resolving into `resolve_target` when the frame is
+    /// antialiased
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let meshes = self.meshes.borrow();
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Chunk render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_bind_group(0, &self.atlas_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+        for (pipeline, meshes) in [
+            (&self.opaque_pipeline, &meshes.opaque),
+            (&self.cutout_pipeline, &meshes.cutout),
+            (&self.transparent_pipeline, &meshes.transparent),
+        ] {
+            render_pass.set_pipeline(pipeline);
+            for mesh in meshes {
+                mesh.draw(&mut render_pass);
+            }
+        }
+    }
+}
@@ -4,44 +4,62 @@ use gamezap::model::Vertex;
 use lazy_static::lazy_static;
 use nalgebra as na;
 
-use crate::ATLAS_SIZE;
+use crate::{block_registry::BlockDefinition, ATLAS_SIZE};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Blocks {
-    Grass,
-    Dirt,
-    Null,
-}
-
-const FACE_TEXTURE_OFFSET: f32 = 16.0 / ATLAS_SIZE;
+pub const FACE_TEXTURE_OFFSET: f32 = 16.0 / ATLAS_SIZE;
 pub const FACE_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+/// [FACE_INDICES] split along the other diagonal
+pub const FLIPPED_FACE_INDICES: [u32; 6] = [1, 2, 3, 1, 3, 0];
 
-#[derive(Debug)]
-pub struct BlockWrapper;
+/// Converts an atlas tile `(column, row)` into the texture coordinates of its corner
+pub fn tile_coords(tile: (u32, u32)) -> (f32, f32) {
+    (
+        tile.0 as f32 * FACE_TEXTURE_OFFSET,
+        tile.1 as f32 * FACE_TEXTURE_OFFSET,
+    )
+}
 
-impl std::ops::Index<u16> for BlockWrapper {
-    type Output = Blocks;
+/// Four corners of a quad. Its texture repeats the block's atlas tile:
+/// `tex_coords` are measured in tiles from the corner of the quad and
+/// `tangent` holds the atlas coordinates of the tile, so the chunk shader
+/// samples `tangent.xy + fract(tex_coords) * FACE_TEXTURE_OFFSET`.
+pub type Face = [Vertex; 4];
 
-    fn index(&self, index: u16) -> &Self::Output {
-        match index {
-            0 => &Blocks::Grass,
-            1 => &Blocks::Dirt,
-            _ => &Blocks::Null,
-        }
-    }
-}
+/// For every face mask bit: the axis the face points along, and the axes its
+/// texture's `u` and `v` coordinates follow. Axes are `0: x, 1: y, 2: z`.
+pub const FACE_AXES: [(u8, usize, usize, usize); 6] = [
+    (0b0000_0001, 2, 0, 1),
+    (0b0000_0010, 2, 0, 1),
+    (0b0000_0100, 0, 2, 1),
+    (0b0000_1000, 0, 2, 1),
+    (0b0001_0000, 1, 0, 2),
+    (0b0010_0000, 1, 0, 2),
+];
 
-impl Blocks {
-    fn coords(&self) -> (f32, f32) {
-        match self {
-            Blocks::Grass => (0.0, 0.0),
-            Blocks::Dirt => (0.0, FACE_TEXTURE_OFFSET),
-            Blocks::Null => (1.0, 1.0),
-        }
-    }
-}
+/// For every face mask bit: the offset from a block to the neighbor that
+/// face looks at
+pub const FACE_NEIGHBORS: [(u8, [i32; 3]); 6] = [
+    (0b0000_0001, [0, 0, -1]),
+    (0b0000_0010, [0, 0, 1]),
+    (0b0000_0100, [-1, 0, 0]),
+    (0b0000_1000, [1, 0, 0]),
+    (0b0001_0000, [0, -1, 0]),
+    (0b0010_0000, [0, 1, 0]),
+];
 
-pub type Face = [Vertex; 4];
+/// Position of the block that the face `face_bit` of the block at `position`
+/// looks at
+pub fn face_neighbor(position: [i32; 3], face_bit: u8) -> [i32; 3] {
+    let (_, offset) = *FACE_NEIGHBORS
+        .iter()
+        .find(|(bit, _)| *bit == face_bit)
+        .expect("Unknown face bit");
+    [
+        position[0] + offset[0],
+        position[1] + offset[1],
+        position[2] + offset[2],
+    ]
+}
 
 pub struct MeshInfo {
     pub faces: [Face; 6],
@@ -67,6 +85,10 @@ impl MeshInfo {
         mesh
     }
 
+    /// Adds a face template to the mesh, moved by `vertex_offset`.
+    ///
+    /// The templates' texture coordinates span a single tile, `coords` is the
+    /// corner of the block's tile in the atlas, see [Face].
     pub fn append_data(
         &mut self,
         new_vertices: MutexGuard<Face>,
@@ -74,7 +96,9 @@ impl MeshInfo {
         vertex_offset: na::Vector3<f32>,
     ) {
         for (i, vert) in new_vertices.iter().enumerate() {
-            self.faces[self.face_count][i] = vert.translate(vertex_offset);
+            let mut vertex = vert.translate(vertex_offset);
+            vertex.tangent = [coords.0, coords.1, 0.0];
+            self.faces[self.face_count][i] = vertex;
         }
         self.face_count += 1;
     }
@@ -84,28 +108,28 @@ lazy_static! {
     static ref NEGATIVE_Z_FACE: Mutex<Face> = Mutex::new([
         Vertex {
             position: [0.0, 1.0, 0.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [0.0, 0.0],
             normal: [0.0, 0.0, -1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [0.0, 0.0, 0.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [0.0, 1.0],
             normal: [0.0, 0.0, -1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 0.0, 0.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [1.0, 1.0],
             normal: [0.0, 0.0, -1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 1.0, 0.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [1.0, 0.0],
             normal: [0.0, 0.0, -1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
@@ -114,28 +138,28 @@ lazy_static! {
     static ref POSITIVE_Z_FACE: Mutex<Face> = Mutex::new([
         Vertex {
             position: [0.0, 1.0, 1.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [0.0, 0.0],
             normal: [0.0, 0.0, 1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [0.0, 0.0, 1.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [0.0, 1.0],
             normal: [0.0, 0.0, 1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 0.0, 1.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [1.0, 1.0],
             normal: [0.0, 0.0, 1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 1.0, 1.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [1.0, 0.0],
             normal: [0.0, 0.0, 1.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
@@ -144,28 +168,28 @@ lazy_static! {
     static ref NEGATIVE_X_FACE: Mutex<Face> = Mutex::new([
         Vertex {
             position: [0.0, 1.0, 0.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [0.0, 0.0],
             normal: [-1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [0.0, 0.0, 0.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [0.0, 1.0],
             normal: [-1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [0.0, 0.0, 1.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [1.0, 1.0],
             normal: [-1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [0.0, 1.0, 1.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [1.0, 0.0],
             normal: [-1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
@@ -174,28 +198,28 @@ lazy_static! {
     static ref POSITIVE_X_FACE: Mutex<Face> = Mutex::new([
         Vertex {
             position: [1.0, 1.0, 0.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [0.0, 0.0],
             normal: [1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 0.0, 0.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [0.0, 1.0],
             normal: [1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 0.0, 1.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [1.0, 1.0],
             normal: [1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 1.0, 1.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [1.0, 0.0],
             normal: [1.0, 0.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
@@ -204,28 +228,28 @@ lazy_static! {
     static ref NEGATIVE_Y_FACE: Mutex<Face> = Mutex::new([
         Vertex {
             position: [0.0, 0.0, 0.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [0.0, 0.0],
             normal: [0.0, -1.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [0.0, 0.0, 1.0],
-            tex_coords: [2.0 * FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [0.0, 1.0],
             normal: [0.0, -1.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 0.0, 1.0],
-            tex_coords: [3.0 * FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [1.0, 1.0],
             normal: [0.0, -1.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 0.0, 0.0],
-            tex_coords: [3.0 * FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [1.0, 0.0],
             normal: [0.0, -1.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
@@ -241,21 +265,21 @@ lazy_static! {
         },
         Vertex {
             position: [0.0, 1.0, 1.0],
-            tex_coords: [0.0, FACE_TEXTURE_OFFSET],
+            tex_coords: [0.0, 1.0],
             normal: [0.0, 1.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 1.0, 1.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, FACE_TEXTURE_OFFSET],
+            tex_coords: [1.0, 1.0],
             normal: [0.0, 1.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
         },
         Vertex {
             position: [1.0, 1.0, 0.0],
-            tex_coords: [FACE_TEXTURE_OFFSET, 0.0],
+            tex_coords: [1.0, 0.0],
             normal: [0.0, 1.0, 0.0],
             bitangent: [0.0, 0.0, 0.0],
             tangent: [0.0, 0.0, 0.0],
@@ -273,14 +297,23 @@ impl Cube {
     pub fn new(
         position: na::Vector3<f32>,
         material_index: u32,
-        block: Blocks,
+        block: &BlockDefinition,
         face_mask: u8,
         set_verts_to_positon: bool,
     ) -> Self {
-        let texture_coords = block.coords();
-
         let mut mesh_info = MeshInfo::init();
 
+        let Some(textures) = block.textures else {
+            return Cube {
+                position,
+                material_index,
+                mesh_info,
+            };
+        };
+        let top_coords = tile_coords(textures.top);
+        let side_coords = tile_coords(textures.side);
+        let bottom_coords = tile_coords(textures.bottom);
+
         let vertex_offset = if set_verts_to_positon {
             position
         } else {
@@ -291,8 +324,7 @@ impl Cube {
         if face_mask & 0b0000_0001 == 0b0000_0001 {
             mesh_info.append_data(
                 NEGATIVE_Z_FACE.lock().unwrap(),
-                // &face_indices,
-                texture_coords,
+                side_coords,
                 vertex_offset,
             );
         }
@@ -300,8 +332,7 @@ impl Cube {
         if face_mask & 0b0000_0010 == 0b0000_0010 {
             mesh_info.append_data(
                 POSITIVE_Z_FACE.lock().unwrap(),
-                // &face_indices,
-                texture_coords,
+                side_coords,
                 vertex_offset,
             );
         }
@@ -309,8 +340,7 @@ impl Cube {
         if face_mask & 0b0000_0100 == 0b0000_0100 {
             mesh_info.append_data(
                 NEGATIVE_X_FACE.lock().unwrap(),
-                // &face_indices,
-                texture_coords,
+                side_coords,
                 vertex_offset,
             );
         }
@@ -318,8 +348,7 @@ impl Cube {
         if face_mask & 0b0000_1000 == 0b0000_1000 {
             mesh_info.append_data(
                 POSITIVE_X_FACE.lock().unwrap(),
-                // &face_indices,
-                texture_coords,
+                side_coords,
                 vertex_offset,
             );
         }
@@ -327,8 +356,7 @@ impl Cube {
         if face_mask & 0b0001_0000 == 0b0001_0000 {
             mesh_info.append_data(
                 NEGATIVE_Y_FACE.lock().unwrap(),
-                // &face_indices,
-                texture_coords,
+                bottom_coords,
                 vertex_offset,
             );
         }
@@ -336,8 +364,7 @@ impl Cube {
         if face_mask & 0b0010_0000 == 0b0010_0000 {
             mesh_info.append_data(
                 POSITIVE_Y_FACE.lock().unwrap(),
-                // &face_indices,
-                texture_coords,
+                top_coords,
                 vertex_offset,
             );
         }
@@ -349,3 +376,39 @@ impl Cube {
         }
     }
 }
+
+/// Builds a single face covering `extents` blocks, used by greedy meshing.
+/// The block's tile repeats once per block instead of stretching across the
+/// face, see [Face].
+pub fn tiled_face(
+    face_bit: u8,
+    position: na::Vector3<f32>,
+    extents: na::Vector3<f32>,
+    block: &BlockDefinition,
+) -> Option<Face> {
+    let textures = block.textures?;
+    let (template, tile) = match face_bit {
+        0b0000_0001 => (&*NEGATIVE_Z_FACE, textures.side),
+        0b0000_0010 => (&*POSITIVE_Z_FACE, textures.side),
+        0b0000_0100 => (&*NEGATIVE_X_FACE, textures.side),
+        0b0000_1000 => (&*POSITIVE_X_FACE, textures.side),
+        0b0001_0000 => (&*NEGATIVE_Y_FACE, textures.bottom),
+        0b0010_0000 => (&*POSITIVE_Y_FACE, textures.top),
+        _ => return None,
+    };
+    let (_, _, u_axis, v_axis) = *FACE_AXES.iter().find(|axes| axes.0 == face_bit)?;
+    let (tile_u, tile_v) = tile_coords(tile);
+
+    let mut face = *template.lock().unwrap();
+    for vertex in &mut face {
+        for axis in 0..3 {
+            vertex.position[axis] = position[axis] + vertex.position[axis] * extents[axis];
+        }
+        vertex.tex_coords = [
+            vertex.tex_coords[0] * extents[u_axis],
+            vertex.tex_coords[1] * extents[v_axis],
+        ];
+        vertex.tangent = [tile_u, tile_v, 0.0];
+    }
+    Some(face)
+}
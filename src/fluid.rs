@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use nalgebra as na;
+
+use crate::{
+    block_registry::{BlockRegistry, BlockRegistryError, BLOCK_REGISTRY},
+    tick::{offset, TickScheduler, TickWorld},
+};
+
+lazy_static! {
+    pub static ref FLUID_REGISTRY: FluidRegistry = FluidRegistry::new(&BLOCK_REGISTRY)
+        .unwrap_or_else(|err| panic!("Fluids need their level blocks: {err}"));
+}
+
+/// A fluid and the blocks of its flow levels. Block names are looked up in
+/// the block registry when the fluid registry is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FluidDefinition {
+    pub name: &'static str,
+    /// One block for every level, from the lowest flowing level up to the
+    /// source block
+    pub levels: &'static [&'static str],
+    /// Ticks between a change next to a fluid block and the block flowing
+    pub tick_delay: u64,
+}
+
+pub const FLUIDS: [FluidDefinition; 2] = [
+    FluidDefinition {
+        name: "water",
+        levels: &[
+            "water_1", "water_2", "water_3", "water_4", "water_5", "water_6", "water_7", "water",
+        ],
+        tick_delay: 5,
+    },
+    FluidDefinition {
+        name: "lava",
+        levels: &["lava_1", "lava_2", "lava_3", "lava"],
+        tick_delay: 30,
+    },
+];
+
+/// A block of fluid: which of [FLUIDS] it is and its flow level, from 1 up
+/// to the level of the fluid's source block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FluidBlock {
+    pub fluid: usize,
+    pub level: u8,
+}
+
+/// Maps the blocks of every fluid level to their fluid and back
+#[derive(Debug)]
+pub struct FluidRegistry {
+    pub air: u16,
+    /// Level blocks of each of [FLUIDS], lowest level first
+    levels: Vec<Vec<u16>>,
+    fluids_by_block: HashMap<u16, FluidBlock>,
+}
+
+impl FluidRegistry {
+    pub fn new(blocks: &BlockRegistry) -> Result<Self, BlockRegistryError> {
+        let mut levels = Vec::with_capacity(FLUIDS.len());
+        let mut fluids_by_block = HashMap::new();
+        for (fluid, definition) in FLUIDS.iter().enumerate() {
+            let ids = definition
+                .levels
+                .iter()
+                .map(|name| blocks.id_of(name))
+                .collect::<Result<Vec<u16>, _>>()?;
+            for (level, &id) in ids.iter().enumerate() {
+                let level = level as u8 + 1;
+                fluids_by_block.insert(id, FluidBlock { fluid, level });
+            }
+            levels.push(ids);
+        }
+
+        Ok(FluidRegistry {
+            air: blocks.id_of("air")?,
+            levels,
+            fluids_by_block,
+        })
+    }
+
+    /// Fluid and level of a block, `None` if it isn't a fluid
+    pub fn get(&self, block: u16) -> Option<FluidBlock> {
+        self.fluids_by_block.get(&block).copied()
+    }
+
+    /// Block of a fluid level, air for level 0
+    pub fn block(&self, fluid: usize, level: u8) -> u16 {
+        match level {
+            0 => self.air,
+            level => self.levels[fluid][level as usize - 1],
+        }
+    }
+
+    /// Level of a fluid's source block
+    pub fn source_level(&self, fluid: usize) -> u8 {
+        self.levels[fluid].len() as u8
+    }
+
+    /// Whether two blocks are levels of the same fluid
+    pub fn same_fluid(&self, a: u16, b: u16) -> bool {
+        match (self.get(a), self.get(b)) {
+            (Some(a), Some(b)) => a.fluid == b.fluid,
+            _ => false,
+        }
+    }
+
+    /// Height of the surface of a fluid block, as a fraction of a block.
+    /// Source blocks and blocks with the same fluid above them are full,
+    /// flowing blocks get lower the further they flowed. `None` if `block`
+    /// isn't a fluid.
+    pub fn surface_height(&self, block: u16, above: Option<u16>) -> Option<f32> {
+        let fluid = self.get(block)?;
+        if above.is_some_and(|above| self.same_fluid(block, above)) {
+            return Some(1.0);
+        }
+        Some(fluid.level as f32 / self.source_level(fluid.fluid) as f32)
+    }
+}
+
+const HORIZONTAL_OFFSETS: [[i32; 3]; 4] = [[1, 0, 0], [-1, 0, 0], [0, 0, 1], [0, 0, -1]];
+
+/// Lets the fluid block at `position` flow, run by its scheduled tick.
+///
+/// Fluid blocks only move when they are ticked. Changing a block schedules a
+/// tick for every fluid block next to it, [tick_delay](FluidDefinition::tick_delay)
+/// ticks later. A ticked fluid block
+/// - falls into the air below it, as the highest flowing level,
+/// - spreads into the air around it one level lower, if it rests on
+///   something other than air or the same fluid,
+/// - and if it is flowing, takes the level the blocks around it support,
+///   drying up when nothing does.
+///
+/// Each change schedules the blocks around it again, until everything
+/// settles.
+pub fn update_fluid(
+    ticks: &mut TickScheduler,
+    world: &mut impl TickWorld,
+    position: na::Vector3<i32>,
+) {
+    let Some(mut fluid) = fluid_at(world, position) else {
+        return;
+    };
+    let source_level = FLUID_REGISTRY.source_level(fluid.fluid);
+
+    if fluid.level < source_level {
+        let supported = supported_level(world, position, fluid.fluid);
+        if supported != fluid.level {
+            set_level(ticks, world, position, fluid.fluid, supported);
+            if supported == 0 {
+                return;
+            }
+            fluid.level = supported;
+        }
+    }
+
+    let below = offset(position, [0, -1, 0]);
+    let falling_level = source_level - 1;
+    if can_flow_into(world, below, fluid.fluid, falling_level) {
+        set_level(ticks, world, below, fluid.fluid, falling_level);
+    }
+
+    if fluid.level > 1 && spreads(world, position, fluid) {
+        for neighbor_offset in HORIZONTAL_OFFSETS {
+            let neighbor = offset(position, neighbor_offset);
+            if can_flow_into(world, neighbor, fluid.fluid, fluid.level - 1) {
+                set_level(ticks, world, neighbor, fluid.fluid, fluid.level - 1);
+            }
+        }
+    }
+}
+
+fn set_level(
+    ticks: &mut TickScheduler,
+    world: &mut impl TickWorld,
+    position: na::Vector3<i32>,
+    fluid: usize,
+    level: u8,
+) {
+    ticks.replace_block(world, position, FLUID_REGISTRY.block(fluid, level));
+}
+
+fn fluid_at(world: &impl TickWorld, position: na::Vector3<i32>) -> Option<FluidBlock> {
+    world
+        .block_at(position)
+        .and_then(|block| FLUID_REGISTRY.get(block))
+}
+
+/// Whether `fluid` can flow into `position` at `level`: it holds air or a
+/// lower flowing level of the same fluid
+fn can_flow_into(
+    world: &impl TickWorld,
+    position: na::Vector3<i32>,
+    fluid: usize,
+    level: u8,
+) -> bool {
+    match world.block_at(position) {
+        Some(block) if block == FLUID_REGISTRY.air => true,
+        Some(block) => FLUID_REGISTRY
+            .get(block)
+            .is_some_and(|other| other.fluid == fluid && other.level < level),
+        None => false,
+    }
+}
+
+/// Whether the fluid at `position` spreads to the sides: it rests on
+/// something other than air or the same fluid, so it can't fall instead
+fn spreads(world: &impl TickWorld, position: na::Vector3<i32>, fluid: FluidBlock) -> bool {
+    match world.block_at(offset(position, [0, -1, 0])) {
+        Some(below) => {
+            below != FLUID_REGISTRY.air
+                && FLUID_REGISTRY
+                    .get(below)
+                    .is_none_or(|below| below.fluid != fluid.fluid)
+        }
+        None => true,
+    }
+}
+
+/// Level the blocks around a flowing block let it keep
+fn supported_level(world: &impl TickWorld, position: na::Vector3<i32>, fluid: usize) -> u8 {
+    if fluid_at(world, offset(position, [0, 1, 0])).is_some_and(|above| above.fluid == fluid) {
+        return FLUID_REGISTRY.source_level(fluid) - 1;
+    }
+    HORIZONTAL_OFFSETS
+        .iter()
+        .filter_map(|&neighbor_offset| {
+            let neighbor = offset(position, neighbor_offset);
+            fluid_at(world, neighbor)
+                .filter(|other| other.fluid == fluid && spreads(world, neighbor, *other))
+        })
+        .map(|other| other.level - 1)
+        .max()
+        .unwrap_or(0)
+}
@@ -0,0 +1,63 @@
+use nalgebra as na;
+
+use crate::{
+    aabb::Aabb,
+    camera::{camera_axes, Projection},
+};
+
+/// A plane through `point`, with everything on the side `normal` points to
+/// in front of it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Plane {
+    normal: na::Vector3<f32>,
+    point: na::Vector3<f32>,
+}
+
+impl Plane {
+    fn distance(&self, position: na::Vector3<f32>) -> f32 {
+        self.normal.dot(&(position - self.point))
+    }
+}
+
+/// The volume a camera can see, bounded by six planes facing inwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Frustum of a camera at `eye` looking along `yaw` and `pitch`, in
+    /// radians, see [look_direction](crate::camera::look_direction)
+    pub fn new(eye: na::Vector3<f32>, yaw: f32, pitch: f32, projection: Projection) -> Self {
+        let (forward, right, up) = camera_axes(yaw, pitch);
+
+        let vertical = (projection.fovy.to_radians() / 2.0).tan();
+        let horizontal = vertical * projection.aspect;
+        let side = |normal: na::Vector3<f32>| Plane { normal, point: eye };
+        Frustum {
+            planes: [
+                Plane {
+                    normal: forward,
+                    point: eye + forward * projection.znear,
+                },
+                Plane {
+                    normal: -forward,
+                    point: eye + forward * projection.zfar,
+                },
+                side(forward * horizontal + right),
+                side(forward * horizontal - right),
+                side(forward * vertical + up),
+                side(forward * vertical - up),
+            ],
+        }
+    }
+
+    /// Whether any part of `aabb` may be inside the frustum. Boxes near a
+    /// corner of the frustum can pass without being inside, but boxes that
+    /// are inside are never rejected.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.distance(aabb.furthest_corner(plane.normal)) >= 0.0)
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Display;
+
+use nalgebra as na;
+
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    block_storage::BlockArray,
+    chunk::{HORIZONTAL_SLICE_SIZE, Y_SIZE},
+    terrain::TerrainGenerator,
+};
+
+/// Prints a [BlockHistogram] of generated chunks instead of starting the game.
+/// Can be followed by the number of chunks, [DEFAULT_HISTOGRAM_CHUNKS] if not.
+pub const HISTOGRAM_ARG: &str = "--block-histogram";
+pub const DEFAULT_HISTOGRAM_CHUNKS: usize = 64;
+
+/// Number of chunks to build a histogram of if [HISTOGRAM_ARG] is among
+/// `args`
+pub fn histogram_chunk_count(mut args: impl Iterator<Item = String>) -> Option<usize> {
+    args.position(|arg| arg == HISTOGRAM_ARG)?;
+    Some(
+        args.next()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(DEFAULT_HISTOGRAM_CHUNKS),
+    )
+}
+
+/// Number of blocks of every kind at each height, summed over a set of
+/// chunks. Used to check generation, like how deep ores go and how common
+/// they are, without opening a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockHistogram {
+    pub chunk_count: usize,
+    /// Indexed by height, then by block id
+    counts: Vec<Vec<usize>>,
+}
+
+impl BlockHistogram {
+    pub fn new() -> Self {
+        BlockHistogram {
+            chunk_count: 0,
+            counts: vec![vec![]; Y_SIZE],
+        }
+    }
+
+    /// Histogram of `chunk_count` chunks generated in a square around the
+    /// origin, row by row
+    pub fn generate(generator: &TerrainGenerator, chunk_count: usize) -> Self {
+        let side = (chunk_count as f64).sqrt().ceil() as i32;
+        let mut histogram = Self::new();
+        for index in 0..chunk_count as i32 {
+            let position = na::Vector2::new(index % side - side / 2, index / side - side / 2);
+            histogram.add(&generator.generate(position));
+        }
+        histogram
+    }
+
+    pub fn add(&mut self, blocks: &BlockArray) {
+        for (index, block) in blocks.iter().enumerate() {
+            let counts = &mut self.counts[index / HORIZONTAL_SLICE_SIZE];
+            if counts.len() <= block as usize {
+                counts.resize(block as usize + 1, 0);
+            }
+            counts[block as usize] += 1;
+        }
+        self.chunk_count += 1;
+    }
+
+    pub fn count(&self, y: usize, block: u16) -> usize {
+        self.counts[y].get(block as usize).copied().unwrap_or(0)
+    }
+
+    /// Number of blocks of a kind at all heights
+    pub fn total(&self, block: u16) -> usize {
+        (0..Y_SIZE).map(|y| self.count(y, block)).sum()
+    }
+}
+
+impl Default for BlockHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A table with a column for every block that shows up and a row for every
+/// height with anything but air, top to bottom, followed by the totals
+impl Display for BlockHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let air = BLOCK_REGISTRY.id_of("air").ok();
+        let columns: Vec<(u16, String)> = BLOCK_REGISTRY
+            .iter()
+            .filter(|definition| self.total(definition.id) > 0)
+            .map(|definition| (definition.id, definition.name.clone()))
+            .collect();
+        let width = |name: &str| name.len().max(8);
+
+        writeln!(f, "Blocks per height over {} chunks", self.chunk_count)?;
+        write!(f, "{:>5}", "y")?;
+        for (_, name) in &columns {
+            write!(f, " {name:>0$}", width(name))?;
+        }
+        writeln!(f)?;
+
+        let write_row =
+            |f: &mut std::fmt::Formatter<'_>, label: &str, count: &dyn Fn(u16) -> usize| {
+                write!(f, "{label:>5}")?;
+                for (id, name) in &columns {
+                    write!(f, " {:>1$}", count(*id), width(name))?;
+                }
+                writeln!(f)
+            };
+        for y in (0..Y_SIZE).rev() {
+            if columns
+                .iter()
+                .all(|&(id, _)| Some(id) == air || self.count(y, id) == 0)
+            {
+                continue;
+            }
+            write_row(f, &y.to_string(), &|id| self.count(y, id))?;
+        }
+        write_row(f, "total", &|id| self.total(id))
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use nalgebra as na;
+
+use crate::{
+    block_registry::{BlockRegistryError, BLOCK_REGISTRY},
+    block_storage::{sections_around, BlockArray, SectionMask},
+    chunk::{
+        split_neighborhood_position, Chunk, BLOCK_COUNT, HORIZONTAL_SLICE_SIZE, X_SIZE, Y_SIZE,
+        Z_SIZE,
+    },
+    chunk_loader::{LOADED_CHUNKS, NEIGHBOR_OFFSETS},
+    cube::{Face, FACE_NEIGHBORS},
+};
+
+/// Brightest light level, the level of open sky
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// Light levels of every block in a chunk, laid out like [BlockArray]. Each
+/// byte holds the [LightChannel::Sky] level in its high four bits and the
+/// [LightChannel::Block] level in its low four bits.
+pub type LightArray = [u8; BLOCK_COUNT];
+
+/// Light level of a block outside the world above the chunk
+pub const OPEN_SKY: u8 = MAX_LIGHT_LEVEL << 4;
+
+fn offset_position(position: [i32; 3], offset: [i32; 3]) -> [i32; 3] {
+    [
+        position[0] + offset[0],
+        position[1] + offset[1],
+        position[2] + offset[2],
+    ]
+}
+
+/// Taken by every [LightRegion] that changes light levels, so concurrent
+/// updates can't lock the same chunks in a different order
+static LIGHT_UPDATES: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light coming down from the top of the world
+    Sky,
+    /// Light given off by blocks with a
+    /// [light_emission](crate::block_registry::BlockDefinition::light_emission)
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+    fn shift(self) -> u32 {
+        match self {
+            LightChannel::Sky => 4,
+            LightChannel::Block => 0,
+        }
+    }
+
+    /// This channel's level in a packed [LightArray] entry
+    pub fn level(self, light: u8) -> u8 {
+        (light >> self.shift()) & MAX_LIGHT_LEVEL
+    }
+
+    fn set_level(self, light: &mut u8, level: u8) {
+        *light = *light & !(MAX_LIGHT_LEVEL << self.shift()) | level << self.shift();
+    }
+}
+
+/// How much a face lit by a packed [LightArray] entry is brightened, from the
+/// brighter of its two channels. Every level below the maximum is a little
+/// darker than the one above it.
+pub fn light_brightness(light: u8) -> f32 {
+    let level = LightChannel::ALL
+        .map(|channel| channel.level(light))
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+    0.85_f32.powi((MAX_LIGHT_LEVEL - level) as i32)
+}
+
+/// Multiplies the brightness in the first component of each vertex's
+/// `bitangent`, see [shade_face](crate::ambient_occlusion::shade_face), by
+/// the [light_brightness] of the block the face looks at. The chunk shader
+/// reads the product as its `shade` input.
+pub fn light_face(face: &mut Face, light: u8) {
+    let brightness = light_brightness(light);
+    for vertex in face {
+        vertex.bitangent[0] *= brightness;
+    }
+}
+
+/// Blocks and light levels of a chunk and the eight chunks around it.
+///
+/// Light spreads at most [MAX_LIGHT_LEVEL] blocks, so every light level that
+/// a change inside the chunk can affect is inside the region. Positions are
+/// relative to the chunk in the middle. Chunks that aren't loaded block light
+/// and are never lit.
+pub struct LightRegion<'a> {
+    /// The chunk in the middle followed by its neighbors in
+    /// [NEIGHBOR_OFFSETS] order
+    pub chunks: [Option<(&'a BlockArray, &'a mut LightArray)>; 9],
+    /// Sections of each of [chunks](LightRegion::chunks) whose meshes show a
+    /// changed light level
+    changed: [SectionMask; 9],
+}
+
+impl<'a> LightRegion<'a> {
+    pub fn new(chunks: [Option<(&'a BlockArray, &'a mut LightArray)>; 9]) -> Self {
+        LightRegion {
+            chunks,
+            changed: [0; 9],
+        }
+    }
+
+    /// Calls `f` with the region around the loaded chunk at `position`,
+    /// keeping every chunk involved locked until it returns. Returns `None`
+    /// if the chunk isn't in [LOADED_CHUNKS].
+    pub fn with_loaded<T>(
+        position: na::Vector2<i32>,
+        f: impl FnOnce(&mut LightRegion) -> T,
+    ) -> Option<T> {
+        let _light_updates = LIGHT_UPDATES.lock().unwrap();
+        let chunks = {
+            let loaded_chunks = LOADED_CHUNKS.read().unwrap();
+            loaded_chunks.get(&position)?;
+            [(0, 0)]
+                .into_iter()
+                .chain(NEIGHBOR_OFFSETS)
+                .map(|(x, z)| {
+                    loaded_chunks
+                        .get(&(position + na::Vector2::new(x, z)))
+                        .cloned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let blocks: Vec<Option<RwLockReadGuard<BlockArray>>> = chunks
+            .iter()
+            .map(|chunk| chunk.as_ref().map(|chunk| chunk.blocks.read().unwrap()))
+            .collect();
+        let mut light: Vec<Option<RwLockWriteGuard<LightArray>>> = chunks
+            .iter()
+            .map(|chunk| chunk.as_ref().map(|chunk| chunk.light.write().unwrap()))
+            .collect();
+
+        let mut region_chunks = blocks
+            .iter()
+            .zip(light.iter_mut())
+            .map(|(blocks, light)| Some((&**blocks.as_ref()?, &mut **light.as_mut()?)));
+        let mut region = LightRegion::new(std::array::from_fn(|_| region_chunks.next().flatten()));
+        Some(f(&mut region))
+    }
+
+    /// Offsets of the chunks whose light levels changed, starting with
+    /// `(0, 0)` for the chunk in the middle, and the sections whose meshes
+    /// show the changes
+    pub fn changed_sections(&self) -> Vec<((i32, i32), SectionMask)> {
+        [(0, 0)]
+            .into_iter()
+            .chain(NEIGHBOR_OFFSETS)
+            .zip(self.changed)
+            .filter(|(_, changed)| *changed != 0)
+            .collect()
+    }
+
+    /// The chunk and block index of a position, `None` outside the world or
+    /// in a chunk that isn't loaded
+    fn cell(&self, position: [i32; 3]) -> Option<(usize, usize)> {
+        let [x, y, z] = position;
+        if !(0..Y_SIZE as i32).contains(&y) {
+            return None;
+        }
+        let (slot, x, z) = split_neighborhood_position(x, z)?;
+        self.chunks[slot].as_ref()?;
+        Some((slot, Chunk::block_index(x, y as usize, z)))
+    }
+
+    fn block(&self, (slot, index): (usize, usize)) -> u16 {
+        self.chunks[slot]
+            .as_ref()
+            .map_or(0, |(blocks, _)| blocks[index])
+    }
+
+    fn light(&self, channel: LightChannel, (slot, index): (usize, usize)) -> u8 {
+        self.chunks[slot]
+            .as_ref()
+            .map_or(0, |(_, light)| channel.level(light[index]))
+    }
+
+    fn set_light(&mut self, channel: LightChannel, (slot, index): (usize, usize), level: u8) {
+        if let Some((_, light)) = self.chunks[slot].as_mut() {
+            channel.set_level(&mut light[index], level);
+            self.changed[slot] |= sections_around(index / HORIZONTAL_SLICE_SIZE);
+        }
+    }
+
+    fn transparent(&self, cell: (usize, usize)) -> Result<bool, BlockRegistryError> {
+        Ok(BLOCK_REGISTRY.get(self.block(cell))?.transparent)
+    }
+
+    fn emission(&self, cell: (usize, usize)) -> Result<u8, BlockRegistryError> {
+        Ok(BLOCK_REGISTRY.get(self.block(cell))?.light_emission)
+    }
+
+    /// Lights the chunk in the middle after its blocks were loaded, while
+    /// every light level in it is still 0. Skylight fills each column down to
+    /// the first block that isn't transparent, and then spreads along with
+    /// block light from emissive blocks and from the loaded neighbors.
+    pub fn light_chunk(&mut self) -> Result<(), BlockRegistryError> {
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+
+        for x in 0..X_SIZE as i32 {
+            for z in 0..Z_SIZE as i32 {
+                for y in (0..Y_SIZE as i32).rev() {
+                    let cell = (0, Chunk::block_index(x as usize, y as usize, z as usize));
+                    if !self.transparent(cell)? {
+                        break;
+                    }
+                    self.set_light(LightChannel::Sky, cell, MAX_LIGHT_LEVEL);
+                    sky_queue.push_back([x, y, z]);
+                }
+
+                for y in 0..Y_SIZE as i32 {
+                    let cell = (0, Chunk::block_index(x as usize, y as usize, z as usize));
+                    let emission = self.emission(cell)?;
+                    if emission > 0 {
+                        self.set_light(LightChannel::Block, cell, emission);
+                        block_queue.push_back([x, y, z]);
+                    }
+                }
+            }
+        }
+
+        // Light already in the neighbors spreads in across the borders
+        for y in 0..Y_SIZE as i32 {
+            for i in 0..X_SIZE.max(Z_SIZE) as i32 {
+                let border = [
+                    [-1, y, i],
+                    [X_SIZE as i32, y, i],
+                    [i, y, -1],
+                    [i, y, Z_SIZE as i32],
+                ];
+                for position in border {
+                    let Some(cell) = self.cell(position) else {
+                        continue;
+                    };
+                    if self.light(LightChannel::Sky, cell) > 1 {
+                        sky_queue.push_back(position);
+                    }
+                    if self.light(LightChannel::Block, cell) > 1 {
+                        block_queue.push_back(position);
+                    }
+                }
+            }
+        }
+
+        self.spread(LightChannel::Sky, sky_queue)?;
+        self.spread(LightChannel::Block, block_queue)
+    }
+
+    /// Updates the light levels around `position` after the block there was
+    /// replaced. The new block must already be in the region.
+    ///
+    /// Light that may have come through or from the old block is removed
+    /// first, spreading out for as long as the levels keep falling. The
+    /// levels around the removed area and the new block's own light then
+    /// spread back in.
+    pub fn update_block(&mut self, position: [i32; 3]) -> Result<(), BlockRegistryError> {
+        let Some(cell) = self.cell(position) else {
+            return Ok(());
+        };
+
+        for channel in LightChannel::ALL {
+            let mut removal_queue = VecDeque::new();
+            let mut spread_queue = VecDeque::new();
+
+            let level = self.light(channel, cell);
+            if level > 0 {
+                self.set_light(channel, cell, 0);
+                removal_queue.push_back((position, level));
+            }
+            self.remove(channel, removal_queue, &mut spread_queue)?;
+
+            if self.transparent(cell)? {
+                for (_, offset) in FACE_NEIGHBORS {
+                    let neighbor = offset_position(position, offset);
+                    if self
+                        .cell(neighbor)
+                        .is_some_and(|neighbor| self.light(channel, neighbor) > 0)
+                    {
+                        spread_queue.push_back(neighbor);
+                    }
+                }
+                if channel == LightChannel::Sky && position[1] == Y_SIZE as i32 - 1 {
+                    self.set_light(channel, cell, MAX_LIGHT_LEVEL);
+                    spread_queue.push_back(position);
+                }
+            }
+            if channel == LightChannel::Block {
+                let emission = self.emission(cell)?;
+                if emission > self.light(channel, cell) {
+                    self.set_light(channel, cell, emission);
+                    spread_queue.push_back(position);
+                }
+            }
+
+            self.spread(channel, spread_queue)?;
+        }
+        Ok(())
+    }
+
+    /// The level light at `level` has after moving by `offset`. Full
+    /// skylight doesn't weaken going straight down.
+    fn spread_level(channel: LightChannel, level: u8, offset: [i32; 3]) -> u8 {
+        if channel == LightChannel::Sky && level == MAX_LIGHT_LEVEL && offset == [0, -1, 0] {
+            level
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+
+    /// Raises the levels of transparent blocks around every queued position
+    /// until no level can rise any further
+    fn spread(
+        &mut self,
+        channel: LightChannel,
+        mut queue: VecDeque<[i32; 3]>,
+    ) -> Result<(), BlockRegistryError> {
+        while let Some(position) = queue.pop_front() {
+            let Some(cell) = self.cell(position) else {
+                continue;
+            };
+            let level = self.light(channel, cell);
+            for (_, offset) in FACE_NEIGHBORS {
+                let neighbor_position = offset_position(position, offset);
+                let Some(neighbor) = self.cell(neighbor_position) else {
+                    continue;
+                };
+                let neighbor_level = Self::spread_level(channel, level, offset);
+                if neighbor_level > self.light(channel, neighbor) && self.transparent(neighbor)? {
+                    self.set_light(channel, neighbor, neighbor_level);
+                    queue.push_back(neighbor_position);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Darkens every block whose light came from a queued position and its
+    /// old level. Lit blocks at the edge of the darkened area, and emissive
+    /// blocks inside it, are added to `spread_queue` to light it again.
+    fn remove(
+        &mut self,
+        channel: LightChannel,
+        mut queue: VecDeque<([i32; 3], u8)>,
+        spread_queue: &mut VecDeque<[i32; 3]>,
+    ) -> Result<(), BlockRegistryError> {
+        while let Some((position, level)) = queue.pop_front() {
+            for (_, offset) in FACE_NEIGHBORS {
+                let neighbor_position = offset_position(position, offset);
+                let Some(neighbor) = self.cell(neighbor_position) else {
+                    continue;
+                };
+                let neighbor_level = self.light(channel, neighbor);
+                if neighbor_level == 0 {
+                    continue;
+                }
+
+                // Full skylight below full skylight came straight down from it
+                let lit_by_position = neighbor_level < level
+                    || neighbor_level == MAX_LIGHT_LEVEL
+                        && Self::spread_level(channel, level, offset) == MAX_LIGHT_LEVEL;
+                if lit_by_position {
+                    self.set_light(channel, neighbor, 0);
+                    queue.push_back((neighbor_position, neighbor_level));
+                    if channel == LightChannel::Block {
+                        let emission = self.emission(neighbor)?;
+                        if emission > 0 {
+                            self.set_light(channel, neighbor, emission);
+                            spread_queue.push_back(neighbor_position);
+                        }
+                    }
+                } else {
+                    spread_queue.push_back(neighbor_position);
+                }
+            }
+        }
+        Ok(())
+    }
+}
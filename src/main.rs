@@ -1,33 +1,97 @@
 use std::{
     borrow::BorrowMut,
     cell::{Ref, RefCell, RefMut},
+    rc::Rc,
     sync::Arc,
 };
 
-use chunk_loader::ChunkLoader;
+use camera::Projection;
+use chunk::{MeshingMode, UnloadedNeighbors};
+use chunk_loader::{ChunkLoader, DEFAULT_RENDER_DISTANCE};
+use chunk_renderer::{ChunkRenderer, FrameFormat};
 use gamezap::{
     module_manager::ModuleManager, renderer::Renderer, texture::Texture, EngineDetails,
     EngineSettings, EngineSystems, FrameDependancy, GameZap,
 };
+use histogram::{histogram_chunk_count, BlockHistogram};
 use nalgebra as na;
+use player::Player;
 use sdl2::keyboard::Keycode;
+use terrain::TERRAIN_GENERATOR;
 
+mod aabb;
+mod ambient_occlusion;
+mod biome;
+mod block_registry;
+mod block_storage;
+mod camera;
+mod caves;
 mod chunk;
 mod chunk_loader;
+mod chunk_mesher;
+mod chunk_renderer;
 mod cube;
+mod fluid;
+mod frustum;
+mod histogram;
+mod light;
+mod noise;
+mod ores;
+mod player;
+mod raycast;
+mod region;
 mod ring_buffer;
+mod structure;
+mod terrain;
+mod tick;
 
 #[cfg(test)]
 mod tests {
+    pub mod ambient_occlusion;
+    pub mod biome;
+    pub mod block_registry;
+    pub mod block_storage;
+    pub mod camera;
+    pub mod caves;
+    pub mod chunk_loader;
+    pub mod chunk_renderer;
     pub mod chunk_threading;
+    pub mod common;
+    pub mod cube;
+    pub mod face_mask;
+    pub mod fluid;
+    pub mod frustum;
+    pub mod greedy_meshing;
+    pub mod histogram;
+    pub mod light;
+    pub mod lod;
+    pub mod meshing;
+    pub mod ores;
+    pub mod player;
+    pub mod raycast;
+    pub mod region;
     pub mod ring_buffer;
+    pub mod sections;
+    pub mod structure;
+    pub mod terrain;
+    pub mod tick;
+    pub mod transparency;
 }
 
 const ATLAS_SIZE: f32 = 256.0;
+const ATLAS_PATH: &str = "textures/atlas.png";
 
 
 #[tokio::main]
 async fn main() {
+    // Show info messages like the culling stats unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    if let Some(chunk_count) = histogram_chunk_count(std::env::args()) {
+        print!("{}", BlockHistogram::generate(&TERRAIN_GENERATOR, chunk_count));
+        return;
+    }
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let event_pump = sdl_context.event_pump().unwrap();
@@ -46,17 +110,22 @@ async fn main() {
         a: 1.0,
     };
 
+    let projection = Projection {
+        aspect: window_size.0 as f32 / window_size.1 as f32,
+        ..Default::default()
+    };
+    let spawn_position = na::Vector3::new(0.0, 100.0, 0.0);
     let module_manager = ModuleManager::builder()
         .mesh_manager()
         .camera_manager(
-            na::Vector3::new(0.0, 300.0, 0.0),
+            spawn_position,
             0.1,
             7.0,
             0.0,
             0.0,
-            45.0,
-            0.01,
-            1200.0,
+            projection.fovy,
+            projection.znear,
+            projection.zfar,
             window_size.0 as f32,
             window_size.1 as f32,
         )
@@ -79,47 +148,60 @@ async fn main() {
     let renderer_device = renderer.device.clone();
     let renderer_queue = renderer.queue.clone();
 
-    let mut material_manager = renderer.module_manager.material_manager.borrow_mut();
-
-    let texture_atlas = material_manager.new_material(
-        "Texture Atlas",
+    let atlas = image::open(ATLAS_PATH).unwrap().to_rgba8();
+    let frame_format = FrameFormat {
+        color: renderer.config.format,
+        depth: Texture::DEPTH_FORMAT,
+        sample_count: renderer.sample_count,
+    };
+    let chunk_renderer = Rc::new(ChunkRenderer::new(
         &renderer_device,
-        Some(
-            pollster::block_on(Texture::load_texture(
-                "atlas.png",
-                &renderer_device,
-                &renderer_queue.clone(),
-                false,
-            ))
-            .unwrap(),
-        ),
-        None,
-    );
-
-    drop(material_manager);
-
-    let mesh_manager = renderer.module_manager.mesh_manager.as_ref().unwrap();
+        &renderer_queue,
+        &atlas,
+        frame_format,
+    ));
 
-    let chunk_loader = ChunkLoader::new(
-        texture_atlas.1,
-        mesh_manager.lock().unwrap().diffuse_pipeline_models.len(),
-    );
+    let mut chunk_loader =
+        ChunkLoader::new(render_distance_argument().unwrap_or(DEFAULT_RENDER_DISTANCE));
+    chunk_loader.chunk_renderer = Some(chunk_renderer.clone());
+    chunk_loader.projection = projection;
+    let player = Player::from_eye(spawn_position);
+    chunk_loader.player_aabb = Some(player.shared_aabb());
+    if std::env::args().any(|arg| arg == "--greedy") {
+        chunk_loader.meshing_mode = MeshingMode::Greedy;
+    }
+    if std::env::args().any(|arg| arg == "--hide-unloaded-neighbors") {
+        chunk_loader.unloaded_neighbors = UnloadedNeighbors::Hide;
+    }
 
-    chunk_loader.initialize_chunks(mesh_manager.clone(), renderer_device.clone());
+    chunk_loader.initialize_chunks();
 
     let chunk_loader_frame_dependancy: RefCell<Box<dyn FrameDependancy>> =
         RefCell::new(Box::new(chunk_loader));
+    let player_frame_dependancy: RefCell<Box<dyn FrameDependancy>> =
+        RefCell::new(Box::new(player));
 
     renderer.prep_renderer();
 
     drop(renderer);
 
+    // Chunks are drawn over the frame the engine cleared, into its color
+    // and depth targets
+    engine.renderer.add_render_pass(Box::new(
+        move |encoder, view, resolve_target, depth_view| {
+            chunk_renderer.render(encoder, view, resolve_target, depth_view)
+        },
+    ));
+
     engine
         .keybinds
         .insert(Keycode::Escape, (Box::new(toggle_cursor), vec![]));
 
     engine.main_loop(vec![
-        (Box::new(input), vec![]),
+        (
+            Box::new(move_player),
+            vec![player_frame_dependancy.borrow_mut()],
+        ),
         (
             Box::new(recalculate_chunks),
             vec![chunk_loader_frame_dependancy.borrow_mut()],
@@ -127,27 +209,26 @@ async fn main() {
     ]).await;
 }
 
-fn input(
+#[allow(clippy::ptr_arg)]
+fn move_player(
     engine_details: RefMut<EngineDetails>,
     renderer: &Renderer,
     engine_systems: Ref<EngineSystems>,
-    _frame_dependancies: &mut Vec<RefMut<Box<dyn FrameDependancy>>>,
+    frame_dependancies: &mut Vec<RefMut<Box<dyn FrameDependancy>>>,
 ) {
-    let camera_manager = &renderer.module_manager.camera_manager;
-    if let Some(camera_manager) = camera_manager {
-        let camera_manager = camera_manager.borrow();
-        let mut camera = camera_manager.camera.borrow_mut();
-        if let Some(mouse_state) = engine_details.mouse_state.0 {
-            camera.transform_camera(
-                &engine_details.pressed_scancodes,
-                &mouse_state,
-                engine_systems
-                    .sdl_context
-                    .borrow()
-                    .mouse()
-                    .relative_mouse_mode(),
-                engine_details.last_frame_duration.as_seconds_f32(),
-            );
+    let player = frame_dependancies[0].borrow_mut();
+    player.frame_update(engine_details, renderer, engine_systems);
+}
+
+/// Value of the `--render-distance <chunks>` command line argument
+fn render_distance_argument() -> Option<usize> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--render-distance");
+    args.next()?;
+    match args.next().map(|value| value.parse()) {
+        Some(Ok(render_distance)) => Some(render_distance),
+        _ => {
+            log::warn!("--render-distance expects a number of chunks");
+            None
         }
     }
 }
@@ -0,0 +1,204 @@
+/// Mixes a seed and a pair of integer coordinates into well distributed bits
+pub fn hash_2d(seed: u64, x: i32, y: i32) -> u64 {
+    let mut hash = seed
+        ^ (x as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    hash ^ (hash >> 33)
+}
+
+/// [hash_2d] with a third coordinate mixed into the seed
+pub fn hash_3d(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    hash_2d(
+        seed ^ (z as u32 as u64).wrapping_mul(0x1656_67B1_9E37_79F9),
+        x,
+        y,
+    )
+}
+
+/// Sequence of random numbers derived from a single seed
+#[derive(Debug, Clone)]
+pub struct ChunkRandom(pub u64);
+
+impl ChunkRandom {
+    pub fn next_bits(&mut self) -> u64 {
+        self.0 = hash_2d(self.0, 1, 0);
+        self.0
+    }
+
+    /// A number from 0 up to, but not including, `bound`
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_bits() % bound as u64) as usize
+    }
+
+    /// A fraction in [0, 1)
+    pub fn fraction(&mut self) -> f64 {
+        (self.next_bits() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+/// Seeded gradient noise. Lattice gradients are derived by hashing the lattice
+/// coordinates with the seed, so no permutation table has to be stored and the
+/// same seed always produces the same field.
+#[derive(Debug, Clone, Copy)]
+pub struct Perlin {
+    seed: u64,
+}
+
+impl Perlin {
+    pub const fn new(seed: u64) -> Self {
+        Perlin { seed }
+    }
+
+    fn gradient_2d(&self, x: i32, y: i32, dx: f64, dy: f64) -> f64 {
+        match hash_2d(self.seed, x, y) & 7 {
+            0 => dx + dy,
+            1 => dx - dy,
+            2 => -dx + dy,
+            3 => -dx - dy,
+            4 => dx,
+            5 => -dx,
+            6 => dy,
+            _ => -dy,
+        }
+    }
+
+    /// Samples the noise field, returning a value roughly in `[-1, 1]`
+    pub fn get_2d(&self, x: f64, y: f64) -> f64 {
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let dx = x - x0;
+        let dy = y - y0;
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let n00 = self.gradient_2d(x0, y0, dx, dy);
+        let n10 = self.gradient_2d(x0 + 1, y0, dx - 1.0, dy);
+        let n01 = self.gradient_2d(x0, y0 + 1, dx, dy - 1.0);
+        let n11 = self.gradient_2d(x0 + 1, y0 + 1, dx - 1.0, dy - 1.0);
+
+        let u = fade(dx);
+        let v = fade(dy);
+        lerp(lerp(n00, n10, u), lerp(n01, n11, u), v)
+    }
+
+    /// One of the twelve gradients pointing at the edges of a cube, dotted
+    /// with the offset from the lattice point
+    fn gradient_3d(&self, lattice: [i32; 3], offset: [f64; 3]) -> f64 {
+        let [dx, dy, dz] = offset;
+        match hash_3d(self.seed, lattice[0], lattice[1], lattice[2]) % 12 {
+            0 => dx + dy,
+            1 => -dx + dy,
+            2 => dx - dy,
+            3 => -dx - dy,
+            4 => dx + dz,
+            5 => -dx + dz,
+            6 => dx - dz,
+            7 => -dx - dz,
+            8 => dy + dz,
+            9 => -dy + dz,
+            10 => dy - dz,
+            _ => -dy - dz,
+        }
+    }
+
+    /// Samples the 3D noise field, returning a value roughly in `[-1, 1]`
+    pub fn get_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        let floor = [x.floor(), y.floor(), z.floor()];
+        let fraction = [x - floor[0], y - floor[1], z - floor[2]];
+        let lattice = floor.map(|component| component as i32);
+
+        let corner = |corner: usize| {
+            let step = [corner & 1, corner >> 1 & 1, corner >> 2 & 1];
+            self.gradient_3d(
+                std::array::from_fn(|axis| lattice[axis] + step[axis] as i32),
+                std::array::from_fn(|axis| fraction[axis] - step[axis] as f64),
+            )
+        };
+        let [u, v, w] = fraction.map(fade);
+        let bottom = lerp(
+            lerp(corner(0), corner(1), u),
+            lerp(corner(2), corner(3), u),
+            v,
+        );
+        let top = lerp(
+            lerp(corner(4), corner(5), u),
+            lerp(corner(6), corner(7), u),
+            v,
+        );
+        lerp(bottom, top, w)
+    }
+}
+
+/// Fractal brownian motion: several octaves of [Perlin] noise summed with
+/// increasing frequency and decreasing amplitude
+#[derive(Debug, Clone, Copy)]
+pub struct Fbm {
+    noise: Perlin,
+    pub octaves: u32,
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+}
+
+impl Fbm {
+    pub const fn new(seed: u64, octaves: u32, frequency: f64) -> Self {
+        Fbm {
+            noise: Perlin::new(seed),
+            octaves,
+            frequency,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+
+    /// Samples every octave and normalizes the sum back into roughly `[-1, 1]`
+    pub fn get_2d(&self, x: f64, y: f64) -> f64 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut max_amplitude = 0.0;
+        for octave in 0..self.octaves {
+            // Offset every octave so their lattices don't line up at the origin
+            let offset = octave as f64 * 17.31;
+            total += self
+                .noise
+                .get_2d(x * frequency + offset, y * frequency + offset)
+                * amplitude;
+            max_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        total / max_amplitude
+    }
+
+    /// 3D version of [get_2d](Fbm::get_2d)
+    pub fn get_3d(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut max_amplitude = 0.0;
+        for octave in 0..self.octaves {
+            let offset = octave as f64 * 17.31;
+            total += self.noise.get_3d(
+                x * frequency + offset,
+                y * frequency + offset,
+                z * frequency + offset,
+            ) * amplitude;
+            max_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        total / max_amplitude
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
@@ -0,0 +1,155 @@
+use std::{fmt::Display, path::Path};
+
+use lazy_static::lazy_static;
+use nalgebra as na;
+use serde::Deserialize;
+
+use crate::{
+    block_registry::{BlockRegistry, BLOCK_REGISTRY},
+    block_storage::BlockArray,
+    chunk::{Chunk, X_SIZE, Y_SIZE, Z_SIZE},
+    noise::{hash_3d, ChunkRandom},
+};
+
+pub const ORE_DEFINITIONS_PATH: &str = "ores.ron";
+
+lazy_static! {
+    pub static ref ORES: Vec<Ore> = load_ores(ORE_DEFINITIONS_PATH, &BLOCK_REGISTRY)
+        .unwrap_or_else(|err| panic!("Failed to load {ORE_DEFINITIONS_PATH}: {err}"));
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct OreDefinition {
+    pub block: String,
+    pub min_height: usize,
+    /// Highest height veins reach, included
+    pub max_height: usize,
+    /// Most blocks a single vein can have
+    pub vein_size: usize,
+    /// Average number of veins in a chunk
+    pub veins_per_chunk: f64,
+}
+
+/// An [OreDefinition] with its block looked up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ore {
+    pub block: u16,
+    pub min_height: usize,
+    pub max_height: usize,
+    pub vein_size: usize,
+    pub veins_per_chunk: f64,
+}
+
+#[derive(Debug)]
+pub enum OreError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    UnknownBlock(String),
+    /// The height range of an ore is empty or doesn't fit in the world
+    InvalidHeights(String),
+}
+
+impl Display for OreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OreError::Io(err) => write!(f, "could not read ore definitions: {err}"),
+            OreError::Parse(err) => write!(f, "invalid ore definitions: {err}"),
+            OreError::UnknownBlock(name) => write!(f, "no block is registered as ore \"{name}\""),
+            OreError::InvalidHeights(name) => write!(
+                f,
+                "ore \"{name}\" needs a height range from 0 to {}",
+                Y_SIZE - 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OreError {}
+
+pub fn parse_ores(source: &str, blocks: &BlockRegistry) -> Result<Vec<Ore>, OreError> {
+    let definitions: Vec<OreDefinition> = ron::from_str(source).map_err(OreError::Parse)?;
+    definitions
+        .into_iter()
+        .map(|definition| {
+            if definition.min_height > definition.max_height || definition.max_height >= Y_SIZE {
+                return Err(OreError::InvalidHeights(definition.block));
+            }
+            Ok(Ore {
+                block: blocks
+                    .id_of(&definition.block)
+                    .map_err(|_| OreError::UnknownBlock(definition.block.clone()))?,
+                min_height: definition.min_height,
+                max_height: definition.max_height,
+                vein_size: definition.vein_size,
+                veins_per_chunk: definition.veins_per_chunk,
+            })
+        })
+        .collect()
+}
+
+pub fn load_ores(path: impl AsRef<Path>, blocks: &BlockRegistry) -> Result<Vec<Ore>, OreError> {
+    let source = std::fs::read_to_string(path).map_err(OreError::Io)?;
+    parse_ores(&source, blocks)
+}
+
+/// Steps a vein can take, one block along each axis
+const VEIN_STEPS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// Places ore veins in the stone of generated chunks.
+///
+/// Veins never leave their chunk, so where they go only depends on the seed
+/// and the chunk's position.
+#[derive(Debug, Clone)]
+pub struct OreVeins {
+    seed: u64,
+    pub ores: Vec<Ore>,
+}
+
+impl OreVeins {
+    pub fn new(seed: u64, ores: Vec<Ore>) -> Self {
+        OreVeins { seed, ores }
+    }
+
+    /// Replaces some of the `stone` blocks of the chunk at `position` with
+    /// ore veins
+    pub fn place(&self, blocks: &mut BlockArray, position: na::Vector2<i32>, stone: u16) {
+        for (ore_index, ore) in self.ores.iter().enumerate() {
+            let mut random =
+                ChunkRandom(hash_3d(self.seed, position.x, ore_index as i32, position.y));
+            let vein_count = ore.veins_per_chunk as usize
+                + (random.fraction() < ore.veins_per_chunk.fract()) as usize;
+
+            for _ in 0..vein_count {
+                let mut vein = [
+                    random.below(X_SIZE) as i32,
+                    (ore.min_height + random.below(ore.max_height - ore.min_height + 1)) as i32,
+                    random.below(Z_SIZE) as i32,
+                ];
+                let bounds = [
+                    (0, X_SIZE as i32 - 1),
+                    (ore.min_height as i32, ore.max_height as i32),
+                    (0, Z_SIZE as i32 - 1),
+                ];
+                for _ in 0..ore.vein_size {
+                    let index =
+                        Chunk::block_index(vein[0] as usize, vein[1] as usize, vein[2] as usize);
+                    if blocks[index] == stone {
+                        blocks.set(index, ore.block);
+                    }
+
+                    let step = VEIN_STEPS[random.below(VEIN_STEPS.len())];
+                    for ((coordinate, step), (min, max)) in vein.iter_mut().zip(step).zip(bounds) {
+                        *coordinate = (*coordinate + step).clamp(min, max);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    cell::{Cell, RefMut},
+    collections::HashSet,
+    rc::Rc,
+    time::Duration,
+};
+
+use gamezap::FrameDependancy;
+use nalgebra as na;
+use sdl2::keyboard::Scancode;
+
+use crate::{
+    aabb::Aabb, block_registry::BLOCK_REGISTRY, chunk::Y_SIZE, chunk_loader::loaded_block,
+};
+
+/// Length of one physics step. Physics runs a whole number of steps every
+/// frame, however long the frame took.
+pub const PHYSICS_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Steps run in a single frame at most. Time beyond that, like a hitch while
+/// chunks load, is dropped instead of being caught up on.
+pub const MAX_STEPS_PER_FRAME: u32 = 10;
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+/// Height of the camera above the bottom of the player
+pub const EYE_HEIGHT: f32 = 1.62;
+
+pub const WALK_SPEED: f32 = 4.3;
+/// Downwards acceleration in blocks per second squared
+pub const GRAVITY: f32 = 32.0;
+/// Upwards speed of a jump, enough to get onto a block one higher
+pub const JUMP_SPEED: f32 = 9.0;
+pub const MAX_FALL_SPEED: f32 = 60.0;
+
+/// Key that switches between [MovementMode::Fly] and [MovementMode::Walk]
+pub const MOVEMENT_MODE_KEY: Scancode = Scancode::F;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementMode {
+    /// Free camera movement that passes through blocks
+    Fly,
+    /// Falls, jumps and collides with solid blocks
+    Walk,
+}
+
+/// What the player wants to do during a physics step
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovementInput {
+    /// Horizontal direction to walk in, as `(x, z)`. Longer than one is
+    /// shortened to one.
+    pub direction: na::Vector2<f32>,
+    pub jump: bool,
+}
+
+impl MovementInput {
+    /// Walking input from the WASD keys and the space bar, relative to a
+    /// camera looking along `yaw`
+    pub fn from_keys(pressed_scancodes: &HashSet<Scancode>, yaw: f32) -> Self {
+        let pressed = |scancode| pressed_scancodes.contains(&scancode) as i32 as f32;
+        let forward = na::Vector2::new(yaw.cos(), yaw.sin());
+        let right = na::Vector2::new(-yaw.sin(), yaw.cos());
+        MovementInput {
+            direction: forward * (pressed(Scancode::W) - pressed(Scancode::S))
+                + right * (pressed(Scancode::D) - pressed(Scancode::A)),
+            jump: pressed_scancodes.contains(&Scancode::Space),
+        }
+    }
+}
+
+/// The player's body, which the camera follows while walking
+pub struct Player {
+    /// Center of the bottom of the player's box
+    pub position: na::Vector3<f32>,
+    pub velocity: na::Vector3<f32>,
+    pub on_ground: bool,
+    pub mode: MovementMode,
+    /// Position before the last physics step, the camera is placed between
+    /// it and [position](Player::position) to move smoothly between steps
+    previous_position: na::Vector3<f32>,
+    /// Frame time that hasn't been simulated yet, less than a
+    /// [PHYSICS_TIMESTEP] after every [update](Player::update)
+    accumulator: Duration,
+    /// Whether [MOVEMENT_MODE_KEY] was pressed during the last frame
+    previous_mode_key: bool,
+    /// The player's box at the end of the last frame, see
+    /// [shared_aabb](Player::shared_aabb)
+    shared_aabb: Rc<Cell<Aabb>>,
+}
+
+impl Player {
+    pub fn new(position: na::Vector3<f32>) -> Self {
+        let player = Player {
+            position,
+            velocity: na::Vector3::zeros(),
+            on_ground: false,
+            mode: MovementMode::Fly,
+            previous_position: position,
+            accumulator: Duration::ZERO,
+            previous_mode_key: false,
+            shared_aabb: Rc::default(),
+        };
+        player.shared_aabb.set(player.aabb());
+        player
+    }
+
+    /// Player whose camera is at `eye`
+    pub fn from_eye(eye: na::Vector3<f32>) -> Self {
+        Self::new(eye - na::Vector3::new(0.0, EYE_HEIGHT, 0.0))
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let half_width = PLAYER_WIDTH / 2.0;
+        Aabb {
+            min: self.position - na::Vector3::new(half_width, 0.0, half_width),
+            max: self.position + na::Vector3::new(half_width, PLAYER_HEIGHT, half_width),
+        }
+    }
+
+    /// The player's box as of the end of its last frame update, for the
+    /// chunk loader to keep placed blocks out of
+    pub fn shared_aabb(&self) -> Rc<Cell<Aabb>> {
+        self.shared_aabb.clone()
+    }
+
+    /// Where the camera goes, `alpha` of the way from the position before
+    /// the last step to the current one
+    pub fn eye(&self, alpha: f32) -> na::Vector3<f32> {
+        self.previous_position.lerp(&self.position, alpha) + na::Vector3::new(0.0, EYE_HEIGHT, 0.0)
+    }
+
+    pub fn set_mode(&mut self, mode: MovementMode) {
+        self.mode = mode;
+        self.velocity = na::Vector3::zeros();
+        self.on_ground = false;
+        self.previous_position = self.position;
+        self.accumulator = Duration::ZERO;
+    }
+
+    /// Moves the player by one [PHYSICS_TIMESTEP]: walks towards the input
+    /// direction, jumps off the ground and falls, stopping at solid blocks
+    /// one axis at a time
+    pub fn step(
+        &mut self,
+        input: MovementInput,
+        mut is_solid: impl FnMut(na::Vector3<i32>) -> bool,
+    ) {
+        let dt = PHYSICS_TIMESTEP.as_secs_f32();
+        self.previous_position = self.position;
+
+        let direction = if input.direction.norm() > 1.0 {
+            input.direction.normalize()
+        } else {
+            input.direction
+        };
+        self.velocity.x = direction.x * WALK_SPEED;
+        self.velocity.z = direction.y * WALK_SPEED;
+        if input.jump && self.on_ground {
+            self.velocity.y = JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-MAX_FALL_SPEED);
+
+        // Vertical first, so walking off a ledge and into a wall in the same
+        // step still lands on the ledge
+        self.on_ground = false;
+        for axis in [1, 0, 2] {
+            let distance = self.velocity[axis] * dt;
+            let allowed = self.aabb().sweep(axis, distance, &mut is_solid);
+            self.position[axis] += allowed;
+            if allowed != distance {
+                if axis == 1 && distance < 0.0 {
+                    self.on_ground = true;
+                }
+                self.velocity[axis] = 0.0;
+            }
+        }
+    }
+
+    /// Runs as many physics steps as fit into the time since the last
+    /// update, carrying the rest over to the next one. Returns how far the
+    /// leftover time is into the next step, see [eye](Player::eye).
+    pub fn update(
+        &mut self,
+        frame_time: Duration,
+        input: MovementInput,
+        mut is_solid: impl FnMut(na::Vector3<i32>) -> bool,
+    ) -> f32 {
+        self.accumulator += frame_time;
+        let mut steps = 0;
+        while self.accumulator >= PHYSICS_TIMESTEP {
+            if steps == MAX_STEPS_PER_FRAME {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+            self.step(input, &mut is_solid);
+            self.accumulator -= PHYSICS_TIMESTEP;
+            steps += 1;
+        }
+        self.accumulator.as_secs_f32() / PHYSICS_TIMESTEP.as_secs_f32()
+    }
+}
+
+/// Whether the player collides with the block at a world position. Blocks
+/// in chunks that aren't loaded yet are solid, so the player can't fall
+/// into them, and so is everything below the world.
+pub fn blocks_player(world_position: na::Vector3<i32>) -> bool {
+    if world_position.y >= Y_SIZE as i32 {
+        return false;
+    }
+    loaded_block(world_position).is_none_or(|block| {
+        BLOCK_REGISTRY
+            .get(block)
+            .is_ok_and(|definition| definition.solid)
+    })
+}
+
+impl FrameDependancy for Player {
+    fn frame_update(
+        &mut self,
+        engine_details: RefMut<gamezap::EngineDetails>,
+        renderer: &gamezap::renderer::Renderer,
+        engine_systems: std::cell::Ref<gamezap::EngineSystems>,
+    ) {
+        let mode_key = engine_details
+            .pressed_scancodes
+            .contains(&MOVEMENT_MODE_KEY);
+        if mode_key && !self.previous_mode_key {
+            self.set_mode(match self.mode {
+                MovementMode::Fly => MovementMode::Walk,
+                MovementMode::Walk => MovementMode::Fly,
+            });
+        }
+        self.previous_mode_key = mode_key;
+
+        let Some(camera_manager) = &renderer.module_manager.camera_manager else {
+            return;
+        };
+        let camera_manager = camera_manager.borrow();
+        let mut camera = camera_manager.camera.borrow_mut();
+
+        // The camera only turns with the mouse while walking, the keys move
+        // the player instead
+        let no_keys = HashSet::new();
+        let camera_keys = match self.mode {
+            MovementMode::Fly => &engine_details.pressed_scancodes,
+            MovementMode::Walk => &no_keys,
+        };
+        if let Some(mouse_state) = engine_details.mouse_state.0 {
+            camera.transform_camera(
+                camera_keys,
+                &mouse_state,
+                engine_systems
+                    .sdl_context
+                    .borrow()
+                    .mouse()
+                    .relative_mouse_mode(),
+                engine_details.last_frame_duration.as_seconds_f32(),
+            );
+        }
+
+        match self.mode {
+            MovementMode::Fly => {
+                self.position = camera.position - na::Vector3::new(0.0, EYE_HEIGHT, 0.0);
+                self.previous_position = self.position;
+            }
+            MovementMode::Walk => {
+                let input = MovementInput::from_keys(&engine_details.pressed_scancodes, camera.yaw);
+                let frame_time = Duration::from_secs_f32(
+                    engine_details.last_frame_duration.as_seconds_f32().max(0.0),
+                );
+                let alpha = self.update(frame_time, input, blocks_player);
+                camera.position = self.eye(alpha);
+            }
+        }
+        self.shared_aabb.set(self.aabb());
+    }
+}
@@ -0,0 +1,70 @@
+use nalgebra as na;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// World position of the block that was hit
+    pub block: na::Vector3<i32>,
+    /// Normal of the face the ray entered through, zero if the ray started
+    /// inside the block
+    pub normal: na::Vector3<i32>,
+    pub distance: f32,
+}
+
+/// Walks the block grid along a ray, visiting every block the ray passes
+/// through in order (Amanatides & Woo), and returns the first one `is_solid`
+/// accepts within `max_distance`.
+pub fn raycast(
+    origin: na::Vector3<f32>,
+    direction: na::Vector3<f32>,
+    max_distance: f32,
+    mut is_solid: impl FnMut(na::Vector3<i32>) -> bool,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize(f32::EPSILON)?;
+
+    let mut block = origin.map(|component| component.floor() as i32);
+    let step = direction.map(|component| {
+        if component > 0.0 {
+            1
+        } else if component < 0.0 {
+            -1
+        } else {
+            0
+        }
+    });
+    // Distance along the ray between two block boundaries on each axis
+    let distance_delta = direction.map(|component| (1.0 / component).abs());
+    // Distance along the ray to the next block boundary on each axis
+    let mut next_boundary = na::Vector3::from_fn(|axis, _| {
+        let component = direction[axis];
+        if component > 0.0 {
+            (block[axis] as f32 + 1.0 - origin[axis]) / component
+        } else if component < 0.0 {
+            (origin[axis] - block[axis] as f32) / -component
+        } else {
+            f32::INFINITY
+        }
+    });
+
+    let mut normal = na::Vector3::zeros();
+    let mut distance = 0.0;
+    loop {
+        if is_solid(block) {
+            return Some(RaycastHit {
+                block,
+                normal,
+                distance,
+            });
+        }
+
+        let axis = next_boundary.imin();
+        distance = next_boundary[axis];
+        if distance > max_distance {
+            return None;
+        }
+
+        block[axis] += step[axis];
+        next_boundary[axis] += distance_delta[axis];
+        normal = na::Vector3::zeros();
+        normal[axis] = -step[axis];
+    }
+}
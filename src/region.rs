@@ -0,0 +1,426 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use nalgebra as na;
+
+use crate::{block_storage::BlockArray, chunk::BLOCK_COUNT, tick::SavedTick};
+
+pub const WORLD_DIRECTORY: &str = "world";
+
+/// Width and depth of a region, in chunks
+pub const REGION_SIZE: i32 = 32;
+const CHUNKS_PER_REGION: usize = (REGION_SIZE * REGION_SIZE) as usize;
+
+const REGION_MAGIC: [u8; 4] = *b"MCRG";
+const REGION_VERSION: u32 = 2;
+/// Oldest version that can still be read. Chunks of version 1 have no
+/// scheduled ticks.
+const OLDEST_REGION_VERSION: u32 = 1;
+/// Block index and delay of a scheduled tick
+const SAVED_TICK_SIZE: usize = 2 + 4;
+/// Magic, version and one `(offset, length)` pair per chunk
+const HEADER_SIZE: usize = 8 + CHUNKS_PER_REGION * 8;
+
+/// Blocks of a chunk loaded from its region file, with the scheduled ticks
+/// it had when it was saved
+#[derive(Debug, Clone)]
+pub struct SavedChunk {
+    pub blocks: BlockArray,
+    pub ticks: Vec<SavedTick>,
+}
+
+/// Stores chunks on disk, grouped into region files of [REGION_SIZE] x
+/// [REGION_SIZE] chunks.
+///
+/// A region file starts with a header holding the magic bytes `MCRG`, the
+/// format version and, for every chunk in the region, the offset and length of
+/// its data. An offset of 0 means the chunk was never saved. Chunk data is
+/// zlib compressed: the block array with each block id stored as a little
+/// endian `u16`, followed by the number of scheduled ticks as a `u32` and the
+/// `u16` block index and `u32` delay of each tick.
+#[derive(Debug, Clone)]
+pub struct RegionStorage {
+    directory: PathBuf,
+}
+
+impl RegionStorage {
+    pub fn new(directory: impl AsRef<Path>) -> Self {
+        RegionStorage {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn region_position(chunk_position: na::Vector2<i32>) -> na::Vector2<i32> {
+        na::Vector2::new(
+            chunk_position.x.div_euclid(REGION_SIZE),
+            chunk_position.y.div_euclid(REGION_SIZE),
+        )
+    }
+
+    fn region_path(&self, region_position: na::Vector2<i32>) -> PathBuf {
+        self.directory.join(format!(
+            "r.{}.{}.region",
+            region_position.x, region_position.y
+        ))
+    }
+
+    /// Index of the chunk's entry in its region's header
+    fn chunk_slot(chunk_position: na::Vector2<i32>) -> usize {
+        (chunk_position.x.rem_euclid(REGION_SIZE) * REGION_SIZE
+            + chunk_position.y.rem_euclid(REGION_SIZE)) as usize
+    }
+
+    /// Reads the compressed data of every chunk in a region, `None` for chunks
+    /// that were never saved
+    fn read_region(&self, region_position: na::Vector2<i32>) -> io::Result<Vec<Option<Vec<u8>>>> {
+        let bytes = match fs::read(self.region_path(region_position)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(vec![None; CHUNKS_PER_REGION])
+            }
+            Err(err) => return Err(err),
+        };
+
+        read_header(&bytes)?
+            .into_iter()
+            .map(|entry| match entry {
+                Some((offset, length)) => bytes
+                    .get(offset..offset + length)
+                    .map(|data| Some(data.to_vec()))
+                    .ok_or_else(|| invalid_data("chunk data is out of bounds")),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    /// Reads the compressed data of a single chunk, `None` if it was never
+    /// saved. Only the header and the chunk's own data are read from the
+    /// region file.
+    fn read_chunk(&self, chunk_position: na::Vector2<i32>) -> io::Result<Option<Vec<u8>>> {
+        let path = self.region_path(Self::region_position(chunk_position));
+        let mut file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut header = vec![0; HEADER_SIZE];
+        read_exact_or(&mut file, &mut header, "not a region file")?;
+        let entries = read_header(&header)?;
+        let Some((offset, length)) = entries[Self::chunk_slot(chunk_position)] else {
+            return Ok(None);
+        };
+
+        let mut data = vec![0; length];
+        file.seek(SeekFrom::Start(offset as u64))?;
+        read_exact_or(&mut file, &mut data, "chunk data is out of bounds")?;
+        Ok(Some(data))
+    }
+
+    fn write_region(
+        &self,
+        region_position: na::Vector2<i32>,
+        chunks: &[Option<Vec<u8>>],
+    ) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(&REGION_MAGIC);
+        header.extend_from_slice(&REGION_VERSION.to_le_bytes());
+
+        let mut body = Vec::new();
+        for chunk in chunks {
+            match chunk {
+                Some(data) => {
+                    header.extend_from_slice(&((HEADER_SIZE + body.len()) as u32).to_le_bytes());
+                    header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                    body.extend_from_slice(data);
+                }
+                None => header.extend_from_slice(&[0; 8]),
+            }
+        }
+
+        fs::create_dir_all(&self.directory)?;
+        // Write the whole region next to the old one first so a crash can't
+        // leave a half written file behind
+        let path = self.region_path(region_position);
+        let temporary_path = path.with_extension("region.tmp");
+        let mut file = fs::File::create(&temporary_path)?;
+        file.write_all(&header)?;
+        file.write_all(&body)?;
+        file.sync_all()?;
+        fs::rename(temporary_path, path)
+    }
+
+    pub fn load_chunk(&self, chunk_position: na::Vector2<i32>) -> io::Result<Option<SavedChunk>> {
+        let Some(compressed) = self.read_chunk(chunk_position)? else {
+            return Ok(None);
+        };
+
+        let mut bytes = Vec::with_capacity(BLOCK_COUNT * 2);
+        ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut bytes)?;
+        if bytes.len() < BLOCK_COUNT * 2 {
+            return Err(invalid_data("chunk data has the wrong size"));
+        }
+        let (block_bytes, tick_bytes) = bytes.split_at(BLOCK_COUNT * 2);
+
+        let ids: Vec<u16> = block_bytes
+            .chunks_exact(2)
+            .map(|id| u16::from_le_bytes([id[0], id[1]]))
+            .collect();
+        Ok(Some(SavedChunk {
+            blocks: BlockArray::from_ids(&ids),
+            ticks: read_ticks(tick_bytes)?,
+        }))
+    }
+
+    /// Saves every chunk with a single read and write of each region file
+    /// they are in
+    pub fn save_chunks(&self, chunks: &[(na::Vector2<i32>, SavedChunk)]) -> io::Result<()> {
+        let mut regions: HashMap<na::Vector2<i32>, Vec<_>> = HashMap::new();
+        for (position, chunk) in chunks {
+            regions
+                .entry(Self::region_position(*position))
+                .or_default()
+                .push((*position, chunk));
+        }
+        regions
+            .into_iter()
+            .try_for_each(|(region_position, chunks)| self.save_region(region_position, &chunks))
+    }
+
+    /// Replaces chunks that are all in the region at `region_position`
+    fn save_region(
+        &self,
+        region_position: na::Vector2<i32>,
+        chunks: &[(na::Vector2<i32>, &SavedChunk)],
+    ) -> io::Result<()> {
+        let mut region = self.read_region(region_position)?;
+        for (chunk_position, chunk) in chunks {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            for block in chunk.blocks.iter() {
+                encoder.write_all(&block.to_le_bytes())?;
+            }
+            encoder.write_all(&(chunk.ticks.len() as u32).to_le_bytes())?;
+            for tick in &chunk.ticks {
+                encoder.write_all(&(tick.block_index as u16).to_le_bytes())?;
+                encoder.write_all(&(tick.delay.min(u32::MAX as u64) as u32).to_le_bytes())?;
+            }
+            region[Self::chunk_slot(*chunk_position)] = Some(encoder.finish()?);
+        }
+        self.write_region(region_position, &region)
+    }
+}
+
+/// Chunks queued in a [RegionWriter] that aren't written yet
+#[derive(Debug, Default)]
+struct PendingChunks {
+    /// Every chunk with the batch it was queued in
+    chunks: HashMap<na::Vector2<i32>, (u64, SavedChunk)>,
+    next_batch: u64,
+}
+
+enum WriterMessage {
+    /// Write the chunks still pending from a batch and the batches before it
+    Write(u64),
+    /// Write everything still pending, then answer with the positions of the
+    /// chunks that couldn't be written
+    Flush(Sender<Vec<na::Vector2<i32>>>),
+}
+
+/// Saves chunks into a [RegionStorage] on a background thread, so saving
+/// never waits on the disk.
+///
+/// Chunks queued together are written as one batch, reading and writing
+/// every region file they are in once. Queued chunks are kept until they are
+/// written, and [load_chunk](RegionWriter::load_chunk) returns them instead
+/// of what is on disk, so a chunk loaded again right after it was saved keeps
+/// its changes. Chunks that fail to be written are logged and stay queued,
+/// they are tried again with the next batch and on [flush](RegionWriter::flush).
+#[derive(Clone)]
+pub struct RegionWriter {
+    storage: RegionStorage,
+    pending: Arc<Mutex<PendingChunks>>,
+    sender: Sender<WriterMessage>,
+}
+
+impl RegionWriter {
+    pub fn new(storage: RegionStorage) -> Self {
+        let pending = Arc::new(Mutex::new(PendingChunks::default()));
+        let (sender, receiver) = mpsc::channel();
+        {
+            let storage = storage.clone();
+            let pending = pending.clone();
+            thread::Builder::new()
+                .name("region writer".to_string())
+                .spawn(move || write_batches(&storage, &pending, receiver))
+                .expect("Failed to start the region writer");
+        }
+        RegionWriter {
+            storage,
+            pending,
+            sender,
+        }
+    }
+
+    /// The chunk as it was last queued or saved, see
+    /// [RegionStorage::load_chunk]
+    pub fn load_chunk(&self, chunk_position: na::Vector2<i32>) -> io::Result<Option<SavedChunk>> {
+        let pending = self.pending.lock().unwrap();
+        if let Some((_, chunk)) = pending.chunks.get(&chunk_position) {
+            return Ok(Some(chunk.clone()));
+        }
+        drop(pending);
+        self.storage.load_chunk(chunk_position)
+    }
+
+    /// Queues writing `chunks` as one batch
+    pub fn save_chunks(&self, chunks: Vec<(na::Vector2<i32>, SavedChunk)>) {
+        if chunks.is_empty() {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        let batch = pending.next_batch;
+        pending.next_batch += 1;
+        for (position, chunk) in chunks {
+            pending.chunks.insert(position, (batch, chunk));
+        }
+        drop(pending);
+        // The thread only stops once every sender is dropped
+        let _ = self.sender.send(WriterMessage::Write(batch));
+    }
+
+    /// Waits until every chunk queued so far is written, trying the ones that
+    /// failed before once more. Returns the positions of the chunks that still
+    /// couldn't be written.
+    pub fn flush(&self) -> Vec<na::Vector2<i32>> {
+        let (sender, receiver) = mpsc::channel();
+        let _ = self.sender.send(WriterMessage::Flush(sender));
+        // Nothing else gets written once the writer thread is gone
+        receiver.recv().unwrap_or_else(|_| {
+            let pending = self.pending.lock().unwrap();
+            pending.chunks.keys().copied().collect()
+        })
+    }
+}
+
+fn write_batches(
+    storage: &RegionStorage,
+    pending: &Mutex<PendingChunks>,
+    receiver: Receiver<WriterMessage>,
+) {
+    for message in receiver {
+        match message {
+            WriterMessage::Write(batch) => {
+                write_pending(storage, pending, batch);
+            }
+            WriterMessage::Flush(sender) => {
+                let _ = sender.send(write_pending(storage, pending, u64::MAX));
+            }
+        }
+    }
+}
+
+/// Writes the pending chunks queued in `last_batch` or before, which
+/// includes the ones that failed to be written earlier. Returns the
+/// positions of the chunks that failed.
+fn write_pending(
+    storage: &RegionStorage,
+    pending: &Mutex<PendingChunks>,
+    last_batch: u64,
+) -> Vec<na::Vector2<i32>> {
+    // Chunks queued again since are left to their newer batch
+    let (batches, chunks): (Vec<_>, Vec<_>) = pending
+        .lock()
+        .unwrap()
+        .chunks
+        .iter()
+        .filter(|(_, (batch, _))| *batch <= last_batch)
+        .map(|(position, (batch, chunk))| ((*position, *batch), (*position, chunk.clone())))
+        .unzip();
+    if chunks.is_empty() {
+        return vec![];
+    }
+
+    if let Err(err) = storage.save_chunks(&chunks) {
+        let positions: Vec<_> = batches.into_iter().map(|(position, _)| position).collect();
+        log::error!("Failed to save chunks {positions:?}, trying again later: {err}");
+        return positions;
+    }
+    let mut pending = pending.lock().unwrap();
+    for (position, batch) in batches {
+        if pending.chunks.get(&position).map(|(queued, _)| *queued) == Some(batch) {
+            pending.chunks.remove(&position);
+        }
+    }
+    vec![]
+}
+
+/// Offset and length of every chunk's data in a region file from the start of
+/// its `bytes`, `None` for chunks that were never saved
+fn read_header(bytes: &[u8]) -> io::Result<Vec<Option<(usize, usize)>>> {
+    if bytes.len() < HEADER_SIZE || bytes[..4] != REGION_MAGIC {
+        return Err(invalid_data("not a region file"));
+    }
+    let version = read_u32(bytes, 4);
+    if !(OLDEST_REGION_VERSION..=REGION_VERSION).contains(&version) {
+        return Err(invalid_data(&format!(
+            "unsupported region version {version}"
+        )));
+    }
+
+    Ok((0..CHUNKS_PER_REGION)
+        .map(|slot| {
+            let offset = read_u32(bytes, 8 + slot * 8) as usize;
+            let length = read_u32(bytes, 12 + slot * 8) as usize;
+            (offset != 0).then_some((offset, length))
+        })
+        .collect())
+}
+
+/// Scheduled ticks stored after a chunk's blocks, none for chunks saved
+/// before ticks were
+fn read_ticks(bytes: &[u8]) -> io::Result<Vec<SavedTick>> {
+    if bytes.is_empty() {
+        return Ok(vec![]);
+    }
+    if bytes.len() < 4 {
+        return Err(invalid_data("chunk data has the wrong size"));
+    }
+    let count = read_u32(bytes, 0) as usize;
+    if bytes.len() != 4 + count * SAVED_TICK_SIZE {
+        return Err(invalid_data("chunk data has the wrong size"));
+    }
+    Ok(bytes[4..]
+        .chunks_exact(SAVED_TICK_SIZE)
+        .map(|tick| SavedTick {
+            block_index: u16::from_le_bytes([tick[0], tick[1]]) as usize,
+            delay: read_u32(tick, 2) as u64,
+        })
+        .collect())
+}
+
+/// Fills `buffer` from `file`, reporting a file that ends too early as
+/// invalid data
+fn read_exact_or(file: &mut fs::File, buffer: &mut [u8], message: &str) -> io::Result<()> {
+    file.read_exact(buffer).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => invalid_data(message),
+        _ => err,
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
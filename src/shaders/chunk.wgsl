@@ -0,0 +1,70 @@
+// Chunk meshes, see chunk_renderer.rs for the vertex layout. TILE_SIZE, the
+// edge of an atlas tile in texture coordinates, is prepended by chunk_shader.
+
+// Cutout texels less opaque than this are discarded
+const ALPHA_CUTOFF: f32 = 0.5;
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var atlas: texture_2d<f32>;
+@group(0) @binding(1)
+var atlas_sampler: sampler;
+
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    // Measured in tiles from the corner of the face, see Face in cube.rs
+    @location(1) tex_coords: vec2<f32>,
+    // Atlas coordinates of the corner of the face's tile
+    @location(2) tile: vec2<f32>,
+    // Corner of the chunk the mesh belongs to
+    @location(3) chunk_origin: vec3<f32>,
+    // Ambient occlusion and light of the vertex, from 0 for black to 1
+    @location(4) shade: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tile: vec2<f32>,
+    @location(2) shade: f32,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let position = vertex.position + vertex.chunk_origin;
+    out.clip_position = camera.view_projection * vec4<f32>(position, 1.0);
+    out.tex_coords = vertex.tex_coords;
+    out.tile = vertex.tile;
+    out.shade = vertex.shade;
+    return out;
+}
+
+fn sample_tile(in: VertexOutput) -> vec4<f32> {
+    // Faces larger than a block repeat their tile instead of stretching it
+    let tex_coords = in.tile + fract(in.tex_coords) * TILE_SIZE;
+    let color = textureSample(atlas, atlas_sampler, tex_coords);
+    return vec4<f32>(color.rgb * in.shade, color.a);
+}
+
+// Opaque and blended faces
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return sample_tile(in);
+}
+
+// Faces with see-through holes, like leaves
+@fragment
+fn fs_cutout(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_tile(in);
+    if color.a < ALPHA_CUTOFF {
+        discard;
+    }
+    return color;
+}
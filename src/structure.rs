@@ -0,0 +1,322 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use nalgebra as na;
+use serde::Deserialize;
+
+use crate::{
+    block_registry::{BlockRegistry, BLOCK_REGISTRY},
+    block_storage::BlockArray,
+    chunk::{Chunk, X_SIZE, Y_SIZE, Z_SIZE},
+};
+
+pub const STRUCTURE_DEFINITIONS_PATH: &str = "structures.ron";
+
+lazy_static! {
+    pub static ref STRUCTURE_REGISTRY: StructureRegistry =
+        StructureRegistry::load(STRUCTURE_DEFINITIONS_PATH, &BLOCK_REGISTRY)
+            .unwrap_or_else(|err| panic!("Failed to load {STRUCTURE_DEFINITIONS_PATH}: {err}"));
+}
+
+/// A box of blocks in a structure, with offsets from the structure's origin
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StructurePart {
+    pub from: (i32, i32, i32),
+    /// Opposite corner of the box from [from](StructurePart::from), included
+    pub to: (i32, i32, i32),
+    pub block: String,
+    /// Whether blocks that aren't air are left alone
+    #[serde(default)]
+    pub only_air: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StructureDefinition {
+    pub name: String,
+    /// Filled in order, so later parts overwrite earlier ones
+    pub parts: Vec<StructurePart>,
+}
+
+/// A single block of a structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StructureBlock {
+    pub offset: na::Vector3<i32>,
+    pub block: u16,
+    pub only_air: bool,
+}
+
+/// A structure with its parts flattened into blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Structure {
+    pub name: String,
+    pub blocks: Vec<StructureBlock>,
+}
+
+#[derive(Debug)]
+pub enum StructureError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    DuplicateName(String),
+    /// A structure uses a block the block registry doesn't have
+    UnknownBlock(String, String),
+    /// A structure reaches further than one chunk away from its origin
+    TooLarge(String),
+    UnknownName(String),
+}
+
+impl Display for StructureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StructureError::Io(err) => write!(f, "could not read structure definitions: {err}"),
+            StructureError::Parse(err) => write!(f, "invalid structure definitions: {err}"),
+            StructureError::DuplicateName(name) => {
+                write!(f, "structure \"{name}\" is defined twice")
+            }
+            StructureError::UnknownBlock(name, block) => {
+                write!(f, "structure \"{name}\" uses unknown block \"{block}\"")
+            }
+            StructureError::TooLarge(name) => write!(
+                f,
+                "structure \"{name}\" reaches more than {X_SIZE} blocks to the side"
+            ),
+            StructureError::UnknownName(name) => {
+                write!(f, "no structure is registered with name \"{name}\"")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StructureError {}
+
+/// Every structure template, by name
+#[derive(Debug)]
+pub struct StructureRegistry {
+    structures: HashMap<String, Structure>,
+}
+
+impl StructureRegistry {
+    /// Flattens the definitions into blocks, looking their block names up in
+    /// `blocks`
+    pub fn new(
+        definitions: Vec<StructureDefinition>,
+        blocks: &BlockRegistry,
+    ) -> Result<Self, StructureError> {
+        let mut structures = HashMap::with_capacity(definitions.len());
+        for definition in definitions {
+            let mut structure = Structure {
+                name: definition.name.clone(),
+                blocks: vec![],
+            };
+            for part in definition.parts {
+                let block = blocks.id_of(&part.block).map_err(|_| {
+                    StructureError::UnknownBlock(definition.name.clone(), part.block.clone())
+                })?;
+                let from = na::Vector3::new(part.from.0, part.from.1, part.from.2);
+                let to = na::Vector3::new(part.to.0, part.to.1, part.to.2);
+                let (min, max) = (from.inf(&to), from.sup(&to));
+                if min.x < -(X_SIZE as i32)
+                    || max.x > X_SIZE as i32
+                    || min.z < -(Z_SIZE as i32)
+                    || max.z > Z_SIZE as i32
+                {
+                    return Err(StructureError::TooLarge(definition.name));
+                }
+
+                for x in min.x..=max.x {
+                    for y in min.y..=max.y {
+                        for z in min.z..=max.z {
+                            structure.blocks.push(StructureBlock {
+                                offset: na::Vector3::new(x, y, z),
+                                block,
+                                only_air: part.only_air,
+                            });
+                        }
+                    }
+                }
+            }
+
+            if structures
+                .insert(definition.name.clone(), structure)
+                .is_some()
+            {
+                return Err(StructureError::DuplicateName(definition.name));
+            }
+        }
+
+        Ok(StructureRegistry { structures })
+    }
+
+    pub fn parse(source: &str, blocks: &BlockRegistry) -> Result<Self, StructureError> {
+        let definitions: Vec<StructureDefinition> =
+            ron::from_str(source).map_err(StructureError::Parse)?;
+        Self::new(definitions, blocks)
+    }
+
+    pub fn load(path: impl AsRef<Path>, blocks: &BlockRegistry) -> Result<Self, StructureError> {
+        let source = std::fs::read_to_string(path).map_err(StructureError::Io)?;
+        Self::parse(&source, blocks)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Structure, StructureError> {
+        self.structures
+            .get(name)
+            .ok_or_else(|| StructureError::UnknownName(name.to_string()))
+    }
+}
+
+/// A block of a structure waiting to be placed in a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuedBlock {
+    /// Index into the chunk's [BlockArray]
+    pub index: usize,
+    pub block: u16,
+    pub only_air: bool,
+}
+
+impl QueuedBlock {
+    /// Places the block in the chunk
+    pub fn apply(&self, blocks: &mut BlockArray, air: u16) {
+        if !self.only_air || blocks[self.index] == air {
+            blocks.set(self.index, self.block);
+        }
+    }
+}
+
+/// Splits the blocks of a structure starting at `origin` into the chunks they
+/// land in. Blocks above or below the world are dropped.
+pub fn place_structure(
+    structure: &Structure,
+    origin: na::Vector3<i32>,
+) -> impl Iterator<Item = (na::Vector2<i32>, QueuedBlock)> + '_ {
+    structure.blocks.iter().filter_map(move |block| {
+        let position = origin + block.offset;
+        if position.y < 0 || position.y >= Y_SIZE as i32 {
+            return None;
+        }
+        let chunk_position = na::Vector2::new(
+            position.x.div_euclid(X_SIZE as i32),
+            position.z.div_euclid(Z_SIZE as i32),
+        );
+        let index = Chunk::block_index(
+            position.x.rem_euclid(X_SIZE as i32) as usize,
+            position.y as usize,
+            position.z.rem_euclid(Z_SIZE as i32) as usize,
+        );
+        Some((
+            chunk_position,
+            QueuedBlock {
+                index,
+                block: block.block,
+                only_air: block.only_air,
+            },
+        ))
+    })
+}
+
+/// Queued blocks of one chunk, by the position of the chunk whose structures
+/// they belong to
+type QueuedBySource = BTreeMap<(i32, i32), Vec<QueuedBlock>>;
+
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Chunks whose structures were already split into
+    /// [pending](QueueState::pending)
+    planned: HashSet<na::Vector2<i32>>,
+    /// Blocks waiting to be placed in each chunk
+    pending: HashMap<na::Vector2<i32>, QueuedBySource>,
+}
+
+/// Holds the blocks of structures that reach into chunks which weren't
+/// generated yet.
+///
+/// Structures only start in a chunk's own columns and reach at most one
+/// chunk away, so before a chunk is generated the structures of the chunks
+/// around it are planned and their blocks are queued by the chunk they land
+/// in. Planning only depends on the world position, and queued blocks are
+/// placed ordered by the chunk their structure starts in, so chunks look the
+/// same no matter which of them is generated first.
+#[derive(Debug, Default)]
+pub struct StructureQueue {
+    state: Mutex<QueueState>,
+}
+
+impl StructureQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every structure block that lands in the chunk at `position`. `plan`
+    /// returns the blocks of the structures starting in a chunk, along with
+    /// the chunks they land in, and is called for every chunk around
+    /// `position` that wasn't planned yet.
+    pub fn blocks_for(
+        &self,
+        position: na::Vector2<i32>,
+        mut plan: impl FnMut(na::Vector2<i32>) -> Vec<(na::Vector2<i32>, QueuedBlock)>,
+    ) -> Vec<QueuedBlock> {
+        let mut state = self.state.lock().unwrap();
+        for offset_x in -1..=1 {
+            for offset_z in -1..=1 {
+                let source = position + na::Vector2::new(offset_x, offset_z);
+                if !state.planned.insert(source) {
+                    continue;
+                }
+
+                let mut by_target: HashMap<na::Vector2<i32>, Vec<QueuedBlock>> = HashMap::new();
+                for (target, block) in plan(source) {
+                    by_target.entry(target).or_default().push(block);
+                }
+                // Replaces what was queued if the source was planned before
+                // and forgotten since
+                for (target, blocks) in by_target {
+                    state
+                        .pending
+                        .entry(target)
+                        .or_default()
+                        .insert((source.x, source.y), blocks);
+                }
+            }
+        }
+
+        state
+            .pending
+            .get(&position)
+            .map(|sources| sources.values().flatten().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drops the queued blocks of chunks further than `distance` chunks from
+    /// `center` along either axis. They are planned again if one of them is
+    /// generated later.
+    pub fn forget_outside(&self, center: na::Vector2<i32>, distance: i32) {
+        let mut state = self.state.lock().unwrap();
+        let outside = |position: &na::Vector2<i32>| {
+            let offset = position - center;
+            offset.x.abs() > distance || offset.y.abs() > distance
+        };
+
+        let forgotten: Vec<na::Vector2<i32>> = state
+            .pending
+            .keys()
+            .filter(|p| outside(p))
+            .copied()
+            .collect();
+        for position in forgotten {
+            state.pending.remove(&position);
+            // The chunks around it have to queue their blocks for it again
+            for offset_x in -1..=1 {
+                for offset_z in -1..=1 {
+                    state
+                        .planned
+                        .remove(&(position + na::Vector2::new(offset_x, offset_z)));
+                }
+            }
+        }
+        state.planned.retain(|position| !outside(position));
+    }
+}
@@ -0,0 +1,305 @@
+use lazy_static::lazy_static;
+use nalgebra as na;
+
+use crate::{
+    biome::{Biome, BIOMES},
+    block_registry::BLOCK_REGISTRY,
+    block_storage::BlockArray,
+    caves::CaveCarver,
+    chunk::{Chunk, X_SIZE, Y_SIZE, Z_SIZE},
+    noise::{hash_2d, Fbm},
+    ores::{OreVeins, ORES},
+    structure::{place_structure, QueuedBlock, Structure, StructureQueue, STRUCTURE_REGISTRY},
+};
+
+pub const WORLD_SEED: u64 = 0x006D_7963_7261_6674;
+
+/// Water fills every column up to and including this height
+pub const SEA_LEVEL: usize = 64;
+/// Number of filler blocks between the surface and the stone
+pub const FILLER_DEPTH: usize = 4;
+/// Biomes further away from a column than this don't change its height
+pub const BLEND_RADIUS: i32 = 16;
+/// Distance between the climate samples that heights are blended from
+const BLEND_SPACING: i32 = 4;
+
+lazy_static! {
+    /// Colors the terrain by biome when the game is started with
+    /// `--biome-colors`
+    pub static ref TERRAIN_GENERATOR: TerrainGenerator = TerrainGenerator {
+        biome_colors: std::env::args().any(|arg| arg == "--biome-colors"),
+        ..TerrainGenerator::new(WORLD_SEED)
+    };
+}
+
+/// Ids of the blocks named by a [BiomeDefinition](crate::biome::BiomeDefinition)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BiomeBlocks {
+    pub surface: u16,
+    pub shore: u16,
+    pub filler: u16,
+    pub debug_block: u16,
+}
+
+/// Shape of the terrain in a column, blended from the biomes around it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct HeightProfile {
+    base_height: f64,
+    hill_height: f64,
+    roughness: f64,
+}
+
+/// Generates the blocks of a chunk from layered heightmap noise shaped by
+/// biomes.
+///
+/// Every column gets a stone base, [FILLER_DEPTH] blocks of its biome's
+/// filler and a single surface block on top, with water above it up to
+/// [SEA_LEVEL]. The height of a column blends the height profiles of every
+/// biome within [BLEND_RADIUS], so biome borders don't turn into cliffs.
+/// Caves are carved out of the columns afterwards, see [CaveCarver], ore
+/// veins replace some of the stone, see [OreVeins], and structures are placed
+/// on top, see [StructureQueue]. The output only depends on the seed and the
+/// chunk's world position.
+#[derive(Debug)]
+pub struct TerrainGenerator {
+    seed: u64,
+    /// Broad hills and valleys
+    continent: Fbm,
+    /// Small bumps on top of the continent noise
+    detail: Fbm,
+    temperature: Fbm,
+    humidity: Fbm,
+    pub caves: CaveCarver,
+    pub ores: OreVeins,
+    pub structures: StructureQueue,
+    /// Whether every biome's surface is replaced with its
+    /// [debug_block](crate::biome::BiomeDefinition::debug_block)
+    pub biome_colors: bool,
+    pub air: u16,
+    pub stone: u16,
+    pub water: u16,
+    /// Indexed by [Biome] in [BIOMES] order
+    biome_blocks: [BiomeBlocks; BIOMES.len()],
+    /// Structures each biome grows with their chances, in [BIOMES] order
+    biome_structures: [Vec<(&'static Structure, f64)>; BIOMES.len()],
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: u64) -> Self {
+        TerrainGenerator {
+            seed,
+            continent: Fbm::new(seed, 4, 1.0 / 128.0),
+            detail: Fbm::new(seed.wrapping_add(1), 3, 1.0 / 24.0),
+            temperature: Fbm::new(seed.wrapping_add(2), 3, 1.0 / 512.0),
+            humidity: Fbm::new(seed.wrapping_add(3), 3, 1.0 / 512.0),
+            caves: CaveCarver::new(seed),
+            ores: OreVeins::new(seed.wrapping_add(20), ORES.clone()),
+            structures: StructureQueue::new(),
+            biome_colors: false,
+            air: block_id("air"),
+            stone: block_id("stone"),
+            water: block_id("water"),
+            biome_blocks: BIOMES.map(|biome| {
+                let definition = biome.definition();
+                BiomeBlocks {
+                    surface: block_id(definition.surface),
+                    shore: block_id(definition.shore),
+                    filler: block_id(definition.filler),
+                    debug_block: block_id(definition.debug_block),
+                }
+            }),
+            biome_structures: BIOMES.map(|biome| {
+                biome
+                    .definition()
+                    .structures
+                    .iter()
+                    .map(|&(name, chance)| (structure(name), chance))
+                    .collect()
+            }),
+        }
+    }
+
+    pub fn biome_blocks(&self, biome: Biome) -> BiomeBlocks {
+        self.biome_blocks[biome as usize]
+    }
+
+    /// Biome of the column at the given world coordinates
+    pub fn biome_at(&self, world_x: i32, world_z: i32) -> Biome {
+        let (x, z) = (world_x as f64, world_z as f64);
+        Biome::from_climate(self.temperature.get_2d(x, z), self.humidity.get_2d(x, z))
+    }
+
+    /// Height profile of a column, averaged over the biomes of the climate
+    /// samples within [BLEND_RADIUS]. Closer samples weigh more and samples at
+    /// the edge of the radius weigh nothing, so the profile changes smoothly
+    /// from one column to the next. Samples sit on a grid every
+    /// [BLEND_SPACING] blocks, `biome_at` gives their biomes.
+    fn blended_profile(
+        world_x: i32,
+        world_z: i32,
+        mut biome_at: impl FnMut(i32, i32) -> Biome,
+    ) -> HeightProfile {
+        let mut profile = HeightProfile::default();
+        let mut total_weight = 0.0;
+        for sample_x in
+            (first_blend_sample(world_x)..=world_x + BLEND_RADIUS).step_by(BLEND_SPACING as usize)
+        {
+            for sample_z in (first_blend_sample(world_z)..=world_z + BLEND_RADIUS)
+                .step_by(BLEND_SPACING as usize)
+            {
+                let distance_squared = ((sample_x - world_x).pow(2) + (sample_z - world_z).pow(2))
+                    as f64
+                    / BLEND_RADIUS.pow(2) as f64;
+                if distance_squared >= 1.0 {
+                    continue;
+                }
+                let weight = (1.0 - distance_squared).powi(2);
+                let definition = biome_at(sample_x, sample_z).definition();
+                profile.base_height += definition.base_height * weight;
+                profile.hill_height += definition.hill_height * weight;
+                profile.roughness += definition.roughness * weight;
+                total_weight += weight;
+            }
+        }
+
+        HeightProfile {
+            base_height: profile.base_height / total_weight,
+            hill_height: profile.hill_height / total_weight,
+            roughness: profile.roughness / total_weight,
+        }
+    }
+
+    fn profile_height(&self, profile: HeightProfile, world_x: i32, world_z: i32) -> usize {
+        let (x, z) = (world_x as f64, world_z as f64);
+        let height = profile.base_height
+            + self.continent.get_2d(x, z) * profile.hill_height
+            + self.detail.get_2d(x, z) * profile.roughness;
+        (height.round() as usize).clamp(FILLER_DEPTH + 1, Y_SIZE - 2)
+    }
+
+    /// Height of the surface block in the column at the given world coordinates
+    pub fn surface_height(&self, world_x: i32, world_z: i32) -> usize {
+        let profile = Self::blended_profile(world_x, world_z, |x, z| self.biome_at(x, z));
+        self.profile_height(profile, world_x, world_z)
+    }
+
+    pub fn generate(&self, position: na::Vector2<i32>) -> BlockArray {
+        let mut blocks = BlockArray::filled(self.air);
+        let origin = (position.x * X_SIZE as i32, position.y * Z_SIZE as i32);
+
+        // Biomes of every climate sample the chunk's columns are blended from
+        let start = (first_blend_sample(origin.0), first_blend_sample(origin.1));
+        let sample_count = |start: i32, origin: i32, size: usize| {
+            ((origin + size as i32 - 1 + BLEND_RADIUS - start) / BLEND_SPACING + 1) as usize
+        };
+        let counts = (
+            sample_count(start.0, origin.0, X_SIZE),
+            sample_count(start.1, origin.1, Z_SIZE),
+        );
+        let samples: Vec<Biome> = (0..counts.0 as i32)
+            .flat_map(|x| (0..counts.1 as i32).map(move |z| (x, z)))
+            .map(|(x, z)| self.biome_at(start.0 + x * BLEND_SPACING, start.1 + z * BLEND_SPACING))
+            .collect();
+        let sample_at = |x: i32, z: i32| {
+            samples[((x - start.0) / BLEND_SPACING) as usize * counts.1
+                + ((z - start.1) / BLEND_SPACING) as usize]
+        };
+
+        // Indexed by `x * Z_SIZE + z`
+        let mut heights = Vec::with_capacity(X_SIZE * Z_SIZE);
+        for x in 0..X_SIZE {
+            for z in 0..Z_SIZE {
+                let world_x = origin.0 + x as i32;
+                let world_z = origin.1 + z as i32;
+                let profile = Self::blended_profile(world_x, world_z, sample_at);
+                let height = self.profile_height(profile, world_x, world_z);
+                let biome_blocks = self.biome_blocks(self.biome_at(world_x, world_z));
+
+                let surface = if self.biome_colors {
+                    biome_blocks.debug_block
+                } else if height <= SEA_LEVEL {
+                    biome_blocks.shore
+                } else {
+                    biome_blocks.surface
+                };
+                for y in 0..=height.max(SEA_LEVEL) {
+                    let block = if y > height {
+                        self.water
+                    } else if y == height {
+                        surface
+                    } else if y + FILLER_DEPTH >= height {
+                        biome_blocks.filler
+                    } else {
+                        self.stone
+                    };
+                    blocks.set(Chunk::block_index(x, y, z), block);
+                }
+                heights.push(height);
+            }
+        }
+
+        self.caves.carve(&mut blocks, position, &heights, self.air);
+        self.ores.place(&mut blocks, position, self.stone);
+
+        for queued in self
+            .structures
+            .blocks_for(position, |source| self.plan_structures(source))
+        {
+            queued.apply(&mut blocks, self.air);
+        }
+
+        blocks
+    }
+
+    /// Blocks of every structure starting in the chunk at `position`, along
+    /// with the chunks they land in. Each column above the water whose
+    /// surface wasn't carved away rolls for one of its biome's structures.
+    fn plan_structures(&self, position: na::Vector2<i32>) -> Vec<(na::Vector2<i32>, QueuedBlock)> {
+        let mut blocks = vec![];
+        for x in 0..X_SIZE {
+            for z in 0..Z_SIZE {
+                let world_x = position.x * X_SIZE as i32 + x as i32;
+                let world_z = position.y * Z_SIZE as i32 + z as i32;
+                let random = hash_2d(self.seed, world_x, world_z);
+                // The top 53 bits as a fraction in [0, 1)
+                let mut roll = (random >> 11) as f64 / (1_u64 << 53) as f64;
+                let biome = self.biome_at(world_x, world_z);
+                let Some(structure) = self.biome_structures[biome as usize].iter().find_map(
+                    |&(structure, chance)| {
+                        roll -= chance;
+                        (roll < 0.0).then_some(structure)
+                    },
+                ) else {
+                    continue;
+                };
+
+                let height = self.surface_height(world_x, world_z);
+                if height <= SEA_LEVEL
+                    || self.caves.carves([world_x, height as i32, world_z], height)
+                {
+                    continue;
+                }
+                let origin = na::Vector3::new(world_x, height as i32 + 1, world_z);
+                blocks.extend(place_structure(structure, origin));
+            }
+        }
+        blocks
+    }
+}
+
+/// Lowest climate sample within [BLEND_RADIUS] of a position along one axis
+fn first_blend_sample(position: i32) -> i32 {
+    (position - BLEND_RADIUS).div_euclid(BLEND_SPACING) * BLEND_SPACING
+}
+
+fn block_id(name: &str) -> u16 {
+    BLOCK_REGISTRY
+        .id_of(name)
+        .unwrap_or_else(|err| panic!("Terrain generation needs a \"{name}\" block: {err}"))
+}
+
+fn structure(name: &str) -> &'static Structure {
+    STRUCTURE_REGISTRY
+        .get(name)
+        .unwrap_or_else(|err| panic!("A biome grows an unknown structure: {err}"))
+}
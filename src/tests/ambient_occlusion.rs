@@ -0,0 +1,195 @@
+use crate::{
+    ambient_occlusion::{face_occlusion, shade_face, FaceOcclusion},
+    block_storage::BlockArray,
+    chunk::{Chunk, ChunkMesh, ChunkNeighborhood, MeshingMode, UnloadedNeighbors, X_SIZE, Z_SIZE},
+    cube::Face,
+    tests::common::{filled, id, isolated},
+};
+
+const TOP: u8 = 0b0010_0000;
+
+fn place_stone(blocks: &mut BlockArray, x: usize, y: usize, z: usize) {
+    blocks.set(Chunk::block_index(x, y, z), id("stone"));
+}
+
+/// Occlusion of the top face of the block at `(x, 1, z)`. Corners are
+/// indexed by `x + 2 * z` for the top face.
+fn top_occlusion(neighborhood: &ChunkNeighborhood, x: i32, z: i32) -> FaceOcclusion {
+    face_occlusion(neighborhood, [x, 1, z], TOP).unwrap()
+}
+
+#[test]
+fn test_open_faces_are_unoccluded() {
+    let mut blocks = filled("air");
+    place_stone(&mut blocks, 8, 1, 8);
+    assert_eq!(
+        top_occlusion(&isolated(&blocks, UnloadedNeighbors::Show), 8, 8),
+        [3; 4]
+    );
+
+    for meshing_mode in [MeshingMode::Naive, MeshingMode::Greedy] {
+        let faces = Chunk::build_faces(isolated(&blocks, UnloadedNeighbors::Show), meshing_mode)
+            .unwrap()
+            .opaque;
+        assert!(faces
+            .iter()
+            .flatten()
+            .all(|vertex| vertex.bitangent[0] == 1.0));
+    }
+}
+
+#[test]
+fn test_corner_occlusion_levels() {
+    let mut blocks = filled("air");
+    place_stone(&mut blocks, 8, 1, 8);
+
+    // A wall along +x darkens both +x corners
+    place_stone(&mut blocks, 9, 2, 8);
+    assert_eq!(
+        top_occlusion(&isolated(&blocks, UnloadedNeighbors::Show), 8, 8),
+        [3, 2, 3, 2]
+    );
+
+    // A second wall along +z fully occludes the corner between them
+    place_stone(&mut blocks, 8, 2, 9);
+    assert_eq!(
+        top_occlusion(&isolated(&blocks, UnloadedNeighbors::Show), 8, 8),
+        [3, 2, 2, 0]
+    );
+
+    // A block touching only the corner
+    let mut blocks = filled("air");
+    place_stone(&mut blocks, 8, 1, 8);
+    place_stone(&mut blocks, 7, 2, 7);
+    assert_eq!(
+        top_occlusion(&isolated(&blocks, UnloadedNeighbors::Show), 8, 8),
+        [2, 3, 3, 3]
+    );
+
+    // Two sides and the corner
+    place_stone(&mut blocks, 7, 2, 8);
+    assert_eq!(
+        top_occlusion(&isolated(&blocks, UnloadedNeighbors::Show), 8, 8),
+        [1, 3, 2, 3]
+    );
+}
+
+#[test]
+fn test_occlusion_across_chunk_borders() {
+    let blocks = filled("air");
+    let mut positive_x = filled("air");
+    place_stone(&mut positive_x, 0, 2, 8);
+    let mut positive_xz = filled("air");
+    place_stone(&mut positive_xz, 0, 2, 0);
+
+    let mut neighborhood = isolated(&blocks, UnloadedNeighbors::Show);
+    neighborhood.positive_x = Some(&positive_x);
+    neighborhood.corners[3] = Some(&positive_xz);
+
+    assert_eq!(
+        top_occlusion(&neighborhood, X_SIZE as i32 - 1, 8),
+        [3, 2, 3, 2]
+    );
+    assert_eq!(
+        top_occlusion(&neighborhood, X_SIZE as i32 - 1, Z_SIZE as i32 - 1),
+        [3, 3, 3, 2]
+    );
+
+    // Unloaded chunks don't occlude
+    assert_eq!(
+        top_occlusion(
+            &isolated(&blocks, UnloadedNeighbors::Show),
+            X_SIZE as i32 - 1,
+            8
+        ),
+        [3; 4]
+    );
+}
+
+/// The two vertices both triangles of the face share
+fn shared_diagonal(indices: &[u32]) -> Vec<u32> {
+    let mut shared = indices[..3]
+        .iter()
+        .filter(|index| indices[3..].contains(index))
+        .copied()
+        .collect::<Vec<_>>();
+    shared.sort();
+    shared
+}
+
+#[test]
+fn test_quad_diagonal_follows_occlusion() {
+    let mut blocks = filled("air");
+    place_stone(&mut blocks, 8, 1, 8);
+    let face: Face = Chunk::build_faces(
+        isolated(&blocks, UnloadedNeighbors::Show),
+        MeshingMode::Naive,
+    )
+    .unwrap()
+    .opaque
+    .into_iter()
+    .find(|face| face[0].normal == [0.0, 1.0, 0.0])
+    .unwrap();
+
+    for dark_corner in 0..4 {
+        let mut occlusion = [3; 4];
+        occlusion[dark_corner] = 0;
+        let mut shaded = face;
+        shade_face(&mut shaded, TOP, [8.0, 1.0, 8.0], occlusion);
+
+        let darkest = (0..4)
+            .min_by(|a, b| {
+                shaded[*a as usize].bitangent[0].total_cmp(&shaded[*b as usize].bitangent[0])
+            })
+            .unwrap();
+        let mesh = ChunkMesh::from_faces(&[shaded]);
+        assert!(
+            shared_diagonal(&mesh.indices).contains(&darkest),
+            "corner {dark_corner}"
+        );
+    }
+}
+
+#[test]
+fn test_greedy_meshing_keeps_occlusion() {
+    let mut blocks = filled("air");
+    for x in 0..X_SIZE {
+        for z in 0..Z_SIZE {
+            place_stone(&mut blocks, x, 1, z);
+        }
+    }
+    place_stone(&mut blocks, 8, 2, 8);
+
+    let naive = Chunk::build_faces(
+        isolated(&blocks, UnloadedNeighbors::Show),
+        MeshingMode::Naive,
+    )
+    .unwrap()
+    .opaque;
+    let greedy = Chunk::build_faces(
+        isolated(&blocks, UnloadedNeighbors::Show),
+        MeshingMode::Greedy,
+    )
+    .unwrap()
+    .opaque;
+    let is_floor = |face: &&Face| face[0].normal == [0.0, 1.0, 0.0] && face[0].position[1] == 2.0;
+    assert!(greedy.iter().filter(is_floor).count() > 1);
+
+    // Every vertex of a merged face matches the naive face at the same corner
+    let brightness_at = |faces: &[Face], position: [f32; 3]| {
+        faces
+            .iter()
+            .filter(is_floor)
+            .flatten()
+            .find(|vertex| vertex.position == position)
+            .map(|vertex| vertex.bitangent[0])
+    };
+    for vertex in greedy.iter().filter(is_floor).flatten() {
+        assert_eq!(
+            brightness_at(&naive, vertex.position),
+            Some(vertex.bitangent[0]),
+            "{:?}",
+            vertex.position
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+use nalgebra as na;
+
+use crate::{
+    biome::{Biome, BIOMES},
+    chunk::{Chunk, X_SIZE, Z_SIZE},
+    terrain::TerrainGenerator,
+    tests::common::id,
+};
+
+/// Chunk positions every few chunks across a large area
+fn sample_chunks() -> impl Iterator<Item = na::Vector2<i32>> {
+    (-200..200)
+        .step_by(8)
+        .flat_map(|x| (-200..200).step_by(8).map(move |z| na::Vector2::new(x, z)))
+}
+
+/// A chunk whose corners are all in `biome`
+fn chunk_in(generator: &TerrainGenerator, biome: Biome) -> na::Vector2<i32> {
+    sample_chunks()
+        .find(|position| {
+            [
+                (0, 0),
+                (X_SIZE - 1, 0),
+                (0, Z_SIZE - 1),
+                (X_SIZE - 1, Z_SIZE - 1),
+            ]
+            .iter()
+            .all(|&(x, z)| {
+                generator.biome_at(
+                    position.x * X_SIZE as i32 + x as i32,
+                    position.y * Z_SIZE as i32 + z as i32,
+                ) == biome
+            })
+        })
+        .unwrap_or_else(|| panic!("No chunk is all {biome:?}"))
+}
+
+#[test]
+fn test_every_biome_is_generated() {
+    let generator = TerrainGenerator::new(42);
+    let found: HashSet<Biome> = sample_chunks()
+        .map(|position| generator.biome_at(position.x * X_SIZE as i32, position.y * Z_SIZE as i32))
+        .collect();
+    for biome in BIOMES {
+        assert!(found.contains(&biome), "{biome:?} never shows up");
+    }
+}
+
+#[test]
+fn test_biome_borders_are_blended() {
+    let generator = TerrainGenerator::new(42);
+    let mut borders = 0;
+    for x in (-3000..3000).step_by(7) {
+        for z in (-3000..3000).step_by(211) {
+            let biome = generator.biome_at(x, z);
+            let next_biome = generator.biome_at(x + 1, z);
+            if biome == next_biome {
+                continue;
+            }
+
+            // Even between plains and mountains the height changes gradually
+            borders += 1;
+            let height = generator.surface_height(x, z) as i32;
+            let next_height = generator.surface_height(x + 1, z) as i32;
+            assert!(
+                (height - next_height).abs() <= 3,
+                "{biome:?} at ({x}, {z}) is {height} high, {next_biome:?} next to it {next_height}"
+            );
+        }
+    }
+    assert!(borders > 10, "only crossed {borders} biome borders");
+}
+
+#[test]
+fn test_biomes_decorate_their_surface() {
+    let generator = TerrainGenerator::new(42);
+    let count = |position, block| {
+        generator
+            .generate(position)
+            .iter()
+            .filter(|&placed| placed == block)
+            .count()
+    };
+
+    let forest = chunk_in(&generator, Biome::Forest);
+    assert!(count(forest, id("log")) > 0);
+    assert!(count(forest, id("leaves")) > 0);
+    assert_eq!(count(forest, id("cactus")), 0);
+
+    let desert = chunk_in(&generator, Biome::Desert);
+    assert_eq!(count(desert, id("log")), 0);
+    let desert_chunks = (0..16).map(|offset| desert + na::Vector2::new(offset % 4, offset / 4));
+    assert!(desert_chunks
+        .map(|position| count(position, id("cactus")))
+        .any(|cacti| cacti > 0));
+
+    let ocean = chunk_in(&generator, Biome::Ocean);
+    assert!(count(ocean, generator.water) > 0);
+}
+
+#[test]
+fn test_biome_colors_replace_the_surface() {
+    let mut generator = TerrainGenerator::new(42);
+    generator.biome_colors = true;
+
+    for biome in BIOMES {
+        let position = chunk_in(&generator, biome);
+        let blocks = generator.generate(position);
+        let debug_block = generator.biome_blocks(biome).debug_block;
+        let height =
+            generator.surface_height(position.x * X_SIZE as i32, position.y * Z_SIZE as i32);
+        assert_eq!(
+            blocks[Chunk::block_index(0, height, 0)],
+            debug_block,
+            "{biome:?}"
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use crate::block_registry::{
+    BlockRegistry, BlockRegistryError, RenderLayer, BLOCK_DEFINITIONS_PATH, BLOCK_REGISTRY,
+};
+
+#[test]
+fn test_block_registry_lookup() {
+    let registry = BlockRegistry::load(BLOCK_DEFINITIONS_PATH).unwrap();
+
+    let grass_id = registry.id_of("grass").unwrap();
+    let grass = registry.get(grass_id).unwrap();
+    assert_eq!(grass.name, "grass");
+    assert!(grass.solid);
+    assert!(!grass.transparent);
+    assert!(grass.textures.is_some());
+    assert_eq!(grass.render_layer, RenderLayer::Opaque);
+
+    let water = registry.get(registry.id_of("water").unwrap()).unwrap();
+    assert!(water.transparent);
+    assert_eq!(water.render_layer, RenderLayer::Transparent);
+
+    let air = registry.get(registry.id_of("air").unwrap()).unwrap();
+    assert!(air.transparent);
+    assert!(air.textures.is_none());
+
+    assert_eq!(registry.iter().count(), BLOCK_REGISTRY.iter().count());
+}
+
+#[test]
+fn test_block_registry_errors() {
+    assert!(matches!(
+        BLOCK_REGISTRY.get(u16::MAX),
+        Err(BlockRegistryError::UnknownId(u16::MAX))
+    ));
+    assert!(matches!(
+        BLOCK_REGISTRY.id_of("not a block"),
+        Err(BlockRegistryError::UnknownName(_))
+    ));
+
+    let duplicate_id = r#"[
+        (id: 0, name: "air", textures: None, solid: false, transparent: true),
+        (id: 0, name: "also air", textures: None, solid: false, transparent: true),
+    ]"#;
+    assert!(matches!(
+        BlockRegistry::parse(duplicate_id),
+        Err(BlockRegistryError::DuplicateId(0))
+    ));
+
+    let too_bright = r#"[
+        (id: 0, name: "sun", textures: None, solid: true, transparent: false, light_emission: 16),
+    ]"#;
+    assert!(matches!(
+        BlockRegistry::parse(too_bright),
+        Err(BlockRegistryError::LightEmissionTooHigh(_))
+    ));
+
+    let opaque_water = r#"[
+        (id: 0, name: "water", textures: None, solid: false, transparent: false, render_layer: Transparent),
+    ]"#;
+    assert!(matches!(
+        BlockRegistry::parse(opaque_water),
+        Err(BlockRegistryError::SeeThroughBlockNotTransparent(_))
+    ));
+
+    assert!(matches!(
+        BlockRegistry::parse("[(id: 0)]"),
+        Err(BlockRegistryError::Parse(_))
+    ));
+}
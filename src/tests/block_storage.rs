@@ -0,0 +1,163 @@
+use nalgebra as na;
+
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    block_storage::{
+        sections_around, BlockArray, PalettedSection, SectionFill, ALL_SECTIONS,
+        SECTION_BLOCK_COUNT, SECTION_COUNT, SECTION_HEIGHT,
+    },
+    chunk::{Chunk, BLOCK_COUNT, Y_SIZE},
+    terrain::TERRAIN_GENERATOR,
+};
+
+/// Deterministic stream of pseudo random numbers
+fn random_numbers(mut state: u64) -> impl Iterator<Item = u64> {
+    std::iter::repeat_with(move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        state >> 33
+    })
+}
+
+#[test]
+fn test_single_block_sections_store_no_indices() {
+    let blocks = BlockArray::filled(3);
+    assert_eq!(blocks.sections().len(), SECTION_COUNT);
+    for section in blocks.sections() {
+        assert_eq!(section.palette(), [3]);
+        assert_eq!(section.bits_per_block(), 0);
+    }
+    assert!(blocks.iter().all(|block| block == 3));
+}
+
+#[test]
+fn test_palette_grows_and_repacks() {
+    let mut section = PalettedSection::filled(0);
+    let mut expected = vec![0u16; SECTION_BLOCK_COUNT];
+
+    // Every new block type widens the indices only once the palette is full
+    let mut next = 5;
+    for (block, bits) in [(1, 1), (2, 2), (3, 2), (4, 3), (8, 4), (16, 5)] {
+        while section.palette().len() < block as usize {
+            let index = (next * 97) % SECTION_BLOCK_COUNT;
+            let filler = section.palette().len() as u16;
+            section.set(index, filler);
+            expected[index] = filler;
+            next += 1;
+        }
+        section.set(block as usize * 13, block);
+        expected[block as usize * 13] = block;
+        assert_eq!(section.bits_per_block(), bits, "palette of {}", block + 1);
+        assert!((0..SECTION_BLOCK_COUNT).all(|index| section.get(index) == expected[index]));
+    }
+}
+
+#[test]
+fn test_unused_palette_entries_are_dropped() {
+    let mut section = PalettedSection::filled(0);
+    section.set(10, 1);
+    section.set(11, 2);
+    assert_eq!(section.palette(), [0, 1, 2]);
+
+    // The entry of a block type that is gone is taken by the next new one
+    section.set(10, 0);
+    assert_eq!(section.blocks().collect::<Vec<_>>(), [0, 2]);
+    section.set(12, 3);
+    assert_eq!(section.palette(), [0, 3, 2]);
+    assert_eq!([10, 11, 12].map(|index| section.get(index)), [0, 2, 3]);
+
+    // Back to a single block type, the indices are dropped
+    section.set(11, 0);
+    section.set(12, 0);
+    assert_eq!(section.palette(), [0]);
+    assert_eq!(section.bits_per_block(), 0);
+
+    for index in 0..SECTION_BLOCK_COUNT {
+        section.set(index, 4);
+    }
+    assert_eq!(section.palette(), [4]);
+    assert_eq!(section.bits_per_block(), 0);
+}
+
+#[test]
+fn test_section_fill() {
+    let air = BLOCK_REGISTRY.id_of("air").unwrap();
+    let stone = BLOCK_REGISTRY.id_of("stone").unwrap();
+    let glass = BLOCK_REGISTRY.id_of("glass").unwrap();
+
+    let mut section = PalettedSection::filled(air);
+    assert_eq!(section.fill().unwrap(), SectionFill::Empty);
+    section.set(100, stone);
+    assert_eq!(section.fill().unwrap(), SectionFill::Mixed);
+
+    let mut section = PalettedSection::filled(stone);
+    section.set(100, BLOCK_REGISTRY.id_of("dirt").unwrap());
+    assert_eq!(section.fill().unwrap(), SectionFill::Full);
+    section.set(200, glass);
+    assert_eq!(section.fill().unwrap(), SectionFill::Mixed);
+    section.set(200, stone);
+    assert_eq!(section.fill().unwrap(), SectionFill::Full);
+}
+
+#[test]
+fn test_sections_around() {
+    assert_eq!(sections_around(0), 0b1);
+    assert_eq!(sections_around(20), 0b10);
+    assert_eq!(sections_around(SECTION_HEIGHT), 0b11);
+    assert_eq!(sections_around(2 * SECTION_HEIGHT - 1), 0b110);
+    assert_eq!(sections_around(Y_SIZE - 1), 1 << (SECTION_COUNT - 1));
+    assert_eq!(ALL_SECTIONS.count_ones() as usize, SECTION_COUNT);
+}
+
+#[test]
+fn test_random_edits_match_flat_array() {
+    let mut blocks = BlockArray::filled(0);
+    let mut expected = vec![0u16; BLOCK_COUNT];
+
+    let mut numbers = random_numbers(7);
+    for edit in 0..20_000 {
+        let index = numbers.next().unwrap() as usize % BLOCK_COUNT;
+        // Few block types at first so the palettes grow a step at a time
+        let block = (numbers.next().unwrap() % (2 + edit / 1000)) as u16;
+        blocks.set(index, block);
+        expected[index] = block;
+    }
+
+    assert!(blocks.iter().eq(expected.iter().copied()));
+    assert!((0..BLOCK_COUNT).all(|index| blocks[index] == expected[index]));
+    assert!(BlockArray::from_ids(&expected) == blocks);
+}
+
+#[test]
+fn test_equality_ignores_palette_order() {
+    let mut first = BlockArray::filled(0);
+    first.set(Chunk::block_index(1, 2, 3), 5);
+    first.set(Chunk::block_index(4, 2, 3), 6);
+
+    let mut second = BlockArray::filled(0);
+    second.set(Chunk::block_index(4, 2, 3), 6);
+    second.set(Chunk::block_index(1, 2, 3), 5);
+    assert_ne!(
+        first.sections()[0].palette(),
+        second.sections()[0].palette()
+    );
+    assert!(first == second);
+
+    second.set(Chunk::block_index(1, 2, 3), 0);
+    assert!(first != second);
+}
+
+#[test]
+fn test_generated_chunks_use_less_memory() {
+    let flat_size = std::mem::size_of::<[u16; BLOCK_COUNT]>();
+    for position in [na::Vector2::new(0, 0), na::Vector2::new(-7, 12)] {
+        let blocks = TERRAIN_GENERATOR.generate(position);
+        let memory_size = blocks.memory_size();
+        println!("chunk {position:?}: {memory_size} bytes, flat array {flat_size} bytes");
+        assert!(
+            memory_size * 4 < flat_size,
+            "chunk {position:?} uses {memory_size} bytes"
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::f32::consts::FRAC_PI_2;
+
+use nalgebra as na;
+
+use crate::camera::{look_direction, Projection};
+
+/// Normalized device coordinates of `point` and its clip space `w`
+fn to_device(
+    view_projection: &na::Matrix4<f32>,
+    point: na::Vector3<f32>,
+) -> (na::Vector3<f32>, f32) {
+    let clip = view_projection * point.push(1.0);
+    (clip.xyz() / clip.w, clip.w)
+}
+
+#[test]
+fn test_view_projection_centers_the_look_direction() {
+    let eye = na::Vector3::new(5.0, 70.0, -3.0);
+    let projection = Projection::default();
+    for (yaw, pitch) in [
+        (0.0, 0.0),
+        (FRAC_PI_2, 0.3),
+        (2.0, -1.2),
+        (0.0, FRAC_PI_2 - 0.01),
+    ] {
+        let view_projection = projection.view_projection(eye, yaw, pitch);
+        let (center, w) = to_device(&view_projection, eye + look_direction(yaw, pitch) * 10.0);
+        assert!(center.x.abs() < 1e-4 && center.y.abs() < 1e-4, "{center:?}");
+        assert!((0.0..1.0).contains(&center.z));
+        assert!(w > 0.0);
+
+        // Behind the camera is never on screen
+        let (_, w) = to_device(&view_projection, eye - look_direction(yaw, pitch) * 10.0);
+        assert!(w < 0.0);
+    }
+}
+
+#[test]
+fn test_view_projection_keeps_up_on_top() {
+    let eye = na::Vector3::new(0.0, 70.0, 0.0);
+    let projection = Projection::default();
+    let view_projection = projection.view_projection(eye, 0.0, 0.0);
+    let forward = look_direction(0.0, 0.0);
+    let (above, _) = to_device(&view_projection, eye + forward * 10.0 + na::Vector3::y());
+    let (below, _) = to_device(&view_projection, eye + forward * 10.0 - na::Vector3::y());
+    assert!(above.y > 0.0 && below.y < 0.0);
+}
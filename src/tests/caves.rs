@@ -0,0 +1,119 @@
+use nalgebra as na;
+
+use crate::{
+    caves::{CaveCarver, MIN_CAVE_HEIGHT},
+    chunk::{Chunk, X_SIZE, Z_SIZE},
+    terrain::{TerrainGenerator, FILLER_DEPTH, SEA_LEVEL},
+};
+
+#[test]
+fn test_caves_are_deterministic() {
+    let positions = (-40..40)
+        .step_by(3)
+        .flat_map(|x| (8..120).step_by(5).map(move |y| [x * 7, y, 1000 - x * 13]));
+    let carver = CaveCarver::new(42);
+    let carved: Vec<bool> = positions
+        .clone()
+        .map(|position| carver.carves(position, 130))
+        .collect();
+    assert!(carved.contains(&true) && carved.contains(&false));
+
+    let same_seed: Vec<bool> = positions
+        .clone()
+        .map(|position| CaveCarver::new(42).carves(position, 130))
+        .collect();
+    assert_eq!(carved, same_seed);
+    let other_seed: Vec<bool> = positions
+        .map(|position| CaveCarver::new(43).carves(position, 130))
+        .collect();
+    assert_ne!(carved, other_seed);
+}
+
+#[test]
+fn test_caves_line_up_across_chunk_borders() {
+    let generator = TerrainGenerator::new(42);
+    let mut carved_on_border = 0;
+    let mut continued = 0;
+
+    for position in [
+        na::Vector2::new(-3, 2),
+        na::Vector2::new(7, 7),
+        na::Vector2::new(20, -11),
+    ] {
+        let blocks = generator.generate(position);
+        let east = generator.generate(position + na::Vector2::new(1, 0));
+        let south = generator.generate(position + na::Vector2::new(0, 1));
+        let world = |x: usize, z: usize| {
+            (
+                position.x * X_SIZE as i32 + x as i32,
+                position.y * Z_SIZE as i32 + z as i32,
+            )
+        };
+
+        // Pairs of blocks facing each other across the east and south borders
+        let pairs = (0..Z_SIZE)
+            .map(|z| ((X_SIZE - 1, z), &east, (0, z), world(X_SIZE, z)))
+            .chain((0..X_SIZE).map(|x| ((x, Z_SIZE - 1), &south, (x, 0), world(x, Z_SIZE))));
+        for ((x, z), neighbor, (neighbor_x, neighbor_z), (world_x, world_z)) in pairs {
+            let (own_x, own_z) = world(x, z);
+            let height = generator.surface_height(own_x, own_z);
+            let neighbor_height = generator.surface_height(world_x, world_z);
+            for y in MIN_CAVE_HEIGHT..height.min(neighbor_height) {
+                let own_air = blocks[Chunk::block_index(x, y, z)] == generator.air;
+                let neighbor_air =
+                    neighbor[Chunk::block_index(neighbor_x, y, neighbor_z)] == generator.air;
+
+                // Both chunks agree with carving the whole world at once
+                assert_eq!(
+                    own_air,
+                    generator.caves.carves([own_x, y as i32, own_z], height)
+                );
+                assert_eq!(
+                    neighbor_air,
+                    generator
+                        .caves
+                        .carves([world_x, y as i32, world_z], neighbor_height)
+                );
+
+                if own_air {
+                    carved_on_border += 1;
+                    continued += neighbor_air as usize;
+                }
+            }
+        }
+    }
+
+    // Caves that reach a border go on in the neighbor instead of ending there
+    assert!(
+        carved_on_border > 20,
+        "only {carved_on_border} carved blocks"
+    );
+    assert!(
+        continued * 4 > carved_on_border * 3,
+        "{continued} of {carved_on_border} carved blocks continue"
+    );
+}
+
+#[test]
+fn test_caves_keep_the_world_closed() {
+    let generator = TerrainGenerator::new(42);
+    for position in [na::Vector2::new(0, 0), na::Vector2::new(-25, 40)] {
+        let blocks = generator.generate(position);
+        for x in 0..X_SIZE {
+            for z in 0..Z_SIZE {
+                let height = generator.surface_height(
+                    position.x * X_SIZE as i32 + x as i32,
+                    position.y * Z_SIZE as i32 + z as i32,
+                );
+                let roof = if height <= SEA_LEVEL {
+                    height - FILLER_DEPTH..=height
+                } else {
+                    0..=0
+                };
+                for y in (0..MIN_CAVE_HEIGHT).chain(roof) {
+                    assert_ne!(blocks[Chunk::block_index(x, y, z)], generator.air);
+                }
+            }
+        }
+    }
+}
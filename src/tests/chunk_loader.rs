@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use nalgebra as na;
+
+use crate::{chunk::Lod, chunk_loader::ChunkLoader};
+
+/// Tests that replace or edit [ALL_BLOCK_STATES](crate::chunk_loader::ALL_BLOCK_STATES)
+/// hold this so they don't run at the same time
+static BLOCK_STATES_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn lock_block_states() -> MutexGuard<'static, ()> {
+    BLOCK_STATES_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn assert_chunk_positions(chunk_loader: &ChunkLoader) {
+    let render_distance = (chunk_loader.rendered_chunks_length() / 2) as i32;
+    for x in 0..chunk_loader.rendered_chunks_length() as i32 {
+        for y in 0..chunk_loader.rendered_chunks_length() as i32 {
+            let expected = chunk_loader.center_chunk_position
+                + na::Vector2::new(x - render_distance, y - render_distance);
+            let chunk = &chunk_loader.chunks[(x, y)];
+            assert_eq!(
+                chunk.lock().unwrap().position,
+                expected,
+                "chunk ({x}, {y}) around {:?}",
+                chunk_loader.center_chunk_position
+            );
+            assert!(Arc::ptr_eq(chunk_loader.chunk_at(expected).unwrap(), chunk));
+        }
+    }
+    let outside = chunk_loader.center_chunk_position + na::Vector2::new(render_distance + 1, 0);
+    assert!(chunk_loader.chunk_at(outside).is_none());
+}
+
+#[test]
+fn test_chunk_loader_recentering() {
+    let _lock = lock_block_states();
+    let mut chunk_loader = ChunkLoader::new(2);
+    assert_chunk_positions(&chunk_loader);
+
+    let path = [
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, -1),
+        (-1, 2),
+        (3, 0),
+        (0, -4),
+        (-2, -2),
+        (5, 5),
+        (-40, 17),
+        (1, 0),
+    ];
+    let length = chunk_loader.rendered_chunks_length() as i32;
+    let mut center = chunk_loader.center_chunk_position;
+    for (x, y) in path {
+        center += na::Vector2::new(x, y);
+        let reloaded = chunk_loader.recenter(center);
+
+        let expected_reloaded = if x.abs() >= length || y.abs() >= length {
+            length * length
+        } else {
+            length * length - (length - x.abs()) * (length - y.abs())
+        };
+        assert_eq!(
+            reloaded.len() as i32,
+            expected_reloaded,
+            "moving by ({x}, {y})"
+        );
+        assert_eq!(chunk_loader.center_chunk_position, center);
+        assert_chunk_positions(&chunk_loader);
+    }
+}
+
+#[test]
+fn test_chunk_lods_follow_the_center() {
+    let _lock = lock_block_states();
+    let mut chunk_loader = ChunkLoader::new(6);
+    let assert_lods = |chunk_loader: &ChunkLoader| {
+        for chunk in chunk_loader.chunks.flatten() {
+            let chunk = chunk.lock().unwrap();
+            let offset = chunk.position - chunk_loader.center_chunk_position;
+            let distance = offset.x.abs().max(offset.y.abs()) as u32;
+            assert_eq!(
+                chunk.lod,
+                Lod::for_distance(distance),
+                "{:?}",
+                chunk.position
+            );
+        }
+    };
+    assert_lods(&chunk_loader);
+    assert_eq!(chunk_loader.chunks[(6, 0)].lock().unwrap().lod, Lod::Half);
+
+    for center in [(1, 0), (3, -2), (-20, 4)] {
+        chunk_loader.recenter(na::Vector2::new(center.0, center.1));
+        // None of the chunks were loaded, so none need new meshes
+        assert!(chunk_loader.update_lods().is_empty());
+        assert_lods(&chunk_loader);
+    }
+}
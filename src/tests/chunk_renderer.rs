@@ -0,0 +1,248 @@
+use gamezap::model::Vertex;
+use nalgebra as na;
+
+use crate::{
+    ambient_occlusion::AMBIENT_OCCLUSION_BRIGHTNESS,
+    block_registry::{RenderLayer, BLOCK_REGISTRY},
+    chunk::{Chunk, ChunkNeighborhood, MeshingMode, UnloadedNeighbors, X_SIZE, Z_SIZE},
+    chunk_renderer::{chunk_shader, layer_pipeline, vertex_layouts},
+    cube::{tile_coords, tiled_face, FACE_TEXTURE_OFFSET},
+    light::light_brightness,
+    tests::common::{filled, floor, id, isolated, light_alone},
+};
+
+fn shader() -> naga::Module {
+    naga::front::wgsl::parse_str(&chunk_shader()).unwrap()
+}
+
+/// Location, name and format of every input of the shader's vertex stage
+fn vertex_inputs() -> Vec<(u32, String, wgpu::VertexFormat)> {
+    let module = shader();
+    let vertex_stage = module
+        .entry_points
+        .iter()
+        .find(|entry_point| entry_point.stage == naga::ShaderStage::Vertex)
+        .unwrap();
+    let mut inputs = vec![];
+    for argument in &vertex_stage.function.arguments {
+        let naga::TypeInner::Struct { members, .. } = &module.types[argument.ty].inner else {
+            panic!("Vertex inputs should be grouped in a struct");
+        };
+        for member in members {
+            let Some(naga::Binding::Location { location, .. }) = member.binding else {
+                continue;
+            };
+            let format = match module.types[member.ty].inner {
+                naga::TypeInner::Scalar { .. } => wgpu::VertexFormat::Float32,
+                naga::TypeInner::Vector { size, .. } => match size {
+                    naga::VectorSize::Bi => wgpu::VertexFormat::Float32x2,
+                    naga::VectorSize::Tri => wgpu::VertexFormat::Float32x3,
+                    naga::VectorSize::Quad => wgpu::VertexFormat::Float32x4,
+                },
+                ref other => panic!("Unexpected vertex input {other:?}"),
+            };
+            inputs.push((location, member.name.clone().unwrap(), format));
+        }
+    }
+    inputs
+}
+
+/// Attribute of the vertex buffer layouts feeding the shader input `name`
+fn attribute(name: &str) -> (usize, wgpu::VertexAttribute) {
+    let (location, ..) = vertex_inputs()
+        .into_iter()
+        .find(|(_, input, _)| input == name)
+        .unwrap_or_else(|| panic!("The shader has no {name} input"));
+    vertex_layouts()
+        .iter()
+        .enumerate()
+        .find_map(|(slot, layout)| {
+            let attribute = layout
+                .attributes
+                .iter()
+                .find(|attribute| attribute.shader_location == location)?;
+            Some((slot, *attribute))
+        })
+        .unwrap()
+}
+
+/// Reads the attribute the shader sees for `vertex`
+fn read_attribute(vertex: &Vertex, attribute: wgpu::VertexAttribute) -> Vec<f32> {
+    let bytes = bytemuck::bytes_of(vertex);
+    let offset = attribute.offset as usize;
+    bytemuck::pod_collect_to_vec(&bytes[offset..offset + attribute.format.size() as usize])
+}
+
+#[test]
+fn test_chunk_shader_is_valid() {
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&shader())
+    .unwrap();
+}
+
+#[test]
+fn test_layer_pipelines() {
+    let opaque = layer_pipeline(RenderLayer::Opaque);
+    assert_eq!(opaque.blend, wgpu::BlendState::REPLACE);
+    assert!(opaque.depth_write_enabled);
+
+    // Blended faces don't hide the ones behind them
+    let transparent = layer_pipeline(RenderLayer::Transparent);
+    assert_eq!(transparent.blend, wgpu::BlendState::ALPHA_BLENDING);
+    assert!(!transparent.depth_write_enabled);
+
+    // Cutout faces discard their holes instead of blending them
+    let cutout = layer_pipeline(RenderLayer::Cutout);
+    assert_eq!(cutout.blend, wgpu::BlendState::REPLACE);
+    assert!(cutout.depth_write_enabled);
+    let discards = |entry_point: &str| {
+        let module = shader();
+        let fragment_stage = module
+            .entry_points
+            .iter()
+            .find(|stage| stage.name == entry_point)
+            .unwrap_or_else(|| panic!("The shader has no {entry_point} stage"));
+        fn kills(block: &naga::Block) -> bool {
+            block.iter().any(|statement| match statement {
+                naga::Statement::Kill => true,
+                naga::Statement::Block(block) => kills(block),
+                naga::Statement::If { accept, reject, .. } => kills(accept) || kills(reject),
+                _ => false,
+            })
+        }
+        kills(&fragment_stage.function.body)
+    };
+    assert!(discards(cutout.fragment_entry_point));
+    assert!(!discards(opaque.fragment_entry_point));
+    assert!(!discards(transparent.fragment_entry_point));
+}
+
+#[test]
+fn test_shader_tile_size_matches_the_atlas() {
+    let module = shader();
+    let (_, tile_size) = module
+        .constants
+        .iter()
+        .find(|(_, constant)| constant.name.as_deref() == Some("TILE_SIZE"))
+        .unwrap();
+    let naga::Expression::Literal(naga::Literal::F32(tile_size)) =
+        module.const_expressions[tile_size.init]
+    else {
+        panic!("TILE_SIZE should be a float");
+    };
+    assert_eq!(tile_size, FACE_TEXTURE_OFFSET);
+}
+
+#[test]
+fn test_vertex_layouts_feed_every_shader_input() {
+    let layouts = vertex_layouts();
+    for (location, name, format) in vertex_inputs() {
+        let attribute = layouts
+            .iter()
+            .flat_map(|layout| layout.attributes)
+            .find(|attribute| attribute.shader_location == location)
+            .unwrap_or_else(|| panic!("Nothing feeds {name} at location {location}"));
+        assert_eq!(attribute.format, format, "{name}");
+    }
+    assert_eq!(
+        layouts[0].array_stride,
+        std::mem::size_of::<Vertex>() as u64
+    );
+}
+
+#[test]
+fn test_greedy_faces_repeat_their_tile() {
+    let (_, tex_coords) = attribute("tex_coords");
+    let (_, tile) = attribute("tile");
+
+    // The top of a 16 by 16 floor
+    let grass = BLOCK_REGISTRY
+        .get(BLOCK_REGISTRY.id_of("grass").unwrap())
+        .unwrap();
+    let top = tiled_face(
+        0b0010_0000,
+        na::Vector3::new(0.0, 1.0, 0.0),
+        na::Vector3::new(16.0, 1.0, 16.0),
+        grass,
+    )
+    .unwrap();
+
+    let (u, v) = tile_coords(grass.textures.unwrap().top);
+    let mut corners = vec![];
+    for vertex in &top {
+        assert_eq!(read_attribute(vertex, tile), [u, v]);
+        corners.push(read_attribute(vertex, tex_coords));
+    }
+    // One repetition of the tile per block of the floor
+    for corner in [[0.0, 0.0], [0.0, 16.0], [16.0, 0.0], [16.0, 16.0]] {
+        assert!(corners.contains(&corner.to_vec()), "{corners:?}");
+    }
+}
+
+#[test]
+fn test_shade_attribute_reads_ambient_occlusion() {
+    let (slot, shade) = attribute("shade");
+    assert_eq!(slot, 0, "The shade changes per vertex");
+
+    // A block on the floor darkens the corners of the floor around it
+    let mut blocks = floor("stone");
+    blocks.set(Chunk::block_index(8, 2, 8), id("stone"));
+    let faces = Chunk::build_faces(
+        isolated(&blocks, UnloadedNeighbors::Show),
+        MeshingMode::Naive,
+    )
+    .unwrap()
+    .opaque;
+
+    let shades: Vec<f32> = faces
+        .iter()
+        .flatten()
+        .map(|vertex| read_attribute(vertex, shade)[0])
+        .collect();
+    for brightness in &AMBIENT_OCCLUSION_BRIGHTNESS[1..] {
+        assert!(
+            shades.contains(brightness),
+            "No vertex has a shade of {brightness}"
+        );
+    }
+    assert!(shades
+        .iter()
+        .all(|shade| AMBIENT_OCCLUSION_BRIGHTNESS.contains(shade)));
+}
+
+#[test]
+fn test_shade_attribute_reads_light() {
+    let (_, shade) = attribute("shade");
+
+    // A stone under a roof, lit by a lamp beside it
+    let mut blocks = filled("air");
+    blocks.set(Chunk::block_index(8, 1, 8), id("stone"));
+    for x in 0..X_SIZE {
+        for z in 0..Z_SIZE {
+            blocks.set(Chunk::block_index(x, 5, z), id("stone"));
+        }
+    }
+    blocks.set(Chunk::block_index(8, 3, 10), id("lamp"));
+    let light = light_alone(&blocks);
+    let neighborhood = ChunkNeighborhood {
+        light: [Some(&light), None, None, None, None, None, None, None, None],
+        ..isolated(&blocks, UnloadedNeighbors::Hide)
+    };
+    let faces = Chunk::build_faces(neighborhood, MeshingMode::Naive)
+        .unwrap()
+        .opaque;
+
+    let top = faces
+        .iter()
+        .find(|face| face[0].normal == [0.0, 1.0, 0.0] && face[0].position[1] == 2.0)
+        .unwrap();
+    let brightness = light_brightness(light[Chunk::block_index(8, 2, 8)]);
+    assert!(brightness < 1.0);
+    for vertex in top {
+        let open = AMBIENT_OCCLUSION_BRIGHTNESS[3];
+        assert_eq!(read_attribute(vertex, shade)[0], open * brightness);
+    }
+}
@@ -0,0 +1,82 @@
+//! Fixtures shared by the meshing, light, rendering and tick tests
+
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    block_storage::BlockArray,
+    chunk::{Chunk, ChunkNeighborhood, UnloadedNeighbors, BLOCK_COUNT, X_SIZE, Z_SIZE},
+    light::{LightArray, LightRegion},
+    tick::{TickScheduler, TickWorld},
+};
+
+pub fn id(name: &str) -> u16 {
+    BLOCK_REGISTRY.id_of(name).unwrap()
+}
+
+pub fn filled(name: &str) -> Box<BlockArray> {
+    Box::new(BlockArray::filled(id(name)))
+}
+
+/// Air above a layer of `name` at height 1 on top of stone
+pub fn floor(name: &str) -> Box<BlockArray> {
+    let mut blocks = filled("air");
+    for x in 0..X_SIZE {
+        for z in 0..Z_SIZE {
+            blocks.set(Chunk::block_index(x, 0, z), id("stone"));
+            blocks.set(Chunk::block_index(x, 1, z), id(name));
+        }
+    }
+    blocks
+}
+
+/// Replaces the block at `position` and lets the ticks around it know,
+/// like placing it in the world does
+pub fn place(
+    blocks: &mut BlockArray,
+    ticks: &mut TickScheduler,
+    position: [i32; 3],
+    name: &str,
+) {
+    let position = nalgebra::Vector3::from(position);
+    blocks.replace_block(position, id(name));
+    ticks.block_changed(blocks, position);
+}
+
+/// `blocks` surrounded by `neighbors` on every side, without light levels
+pub fn neighborhood<'a>(
+    blocks: &'a BlockArray,
+    neighbors: Option<&'a BlockArray>,
+    unloaded_neighbors: UnloadedNeighbors,
+) -> ChunkNeighborhood<'a> {
+    ChunkNeighborhood {
+        blocks,
+        negative_x: neighbors,
+        positive_x: neighbors,
+        negative_z: neighbors,
+        positive_z: neighbors,
+        corners: [neighbors; 4],
+        light: [None; 9],
+        unloaded_neighbors,
+    }
+}
+
+/// `blocks` without any loaded neighbors
+pub fn isolated(
+    blocks: &BlockArray,
+    unloaded_neighbors: UnloadedNeighbors,
+) -> ChunkNeighborhood<'_> {
+    neighborhood(blocks, None, unloaded_neighbors)
+}
+
+/// A light region holding only the chunk in the middle
+pub fn alone<'a>(blocks: &'a BlockArray, light: &'a mut LightArray) -> LightRegion<'a> {
+    let mut chunks: [Option<(&BlockArray, &mut LightArray)>; 9] = Default::default();
+    chunks[0] = Some((blocks, light));
+    LightRegion::new(chunks)
+}
+
+/// Light levels of `blocks` without any loaded neighbors
+pub fn light_alone(blocks: &BlockArray) -> Box<LightArray> {
+    let mut light = Box::new([0; BLOCK_COUNT]);
+    alone(blocks, &mut light).light_chunk().unwrap();
+    light
+}
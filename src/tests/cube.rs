@@ -0,0 +1,74 @@
+use nalgebra as na;
+
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    cube::{tile_coords, Cube, FACE_TEXTURE_OFFSET},
+};
+
+/// Face mask bit, face normal and which tile of the block the face should use
+const FACES: [(u8, [f32; 3], &str); 6] = [
+    (0b0000_0001, [0.0, 0.0, -1.0], "side"),
+    (0b0000_0010, [0.0, 0.0, 1.0], "side"),
+    (0b0000_0100, [-1.0, 0.0, 0.0], "side"),
+    (0b0000_1000, [1.0, 0.0, 0.0], "side"),
+    (0b0001_0000, [0.0, -1.0, 0.0], "bottom"),
+    (0b0010_0000, [0.0, 1.0, 0.0], "top"),
+];
+
+#[test]
+fn test_face_texture_coords() {
+    for block in BLOCK_REGISTRY.iter() {
+        let Some(textures) = block.textures else {
+            continue;
+        };
+
+        for (mask, normal, tile_name) in FACES {
+            let tile = match tile_name {
+                "top" => textures.top,
+                "bottom" => textures.bottom,
+                _ => textures.side,
+            };
+            let (u, v) = tile_coords(tile);
+
+            let cube = Cube::new(na::Vector3::new(3.0, 7.0, 5.0), 0, block, mask, true);
+            assert_eq!(cube.mesh_info.face_count, 1);
+
+            let face = &cube.mesh_info.faces[0];
+            for vertex in face {
+                assert_eq!(vertex.normal, normal);
+                assert_eq!(
+                    vertex.tangent,
+                    [u, v, 0.0],
+                    "{} {tile_name} face samples the wrong tile",
+                    block.name
+                );
+                let [vertex_u, vertex_v] = vertex.tex_coords;
+                assert!(
+                    (vertex_u == 0.0 || vertex_u == 1.0) && (vertex_v == 0.0 || vertex_v == 1.0),
+                    "{} {tile_name} face samples {:?} outside of tile {tile:?}",
+                    block.name,
+                    vertex.tex_coords
+                );
+            }
+
+            // The face must cover the whole tile, not a single texel
+            let corners = face
+                .iter()
+                .map(|vertex| (vertex.tex_coords[0] > 0.0, vertex.tex_coords[1] > 0.0))
+                .collect::<Vec<_>>();
+            for corner in [(false, false), (false, true), (true, false), (true, true)] {
+                assert!(corners.contains(&corner));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tile_coords() {
+    assert_eq!(tile_coords((0, 0)), (0.0, 0.0));
+    assert_eq!(tile_coords((1, 0)), (FACE_TEXTURE_OFFSET, 0.0));
+    assert_eq!(
+        tile_coords((2, 3)),
+        (2.0 * FACE_TEXTURE_OFFSET, 3.0 * FACE_TEXTURE_OFFSET)
+    );
+}
@@ -0,0 +1,168 @@
+use crate::{
+    block_storage::BlockArray,
+    chunk::{Chunk, ChunkNeighborhood, UnloadedNeighbors, X_SIZE, Y_SIZE, Z_SIZE},
+    cube::FACE_NEIGHBORS,
+    tests::common::{filled, id, isolated},
+};
+
+const ALL_FACES: u8 = 0b0011_1111;
+
+fn face_mask(
+    blocks: &BlockArray,
+    neighbors: [Option<&BlockArray>; 4],
+    unloaded_neighbors: UnloadedNeighbors,
+    position: (usize, usize, usize),
+) -> u8 {
+    let [negative_x, positive_x, negative_z, positive_z] = neighbors;
+    let neighborhood = ChunkNeighborhood {
+        negative_x,
+        positive_x,
+        negative_z,
+        positive_z,
+        ..isolated(blocks, unloaded_neighbors)
+    };
+    Chunk::query_neighbors(neighborhood, position.0, position.1, position.2).unwrap()
+}
+
+/// Index into a `[negative_x, positive_x, negative_z, positive_z]` neighbor
+/// array and the position of the block across the border, for every
+/// horizontal face of a block at `(x, y, z)`
+fn across_border(face_bit: u8, x: usize, y: usize, z: usize) -> (usize, (usize, usize, usize)) {
+    match face_bit {
+        0b0000_0001 => (2, (x, y, Z_SIZE - 1)),
+        0b0000_0010 => (3, (x, y, 0)),
+        0b0000_0100 => (0, (X_SIZE - 1, y, z)),
+        0b0000_1000 => (1, (0, y, z)),
+        _ => unreachable!("{face_bit:#b} isn't a horizontal face"),
+    }
+}
+
+#[test]
+fn test_face_mask_inside_chunk() {
+    let (x, y, z) = (8, 100, 8);
+    let mut blocks = filled("air");
+    blocks.set(Chunk::block_index(x, y, z), id("stone"));
+
+    for solid_faces in 0..=ALL_FACES {
+        for (face_bit, offset) in FACE_NEIGHBORS {
+            let neighbor = Chunk::block_index(
+                (x as i32 + offset[0]) as usize,
+                (y as i32 + offset[1]) as usize,
+                (z as i32 + offset[2]) as usize,
+            );
+            let block = if solid_faces & face_bit != 0 {
+                id("stone")
+            } else {
+                id("air")
+            };
+            blocks.set(neighbor, block);
+        }
+
+        for unloaded_neighbors in [UnloadedNeighbors::Hide, UnloadedNeighbors::Show] {
+            assert_eq!(
+                face_mask(&blocks, [None; 4], unloaded_neighbors, (x, y, z)),
+                ALL_FACES & !solid_faces,
+                "solid faces {solid_faces:#08b}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_face_mask_chunk_edges() {
+    let (y, middle) = (100, 8);
+    let edges = [
+        (0b0000_0001, (middle, y, 0)),
+        (0b0000_0010, (middle, y, Z_SIZE - 1)),
+        (0b0000_0100, (0, y, middle)),
+        (0b0000_1000, (X_SIZE - 1, y, middle)),
+    ];
+
+    for (face_bit, position) in edges {
+        let mut blocks = filled("air");
+        blocks.set(
+            Chunk::block_index(position.0, position.1, position.2),
+            id("stone"),
+        );
+        let (neighbor_index, (nx, ny, nz)) = across_border(face_bit, position.0, y, position.2);
+        let inner_faces = ALL_FACES & !face_bit;
+
+        let open = filled("air");
+        let mut closed = filled("air");
+        closed.set(Chunk::block_index(nx, ny, nz), id("stone"));
+        // Solid blocks next to the one across the border don't matter
+        let mut elsewhere = filled("air");
+        elsewhere.set(Chunk::block_index(nx, ny + 1, nz), id("stone"));
+
+        for unloaded_neighbors in [UnloadedNeighbors::Hide, UnloadedNeighbors::Show] {
+            for (neighbor, visible) in [(&open, true), (&closed, false), (&elsewhere, true)] {
+                let mut neighbors = [None; 4];
+                neighbors[neighbor_index] = Some(&**neighbor);
+                let expected = if visible { ALL_FACES } else { inner_faces };
+                assert_eq!(
+                    face_mask(&blocks, neighbors, unloaded_neighbors, position),
+                    expected,
+                    "face {face_bit:#08b} with {unloaded_neighbors:?}"
+                );
+            }
+
+            // Only the neighbor on the block's side is read
+            let mut other_neighbors = [Some(&*closed); 4];
+            other_neighbors[neighbor_index] = Some(&*open);
+            assert_eq!(
+                face_mask(&blocks, other_neighbors, unloaded_neighbors, position),
+                ALL_FACES
+            );
+        }
+
+        assert_eq!(
+            face_mask(&blocks, [None; 4], UnloadedNeighbors::Hide, position),
+            inner_faces
+        );
+        assert_eq!(
+            face_mask(&blocks, [None; 4], UnloadedNeighbors::Show, position),
+            ALL_FACES
+        );
+    }
+}
+
+#[test]
+fn test_face_mask_chunk_corner() {
+    let mut blocks = filled("air");
+    blocks.set(Chunk::block_index(0, 100, 0), id("stone"));
+    let open = filled("air");
+    let mut closed = filled("air");
+    for x in 0..X_SIZE {
+        for z in 0..Z_SIZE {
+            closed.set(Chunk::block_index(x, 100, z), id("stone"));
+        }
+    }
+
+    let mask = face_mask(
+        &blocks,
+        [Some(&closed), Some(&closed), Some(&open), Some(&closed)],
+        UnloadedNeighbors::Hide,
+        (0, 100, 0),
+    );
+    assert_eq!(mask, ALL_FACES & !0b0000_0100);
+}
+
+#[test]
+fn test_face_mask_world_edges() {
+    let mut blocks = filled("air");
+    blocks.set(Chunk::block_index(8, 0, 8), id("stone"));
+    blocks.set(Chunk::block_index(8, Y_SIZE - 1, 8), id("stone"));
+
+    for unloaded_neighbors in [UnloadedNeighbors::Hide, UnloadedNeighbors::Show] {
+        // Nothing can see the bottom of the world
+        assert_eq!(
+            face_mask(&blocks, [None; 4], unloaded_neighbors, (8, 0, 8)),
+            ALL_FACES & !0b0001_0000
+        );
+        // The top of the world is open sky
+        assert_eq!(
+            face_mask(&blocks, [None; 4], unloaded_neighbors, (8, Y_SIZE - 1, 8)),
+            ALL_FACES
+        );
+    }
+}
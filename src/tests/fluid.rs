@@ -0,0 +1,165 @@
+use nalgebra as na;
+
+use crate::{
+    block_storage::BlockArray,
+    chunk::{Chunk, MeshingMode, UnloadedNeighbors},
+    fluid::{FLUIDS, FLUID_REGISTRY},
+    tests::common::{floor, id, isolated, place},
+    tick::{TickScheduler, TickWorld},
+};
+
+const WATER: usize = 0;
+const LAVA: usize = 1;
+
+fn level_at(blocks: &BlockArray, position: [i32; 3]) -> u8 {
+    blocks
+        .block_at(na::Vector3::from(position))
+        .and_then(|block| FLUID_REGISTRY.get(block))
+        .map_or(0, |fluid| fluid.level)
+}
+
+#[test]
+fn test_source_spreads_with_falling_levels() {
+    let mut blocks = floor("stone");
+    let mut simulation = TickScheduler::new(0);
+    place(&mut blocks, &mut simulation, [8, 2, 8], "water");
+    let ticks = simulation.settle(&mut *blocks, 1000);
+    assert!(ticks < 1000, "Water never settled");
+
+    for distance in 0..8 {
+        let level = 8 - distance as u8;
+        assert_eq!(level_at(&blocks, [8 + distance, 2, 8]), level);
+        assert_eq!(level_at(&blocks, [8, 2, 8 - distance]), level);
+    }
+    // Levels fall by one for every block walked around corners too
+    assert_eq!(level_at(&blocks, [10, 2, 10]), 4);
+    assert_eq!(level_at(&blocks, [12, 2, 12]), 0);
+    assert_eq!(level_at(&blocks, [8, 3, 8]), 0);
+}
+
+#[test]
+fn test_fluids_wait_for_their_tick_delay() {
+    let mut blocks = floor("stone");
+    let mut simulation = TickScheduler::new(0);
+    place(&mut blocks, &mut simulation, [8, 2, 8], "lava");
+
+    for _ in 0..FLUIDS[LAVA].tick_delay - 1 {
+        assert!(simulation.tick(&mut *blocks).is_empty());
+    }
+    let changed = simulation.tick(&mut *blocks);
+    assert_eq!(changed.len(), 4);
+    assert_eq!(level_at(&blocks, [9, 2, 8]), 3);
+}
+
+#[test]
+fn test_water_falls_as_a_column() {
+    let mut blocks = floor("stone");
+    let mut simulation = TickScheduler::new(0);
+    place(&mut blocks, &mut simulation, [8, 10, 8], "water");
+    simulation.settle(&mut *blocks, 1000);
+
+    let falling = FLUID_REGISTRY.source_level(WATER) - 1;
+    for y in 2..10 {
+        assert_eq!(level_at(&blocks, [8, y, 8]), falling);
+    }
+    // The source falls instead of spreading
+    assert_eq!(level_at(&blocks, [9, 10, 8]), 0);
+    // Once it lands, the water spreads over the floor
+    assert_eq!(level_at(&blocks, [9, 2, 8]), falling - 1);
+}
+
+#[test]
+fn test_flowing_water_dries_up_without_a_source() {
+    let mut blocks = floor("stone");
+    let mut simulation = TickScheduler::new(0);
+    place(&mut blocks, &mut simulation, [8, 2, 8], "water");
+    simulation.settle(&mut *blocks, 1000);
+    assert_eq!(level_at(&blocks, [11, 2, 8]), 5);
+
+    place(&mut blocks, &mut simulation, [8, 2, 8], "stone");
+    assert!(simulation.settle(&mut *blocks, 1000) < 1000);
+    let water_left = blocks
+        .iter()
+        .filter(|&block| FLUID_REGISTRY.get(block).is_some())
+        .count();
+    assert_eq!(water_left, 0);
+}
+
+#[test]
+fn test_lava_spreads_less_than_water() {
+    let mut blocks = floor("stone");
+    let mut simulation = TickScheduler::new(0);
+    place(&mut blocks, &mut simulation, [8, 2, 8], "lava");
+    simulation.settle(&mut *blocks, 1000);
+
+    assert_eq!(level_at(&blocks, [11, 2, 8]), 1);
+    assert_eq!(level_at(&blocks, [12, 2, 8]), 0);
+}
+
+#[test]
+fn test_fluids_stay_inside_walls() {
+    let mut blocks = floor("stone");
+    let mut simulation = TickScheduler::new(0);
+    for z in 7..=9 {
+        blocks.replace_block(na::Vector3::new(9, 2, z), id("stone"));
+        blocks.replace_block(na::Vector3::new(7, 2, z), id("stone"));
+    }
+    blocks.replace_block(na::Vector3::new(8, 2, 9), id("stone"));
+    place(&mut blocks, &mut simulation, [8, 2, 8], "water");
+    simulation.settle(&mut *blocks, 1000);
+
+    assert_eq!(level_at(&blocks, [8, 2, 7]), 7);
+    assert_eq!(level_at(&blocks, [8, 2, 4]), 4);
+    assert_eq!(level_at(&blocks, [9, 2, 6]), 5);
+    // Going around the wall costs levels
+    assert_eq!(level_at(&blocks, [10, 2, 8]), 2);
+    assert_eq!(level_at(&blocks, [8, 2, 10]), 0);
+}
+
+#[test]
+fn test_flowing_surfaces_are_lowered() {
+    let mut blocks = floor("stone");
+    let mut simulation = TickScheduler::new(0);
+    place(&mut blocks, &mut simulation, [8, 2, 8], "water");
+    simulation.settle(&mut *blocks, 1000);
+
+    let neighborhood = isolated(&blocks, UnloadedNeighbors::Hide);
+    assert_eq!(neighborhood.fluid_height([8, 2, 8]), Some(1.0));
+    assert_eq!(neighborhood.fluid_height([10, 2, 8]), Some(6.0 / 8.0));
+    assert_eq!(neighborhood.fluid_height([8, 1, 8]), None);
+
+    // The top of a flowing block sits at its surface
+    let block = Chunk::gen_block_vertices(neighborhood, 10, 2, 8).unwrap();
+    let top = block.faces[..block.face_count]
+        .iter()
+        .find(|face| face[0].normal == [0.0, 1.0, 0.0])
+        .expect("Flowing water has a top face");
+    assert!(top.iter().all(|vertex| vertex.position[1] == 2.75));
+
+    // Every mode draws the surface of each level, and the source's sides
+    // above the lower levels around it
+    for meshing_mode in [MeshingMode::Naive, MeshingMode::Greedy] {
+        let faces = Chunk::build_faces(neighborhood, meshing_mode).unwrap();
+        let heights: Vec<f32> = faces
+            .transparent
+            .iter()
+            .filter(|face| face[0].normal == [0.0, 1.0, 0.0])
+            .map(|face| face[0].position[1])
+            .collect();
+        for level in 1..=8 {
+            let height = 2.0 + level as f32 / 8.0;
+            assert!(
+                heights.contains(&height),
+                "{meshing_mode:?} has no surface at {height}"
+            );
+        }
+        let source_sides = faces
+            .transparent
+            .iter()
+            .filter(|face| face[0].normal == [1.0, 0.0, 0.0])
+            .filter(|face| face.iter().all(|vertex| vertex.position[0] == 9.0))
+            .filter(|face| face.iter().any(|vertex| vertex.position[1] == 3.0))
+            .count();
+        assert_eq!(source_sides, 1, "{meshing_mode:?}");
+    }
+}
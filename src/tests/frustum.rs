@@ -0,0 +1,159 @@
+use std::f32::consts::FRAC_PI_2;
+
+use nalgebra as na;
+
+use crate::{
+    aabb::Aabb,
+    block_storage::{BlockArray, ALL_SECTIONS, SECTION_HEIGHT},
+    camera::{look_direction, Projection},
+    chunk::{Chunk, Lod, MeshingMode, UnloadedNeighbors, X_SIZE, Z_SIZE},
+    frustum::Frustum,
+    tests::common::{id, isolated},
+};
+
+/// Unit box with its center at `center`
+fn block_at(center: na::Vector3<f32>) -> Aabb {
+    Aabb {
+        min: center - na::Vector3::repeat(0.5),
+        max: center + na::Vector3::repeat(0.5),
+    }
+}
+
+/// Camera at the origin looking along +x
+fn looking_east() -> Frustum {
+    Frustum::new(na::Vector3::zeros(), 0.0, 0.0, Projection::default())
+}
+
+#[test]
+fn test_boxes_in_view_are_visible() {
+    let frustum = looking_east();
+    assert!(frustum.intersects(&block_at(na::Vector3::new(10.0, 0.0, 0.0))));
+    assert!(frustum.intersects(&block_at(na::Vector3::new(10.0, 2.0, 3.0))));
+
+    // Boxes around the camera are never culled
+    assert!(frustum.intersects(&block_at(na::Vector3::zeros())));
+}
+
+#[test]
+fn test_boxes_out_of_view_are_culled() {
+    let frustum = looking_east();
+    let projection = Projection::default();
+
+    // Behind the camera
+    assert!(!frustum.intersects(&block_at(na::Vector3::new(-10.0, 0.0, 0.0))));
+    // Beyond the far plane
+    assert!(!frustum.intersects(&block_at(na::Vector3::new(
+        projection.zfar + 10.0,
+        0.0,
+        0.0
+    ))));
+
+    // To the sides, just outside of the field of view
+    let vertical = (projection.fovy.to_radians() / 2.0).tan() * 10.0;
+    let horizontal = vertical * projection.aspect;
+    for offset in [
+        na::Vector3::new(0.0, vertical + 1.0, 0.0),
+        na::Vector3::new(0.0, -vertical - 1.0, 0.0),
+        na::Vector3::new(0.0, 0.0, horizontal + 1.0),
+        na::Vector3::new(0.0, 0.0, -horizontal - 1.0),
+    ] {
+        let center = na::Vector3::new(10.0, 0.0, 0.0) + offset;
+        assert!(!frustum.intersects(&block_at(center)), "{center:?}");
+    }
+
+    // A box crossing the edge of the view is visible
+    let straddling = na::Vector3::new(10.0, 0.0, horizontal);
+    assert!(frustum.intersects(&block_at(straddling)));
+}
+
+#[test]
+fn test_frustum_follows_the_camera_rotation() {
+    let eye = na::Vector3::new(5.0, 70.0, -3.0);
+    let above = block_at(eye + na::Vector3::new(0.0, 20.0, 0.0));
+    let north = block_at(eye + na::Vector3::new(0.0, 0.0, 20.0));
+
+    let looking_up = Frustum::new(eye, 0.0, FRAC_PI_2 - 0.01, Projection::default());
+    assert!(looking_up.intersects(&above));
+    assert!(!looking_up.intersects(&north));
+
+    let looking_down = Frustum::new(eye, 0.0, -FRAC_PI_2 + 0.01, Projection::default());
+    assert!(!looking_down.intersects(&above));
+
+    let looking_north = Frustum::new(eye, FRAC_PI_2, 0.0, Projection::default());
+    assert!(looking_north.intersects(&north));
+    assert!(!looking_north.intersects(&above));
+}
+
+#[test]
+fn test_view_projection_matches_the_frustum() {
+    let eye = na::Vector3::new(5.0, 70.0, -3.0);
+    let projection = Projection::default();
+    for (yaw, pitch) in [
+        (0.0, 0.0),
+        (FRAC_PI_2, 0.3),
+        (2.0, -1.2),
+        (0.0, FRAC_PI_2 - 0.01),
+    ] {
+        let frustum = Frustum::new(eye, yaw, pitch, projection);
+        let view_projection = projection.view_projection(eye, yaw, pitch);
+        let to_device = |point: na::Vector3<f32>| {
+            let clip = view_projection * point.push(1.0);
+            (clip.xyz() / clip.w, clip.w)
+        };
+
+        let (center, _) = to_device(eye + look_direction(yaw, pitch) * 10.0);
+        assert!(center.x.abs() < 1e-4 && center.y.abs() < 1e-4, "{center:?}");
+        assert!((0.0..1.0).contains(&center.z));
+
+        // Points clearly inside or outside of the screen agree with the frustum
+        for x in -5..=5 {
+            for y in -5..=5 {
+                for z in -5..=5 {
+                    let point = eye + na::Vector3::new(x, y, z).cast::<f32>() * 7.0;
+                    let (device, w) = to_device(point);
+                    let visible = frustum.intersects(&Aabb {
+                        min: point,
+                        max: point,
+                    });
+                    if w > 0.0 && device.x.abs().max(device.y.abs()) < 0.99 && device.z < 1.0 {
+                        assert!(visible, "{point:?} at {device:?}");
+                    } else if w <= 0.0 || device.x.abs().max(device.y.abs()) > 1.01 {
+                        assert!(!visible, "{point:?} at {device:?}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_mesh_bounds_cover_sections_with_faces() {
+    let air = BlockArray::filled(id("air"));
+    let mut blocks = air.clone();
+    let stone = id("stone");
+    blocks.set(Chunk::block_index(3, SECTION_HEIGHT + 2, 3), stone);
+    blocks.set(Chunk::block_index(3, 4 * SECTION_HEIGHT, 3), stone);
+    let build = |blocks| {
+        let mut chunk = Chunk::new(na::Vector2::new(2, -1), (0, 0));
+        chunk.replace_sections(
+            Chunk::build_sections(
+                isolated(blocks, UnloadedNeighbors::Hide),
+                ALL_SECTIONS,
+                MeshingMode::Greedy,
+                Lod::Full,
+            )
+            .unwrap(),
+        );
+        chunk
+    };
+
+    assert_eq!(build(&air).mesh_bounds(), None);
+    let bounds = build(&blocks).mesh_bounds().unwrap();
+    assert_eq!(
+        bounds,
+        Aabb {
+            min: na::Vector3::new(2.0 * X_SIZE as f32, SECTION_HEIGHT as f32, -(Z_SIZE as f32)),
+            max: na::Vector3::new(3.0 * X_SIZE as f32, 5.0 * SECTION_HEIGHT as f32, 0.0),
+        }
+    );
+}
@@ -0,0 +1,63 @@
+use nalgebra as na;
+
+use crate::{
+    block_storage::BlockArray,
+    chunk::{Chunk, MeshingMode, UnloadedNeighbors, X_SIZE, Z_SIZE},
+    cube::Face,
+    terrain::TERRAIN_GENERATOR,
+    tests::common::{filled, id, isolated},
+};
+
+/// Number of block faces a quad covers. Greedy quads measure their texture
+/// coordinates in tiles, so the largest coordinates are the quad's size.
+fn greedy_face_area(face: &Face) -> f32 {
+    let max_u = face.iter().map(|v| v.tex_coords[0]).fold(0.0, f32::max);
+    let max_v = face.iter().map(|v| v.tex_coords[1]).fold(0.0, f32::max);
+    max_u * max_v
+}
+
+fn compare_meshing_modes(name: &str, blocks: &BlockArray) -> (Vec<Face>, Vec<Face>) {
+    let neighborhood = isolated(blocks, UnloadedNeighbors::Show);
+    let naive = Chunk::build_faces(neighborhood, MeshingMode::Naive)
+        .unwrap()
+        .opaque;
+    let greedy = Chunk::build_faces(neighborhood, MeshingMode::Greedy)
+        .unwrap()
+        .opaque;
+    println!(
+        "{name}: naive {} faces / {} vertices, greedy {} faces / {} vertices",
+        naive.len(),
+        naive.len() * 4,
+        greedy.len(),
+        greedy.len() * 4
+    );
+
+    let greedy_area: f32 = greedy.iter().map(greedy_face_area).sum();
+    assert_eq!(greedy_area, naive.len() as f32);
+    assert!(greedy.len() <= naive.len());
+    (naive, greedy)
+}
+
+#[test]
+fn test_greedy_meshing() {
+    let grass = id("grass");
+    let mut blocks = filled("air");
+    for x in 0..X_SIZE {
+        for z in 0..Z_SIZE {
+            blocks.set(Chunk::block_index(x, 200, z), grass);
+        }
+    }
+
+    let (naive, greedy) = compare_meshing_modes("flat slab", &blocks);
+    let count_faces = |faces: &Vec<Face>, normal: [f32; 3]| {
+        faces.iter().filter(|face| face[0].normal == normal).count()
+    };
+    assert_eq!(count_faces(&naive, [0.0, 1.0, 0.0]), X_SIZE * Z_SIZE);
+    assert_eq!(count_faces(&greedy, [0.0, 1.0, 0.0]), 1);
+    assert_eq!(count_faces(&naive, [0.0, 0.0, -1.0]), X_SIZE);
+    assert_eq!(count_faces(&greedy, [0.0, 0.0, -1.0]), 1);
+
+    *blocks = TERRAIN_GENERATOR.generate(na::Vector2::new(0, 0));
+    let (naive, greedy) = compare_meshing_modes("generated terrain", &blocks);
+    assert!(greedy.len() < naive.len());
+}
@@ -0,0 +1,59 @@
+use nalgebra as na;
+
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    chunk::{Chunk, BLOCK_COUNT, Y_SIZE},
+    histogram::{histogram_chunk_count, BlockHistogram, DEFAULT_HISTOGRAM_CHUNKS},
+    terrain::TerrainGenerator,
+};
+
+#[test]
+fn test_histogram_counts_blocks_per_height() {
+    let generator = TerrainGenerator::new(42);
+    let histogram = BlockHistogram::generate(&generator, 3);
+    assert_eq!(histogram.chunk_count, 3);
+
+    let total: usize = BLOCK_REGISTRY
+        .iter()
+        .map(|definition| histogram.total(definition.id))
+        .sum();
+    assert_eq!(total, 3 * BLOCK_COUNT);
+
+    // The same chunks counted by hand
+    let chunks: Vec<_> = [(-1, -1), (0, -1), (-1, 0)]
+        .map(|(x, z)| generator.generate(na::Vector2::new(x, z)))
+        .into();
+    for y in [0, 10, 64, 100, Y_SIZE - 1] {
+        for block in [generator.air, generator.stone, generator.water] {
+            let expected: usize = chunks
+                .iter()
+                .map(|blocks| {
+                    (0..16)
+                        .flat_map(|x| (0..16).map(move |z| Chunk::block_index(x, y, z)))
+                        .filter(|&index| blocks[index] == block)
+                        .count()
+                })
+                .sum();
+            assert_eq!(histogram.count(y, block), expected, "{block} at {y}");
+        }
+    }
+
+    let table = histogram.to_string();
+    assert!(table.contains("stone") && table.contains("coal_ore"));
+    assert!(table.lines().last().unwrap().starts_with("total"));
+}
+
+#[test]
+fn test_histogram_argument() {
+    let args = |args: &[&str]| histogram_chunk_count(args.iter().map(|arg| arg.to_string()));
+    assert_eq!(args(&["mycraft"]), None);
+    assert_eq!(
+        args(&["mycraft", "--block-histogram"]),
+        Some(DEFAULT_HISTOGRAM_CHUNKS)
+    );
+    assert_eq!(args(&["mycraft", "--block-histogram", "9"]), Some(9));
+    assert_eq!(
+        args(&["mycraft", "--block-histogram", "--biome-colors"]),
+        Some(DEFAULT_HISTOGRAM_CHUNKS)
+    );
+}
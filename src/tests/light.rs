@@ -0,0 +1,186 @@
+use nalgebra as na;
+
+use crate::{
+    block_storage::BlockArray,
+    chunk::{
+        Chunk, ChunkNeighborhood, MeshingMode, UnloadedNeighbors, BLOCK_COUNT, X_SIZE, Z_SIZE,
+    },
+    light::{LightArray, LightChannel, LightRegion, MAX_LIGHT_LEVEL},
+    terrain::TERRAIN_GENERATOR,
+    tests::common::{alone, filled, id, isolated, light_alone},
+};
+
+fn set(blocks: &mut BlockArray, position: [i32; 3], name: &str) {
+    let [x, y, z] = position.map(|component| component as usize);
+    blocks.set(Chunk::block_index(x, y, z), id(name));
+}
+
+fn level(light: &LightArray, channel: LightChannel, position: [i32; 3]) -> u8 {
+    let [x, y, z] = position.map(|component| component as usize);
+    channel.level(light[Chunk::block_index(x, y, z)])
+}
+
+#[test]
+fn test_skylight_fills_columns_and_spreads_under_roofs() {
+    let mut blocks = filled("air");
+    for x in 0..8 {
+        for z in 0..Z_SIZE as i32 {
+            set(&mut blocks, [x, 100, z], "stone");
+        }
+    }
+    let light = light_alone(&blocks);
+
+    assert_eq!(
+        level(&light, LightChannel::Sky, [4, 101, 4]),
+        MAX_LIGHT_LEVEL
+    );
+    assert_eq!(level(&light, LightChannel::Sky, [4, 100, 4]), 0);
+    assert_eq!(
+        level(&light, LightChannel::Sky, [12, 3, 4]),
+        MAX_LIGHT_LEVEL
+    );
+    // Under the roof the light comes in sideways from the open half
+    for x in 0..8 {
+        assert_eq!(
+            level(&light, LightChannel::Sky, [x, 50, 9]),
+            MAX_LIGHT_LEVEL - (8 - x) as u8
+        );
+    }
+    assert_eq!(level(&light, LightChannel::Block, [4, 50, 4]), 0);
+}
+
+#[test]
+fn test_block_light_spreads_from_emitters() {
+    let mut blocks = filled("stone");
+    for x in 0..X_SIZE as i32 {
+        for y in 90..110 {
+            for z in 0..Z_SIZE as i32 {
+                set(&mut blocks, [x, y, z], "air");
+            }
+        }
+    }
+    // Keep the skylight out of the cave
+    for x in 0..X_SIZE as i32 {
+        for z in 0..Z_SIZE as i32 {
+            set(&mut blocks, [x, 110, z], "stone");
+        }
+    }
+    set(&mut blocks, [8, 100, 8], "lamp");
+    let light = light_alone(&blocks);
+
+    assert_eq!(level(&light, LightChannel::Block, [8, 100, 8]), 15);
+    assert_eq!(level(&light, LightChannel::Block, [8, 100, 12]), 11);
+    assert_eq!(level(&light, LightChannel::Block, [11, 102, 8]), 10);
+    assert_eq!(level(&light, LightChannel::Block, [8, 111, 8]), 0);
+    assert_eq!(level(&light, LightChannel::Sky, [8, 101, 8]), 0);
+}
+
+#[test]
+fn test_incremental_updates_match_relighting() {
+    let mut blocks = Box::new(TERRAIN_GENERATOR.generate(na::Vector2::new(3, -2)));
+    let mut light = light_alone(&blocks);
+
+    let surface = |blocks: &BlockArray, x: i32, z: i32| {
+        (0..256)
+            .rev()
+            .find(|&y| blocks[Chunk::block_index(x as usize, y as usize, z as usize)] != id("air"))
+            .unwrap()
+    };
+    let top = surface(&blocks, 5, 5);
+    let edits = [
+        // Roof over a column, then open it again
+        ([5, top + 4, 5], "stone"),
+        ([6, top + 4, 5], "stone"),
+        ([5, top + 4, 5], "air"),
+        // Dig down and light the shaft
+        ([10, surface(&blocks, 10, 10), 10], "air"),
+        ([10, surface(&blocks, 10, 10) - 1, 10], "air"),
+        ([10, surface(&blocks, 10, 10) - 2, 10], "lamp"),
+        ([11, top + 8, 10], "lamp"),
+        ([10, surface(&blocks, 10, 10) - 2, 10], "air"),
+        ([11, top + 8, 10], "stone"),
+        ([0, 255, 0], "stone"),
+        ([0, 255, 0], "air"),
+    ];
+    for (position, name) in edits {
+        set(&mut blocks, position, name);
+        alone(&blocks, &mut light).update_block(position).unwrap();
+
+        let relit = light_alone(&blocks);
+        assert!(
+            light.iter().zip(relit.iter()).all(|(a, b)| a == b),
+            "light differs from relighting after placing {name} at {position:?}"
+        );
+    }
+}
+
+#[test]
+fn test_light_crosses_chunk_borders() {
+    let mut first = filled("air");
+    let second = filled("air");
+    set(&mut first, [X_SIZE as i32 - 1, 100, 4], "lamp");
+
+    // Lighting the chunks in either order gives the same light levels
+    let mut orders = Vec::new();
+    for first_lit_first in [true, false] {
+        let mut first_light = Box::new([0; BLOCK_COUNT]);
+        let mut second_light = Box::new([0; BLOCK_COUNT]);
+        for step in 0..2 {
+            if (step == 0) == first_lit_first {
+                let mut chunks: [Option<(&BlockArray, &mut LightArray)>; 9] = Default::default();
+                chunks[0] = Some((&first, &mut first_light));
+                if step == 1 {
+                    chunks[2] = Some((&second, &mut second_light));
+                }
+                LightRegion::new(chunks).light_chunk().unwrap();
+            } else {
+                let mut chunks: [Option<(&BlockArray, &mut LightArray)>; 9] = Default::default();
+                chunks[0] = Some((&second, &mut second_light));
+                if step == 1 {
+                    chunks[1] = Some((&first, &mut first_light));
+                }
+                LightRegion::new(chunks).light_chunk().unwrap();
+            }
+        }
+        assert_eq!(level(&second_light, LightChannel::Block, [0, 100, 4]), 14);
+        assert_eq!(level(&second_light, LightChannel::Block, [5, 100, 4]), 9);
+        orders.push((first_light, second_light));
+    }
+    assert!(orders[0] == orders[1]);
+}
+
+#[test]
+fn test_light_shades_faces() {
+    let mut blocks = filled("air");
+    set(&mut blocks, [8, 1, 8], "stone");
+    let top_brightness = |blocks: &BlockArray| {
+        let light = light_alone(blocks);
+        let neighborhood = ChunkNeighborhood {
+            light: [Some(&light), None, None, None, None, None, None, None, None],
+            ..isolated(blocks, UnloadedNeighbors::Hide)
+        };
+        let faces = Chunk::build_faces(neighborhood, MeshingMode::Naive)
+            .unwrap()
+            .opaque;
+        let top = faces
+            .iter()
+            .find(|face| face[0].normal == [0.0, 1.0, 0.0] && face[0].position[1] == 2.0)
+            .unwrap();
+        top[0].bitangent[0]
+    };
+
+    assert_eq!(top_brightness(&blocks), 1.0);
+
+    for x in 0..X_SIZE as i32 {
+        for z in 0..Z_SIZE as i32 {
+            set(&mut blocks, [x, 5, z], "stone");
+        }
+    }
+    let dark = top_brightness(&blocks);
+    assert!(dark < 0.1);
+
+    set(&mut blocks, [8, 3, 10], "lamp");
+    let lamp_lit = top_brightness(&blocks);
+    assert!(lamp_lit > dark);
+    assert!(lamp_lit < 1.0);
+}
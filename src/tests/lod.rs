@@ -0,0 +1,182 @@
+use nalgebra as na;
+
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    block_storage::{BlockArray, ALL_SECTIONS},
+    chunk::{
+        Chunk, ChunkFaces, ChunkNeighborhood, Lod, MeshingMode, UnloadedNeighbors, LOD_DISTANCES,
+        X_SIZE, Y_SIZE, Z_SIZE,
+    },
+    cube::{Face, FACE_TEXTURE_OFFSET},
+    terrain::TERRAIN_GENERATOR,
+    tests::common::{isolated, neighborhood},
+};
+
+fn build(neighborhood: ChunkNeighborhood, lod: Lod) -> ChunkFaces {
+    let sections =
+        Chunk::build_sections(neighborhood, ALL_SECTIONS, MeshingMode::Naive, lod).unwrap();
+    ChunkFaces::concat(sections.iter().map(|(_, faces)| faces))
+}
+
+/// Stone up to and including `height`, air above
+fn ground(height: usize) -> BlockArray {
+    let mut blocks = BlockArray::filled(BLOCK_REGISTRY.id_of("air").unwrap());
+    let stone = BLOCK_REGISTRY.id_of("stone").unwrap();
+    for y in 0..=height {
+        for x in 0..X_SIZE {
+            for z in 0..Z_SIZE {
+                blocks.set(Chunk::block_index(x, y, z), stone);
+            }
+        }
+    }
+    blocks
+}
+
+fn highest_vertex(faces: &[Face]) -> f32 {
+    faces
+        .iter()
+        .flatten()
+        .map(|vertex| vertex.position[1])
+        .fold(f32::MIN, f32::max)
+}
+
+#[test]
+fn test_lod_for_distance() {
+    assert_eq!(Lod::for_distance(0), Lod::Full);
+    assert_eq!(Lod::for_distance(LOD_DISTANCES[0]), Lod::Full);
+    assert_eq!(Lod::for_distance(LOD_DISTANCES[0] + 1), Lod::Half);
+    assert_eq!(Lod::for_distance(LOD_DISTANCES[1] + 1), Lod::Quarter);
+    assert_eq!(Lod::for_distance(LOD_DISTANCES[2]), Lod::Quarter);
+    assert_eq!(Lod::for_distance(LOD_DISTANCES[2] + 1), Lod::Eighth);
+    assert_eq!(Lod::for_distance(u32::MAX), Lod::Eighth);
+}
+
+#[test]
+fn test_coarse_levels_have_fewer_faces() {
+    let blocks = ground(63);
+    let stone = ground(Y_SIZE - 1);
+    let surrounded = neighborhood(&blocks, Some(&stone), UnloadedNeighbors::Show);
+
+    let mut previous = build(surrounded, Lod::Full).opaque.len();
+    assert_eq!(previous, X_SIZE * Z_SIZE);
+    for lod in [Lod::Half, Lod::Quarter, Lod::Eighth] {
+        let faces = build(surrounded, lod).opaque;
+        let cells = X_SIZE / lod.scale() * Z_SIZE / lod.scale();
+        assert_eq!(faces.len(), cells, "{lod:?}");
+        assert!(faces.len() < previous);
+        assert_eq!(highest_vertex(&faces), 64.0);
+        previous = faces.len();
+    }
+}
+
+#[test]
+fn test_coarse_cells_cover_their_blocks() {
+    let mut blocks = BlockArray::filled(BLOCK_REGISTRY.id_of("air").unwrap());
+    let stone = BLOCK_REGISTRY.id_of("stone").unwrap();
+    blocks.set(Chunk::block_index(5, 70, 6), stone);
+
+    // A single block fills its whole cell, so coarse terrain never ends up
+    // lower than the blocks it stands for
+    let faces = build(
+        neighborhood(&blocks, None, UnloadedNeighbors::Show),
+        Lod::Quarter,
+    )
+    .opaque;
+    assert_eq!(faces.len(), 6);
+    let corners = faces.iter().flatten().map(|vertex| vertex.position);
+    for axis in 0..3 {
+        let low = corners
+            .clone()
+            .map(|position| position[axis])
+            .fold(f32::MAX, f32::min);
+        let high = corners
+            .clone()
+            .map(|position| position[axis])
+            .fold(f32::MIN, f32::max);
+        assert_eq!(high - low, 4.0);
+        assert_eq!(low, [4.0, 68.0, 4.0][axis]);
+    }
+}
+
+#[test]
+fn test_coarse_chunk_sides_close_the_seam() {
+    let blocks = ground(63);
+    let air = BlockArray::filled(BLOCK_REGISTRY.id_of("air").unwrap());
+
+    // Next to the same terrain only the top is drawn
+    let same_height = neighborhood(&blocks, Some(&blocks), UnloadedNeighbors::Show);
+    let faces = build(same_height, Lod::Half).opaque;
+    assert_eq!(faces.len(), X_SIZE / 2 * Z_SIZE / 2);
+
+    // Lower neighbors show the sides down to their own surface
+    let lower = ground(59);
+    let faces = build(
+        neighborhood(&blocks, Some(&lower), UnloadedNeighbors::Show),
+        Lod::Half,
+    )
+    .opaque;
+    let side_faces = faces
+        .iter()
+        .filter(|face| face[0].normal[1] == 0.0)
+        .collect::<Vec<_>>();
+    assert_eq!(side_faces.len(), 4 * X_SIZE / 2 * 2);
+    assert!(side_faces
+        .iter()
+        .flat_map(|face| face.iter())
+        .all(|vertex| vertex.position[1] >= 60.0));
+
+    // Faces looking out of the world into empty neighbors are all drawn
+    let faces = build(
+        neighborhood(&blocks, Some(&air), UnloadedNeighbors::Show),
+        Lod::Half,
+    )
+    .opaque;
+    assert_eq!(faces.len(), X_SIZE / 2 * Z_SIZE / 2 + 4 * X_SIZE / 2 * 32);
+}
+
+#[test]
+fn test_coarse_faces_sample_a_single_tile() {
+    let blocks = TERRAIN_GENERATOR.generate(na::Vector2::new(0, 0));
+    for lod in [Lod::Half, Lod::Quarter, Lod::Eighth] {
+        let faces = build(isolated(&blocks, UnloadedNeighbors::Show), lod);
+        for face in faces.opaque.iter().chain(&faces.transparent) {
+            // Every corner points at the same tile, on the atlas grid
+            let tile = [face[0].tangent[0], face[0].tangent[1]];
+            for component in tile {
+                let column = component / FACE_TEXTURE_OFFSET;
+                assert_eq!(column, column.round(), "{face:?}");
+                assert!((0.0..1.0).contains(&component), "{face:?}");
+            }
+            assert!(face.iter().all(|vertex| vertex.tangent[..2] == tile[..]));
+
+            // Points across the face, where the shader samples the atlas at
+            // `tile + fract(tex_coords) * FACE_TEXTURE_OFFSET`, stay in the tile
+            for a in [0.01, 0.3, 0.5, 0.99] {
+                for b in [0.01, 0.3, 0.5, 0.99] {
+                    let lerp = |from: [f32; 2], to: [f32; 2], t: f32| {
+                        [0, 1].map(|axis| from[axis] + (to[axis] - from[axis]) * t)
+                    };
+                    let tex_coords = lerp(
+                        lerp(face[0].tex_coords, face[1].tex_coords, a),
+                        lerp(face[3].tex_coords, face[2].tex_coords, a),
+                        b,
+                    );
+                    for axis in 0..2 {
+                        assert!(tex_coords[axis] >= 0.0, "{face:?}");
+                        let sample = tile[axis] + tex_coords[axis].fract() * FACE_TEXTURE_OFFSET;
+                        assert!(
+                            sample >= tile[axis] && sample < tile[axis] + FACE_TEXTURE_OFFSET,
+                            "{lod:?} face samples {sample} outside of its tile"
+                        );
+                    }
+                }
+            }
+
+            // The tile repeats once per block of the cell
+            let scale = lod.scale() as f32;
+            for vertex in face {
+                assert!(vertex.tex_coords.iter().all(|&t| t == 0.0 || t == scale));
+            }
+        }
+    }
+}
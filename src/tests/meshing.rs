@@ -0,0 +1,130 @@
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    chunk::{Chunk, ChunkMesh, ChunkNeighborhood, MeshingMode, UnloadedNeighbors, X_SIZE, Z_SIZE},
+    tests::common::{filled, neighborhood},
+};
+
+const NORMALS: [[f32; 3]; 6] = [
+    [-1.0, 0.0, 0.0],
+    [1.0, 0.0, 0.0],
+    [0.0, -1.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, -1.0],
+    [0.0, 0.0, 1.0],
+];
+
+fn build(neighborhood: ChunkNeighborhood, meshing_mode: MeshingMode) -> ChunkMesh {
+    let mesh = Chunk::build_mesh(neighborhood, meshing_mode)
+        .unwrap()
+        .opaque;
+    assert_eq!(mesh.vertices.len() % 4, 0);
+    assert_eq!(mesh.indices.len(), mesh.vertices.len() / 4 * 6);
+    assert!(mesh
+        .indices
+        .iter()
+        .all(|&index| (index as usize) < mesh.vertices.len()));
+    mesh
+}
+
+/// Number of faces facing along `normal`
+fn face_count(mesh: &ChunkMesh, normal: [f32; 3]) -> usize {
+    mesh.vertices
+        .chunks_exact(4)
+        .filter(|face| face[0].normal == normal)
+        .count()
+}
+
+#[test]
+fn test_single_block_faces() {
+    let mut blocks = filled("air");
+    blocks.set(
+        Chunk::block_index(8, 1, 8),
+        BLOCK_REGISTRY.id_of("stone").unwrap(),
+    );
+    let air = filled("air");
+
+    for meshing_mode in [MeshingMode::Naive, MeshingMode::Greedy] {
+        let mesh = build(
+            neighborhood(&blocks, Some(&air), UnloadedNeighbors::Hide),
+            meshing_mode,
+        );
+        assert_eq!(mesh.vertices.len(), 6 * 4, "{meshing_mode:?}");
+        for normal in NORMALS {
+            assert_eq!(face_count(&mesh, normal), 1, "{meshing_mode:?} {normal:?}");
+        }
+    }
+
+    let stone = filled("stone");
+    let hidden = build(
+        neighborhood(&stone, Some(&stone), UnloadedNeighbors::Hide),
+        MeshingMode::Naive,
+    );
+    assert_eq!(face_count(&hidden, [1.0, 0.0, 0.0]), 0);
+    assert_eq!(face_count(&hidden, [0.0, 0.0, -1.0]), 0);
+}
+
+#[test]
+fn test_slab_faces() {
+    let mut blocks = filled("air");
+    let dirt = BLOCK_REGISTRY.id_of("dirt").unwrap();
+    for x in 0..X_SIZE {
+        for z in 0..Z_SIZE {
+            blocks.set(Chunk::block_index(x, 1, z), dirt);
+        }
+    }
+    let air = filled("air");
+
+    let naive = build(
+        neighborhood(&blocks, Some(&air), UnloadedNeighbors::Hide),
+        MeshingMode::Naive,
+    );
+    assert_eq!(face_count(&naive, [0.0, 1.0, 0.0]), X_SIZE * Z_SIZE);
+    assert_eq!(face_count(&naive, [0.0, -1.0, 0.0]), X_SIZE * Z_SIZE);
+    assert_eq!(face_count(&naive, [-1.0, 0.0, 0.0]), Z_SIZE);
+    assert_eq!(face_count(&naive, [0.0, 0.0, 1.0]), X_SIZE);
+
+    let greedy = build(
+        neighborhood(&blocks, Some(&air), UnloadedNeighbors::Hide),
+        MeshingMode::Greedy,
+    );
+    for normal in NORMALS {
+        assert_eq!(face_count(&greedy, normal), 1, "{normal:?}");
+    }
+}
+
+#[test]
+fn test_chunk_border_faces() {
+    let mut blocks = filled("air");
+    let stone_id = BLOCK_REGISTRY.id_of("stone").unwrap();
+    blocks.set(Chunk::block_index(0, 0, 8), stone_id);
+    blocks.set(Chunk::block_index(8, 0, Z_SIZE - 1), stone_id);
+    let air = filled("air");
+    let stone = filled("stone");
+
+    // Open air on every side shows the border faces
+    let open = build(
+        neighborhood(&blocks, Some(&air), UnloadedNeighbors::Hide),
+        MeshingMode::Naive,
+    );
+    assert_eq!(face_count(&open, [-1.0, 0.0, 0.0]), 2);
+    assert_eq!(face_count(&open, [0.0, 0.0, 1.0]), 2);
+
+    // Solid or unloaded neighbors hide them
+    for neighbors in [Some(&*stone), None] {
+        let closed = build(
+            neighborhood(&blocks, neighbors, UnloadedNeighbors::Hide),
+            MeshingMode::Naive,
+        );
+        assert_eq!(face_count(&closed, [-1.0, 0.0, 0.0]), 1);
+        assert_eq!(face_count(&closed, [0.0, 0.0, 1.0]), 1);
+        assert_eq!(face_count(&closed, [1.0, 0.0, 0.0]), 2);
+        assert_eq!(face_count(&closed, [0.0, 0.0, -1.0]), 2);
+    }
+
+    // Only the neighbor on the block's side matters
+    let mut one_side = neighborhood(&blocks, Some(&air), UnloadedNeighbors::Hide);
+    one_side.negative_x = Some(&stone);
+    let mesh = build(one_side, MeshingMode::Naive);
+    assert_eq!(face_count(&mesh, [-1.0, 0.0, 0.0]), 1);
+    assert_eq!(face_count(&mesh, [0.0, 0.0, 1.0]), 2);
+}
@@ -0,0 +1,81 @@
+use nalgebra as na;
+
+use crate::{
+    block_registry::BLOCK_REGISTRY,
+    chunk::{BLOCK_COUNT, HORIZONTAL_SLICE_SIZE},
+    ores::{load_ores, parse_ores, OreError, OreVeins, ORE_DEFINITIONS_PATH},
+    terrain::TerrainGenerator,
+    tests::common::id,
+};
+
+#[test]
+fn test_ore_definitions() {
+    let ores = load_ores(ORE_DEFINITIONS_PATH, &BLOCK_REGISTRY).unwrap();
+    for name in ["coal_ore", "iron_ore", "gold_ore", "diamond_ore"] {
+        let block = id(name);
+        assert!(
+            ores.iter().any(|ore| ore.block == block),
+            "{name} is missing"
+        );
+    }
+
+    let ore = |definition: &str| {
+        parse_ores(
+            &format!("[(block: {definition}, vein_size: 4, veins_per_chunk: 1.0)]"),
+            &BLOCK_REGISTRY,
+        )
+    };
+    assert!(ore(r#""coal_ore", min_height: 0, max_height: 255"#).is_ok());
+    assert!(matches!(
+        ore(r#""gold", min_height: 0, max_height: 10"#),
+        Err(OreError::UnknownBlock(_))
+    ));
+    assert!(matches!(
+        ore(r#""coal_ore", min_height: 20, max_height: 10"#),
+        Err(OreError::InvalidHeights(_))
+    ));
+    assert!(matches!(
+        ore(r#""coal_ore", min_height: 0, max_height: 256"#),
+        Err(OreError::InvalidHeights(_))
+    ));
+    assert!(matches!(ore("coal_ore"), Err(OreError::Parse(_))));
+}
+
+#[test]
+fn test_ores_only_replace_stone_in_their_range() {
+    let generator = TerrainGenerator::new(42);
+    let mut bare = TerrainGenerator::new(42);
+    bare.ores = OreVeins::new(0, vec![]);
+
+    let mut ore_counts = vec![0; generator.ores.ores.len()];
+    for position in [na::Vector2::new(0, 0), na::Vector2::new(9, -31)] {
+        let blocks = generator.generate(position);
+        assert!(blocks == generator.generate(position));
+        let bare_blocks = bare.generate(position);
+        for index in 0..BLOCK_COUNT {
+            if blocks[index] == bare_blocks[index] {
+                continue;
+            }
+            assert_eq!(bare_blocks[index], generator.stone);
+            let (ore_index, ore) = generator
+                .ores
+                .ores
+                .iter()
+                .enumerate()
+                .find(|(_, ore)| ore.block == blocks[index])
+                .expect("stone was replaced by something else than ore");
+            let y = index / HORIZONTAL_SLICE_SIZE;
+            assert!((ore.min_height..=ore.max_height).contains(&y));
+            ore_counts[ore_index] += 1;
+        }
+    }
+
+    // Common ores show up in every chunk
+    let iron = id("iron_ore");
+    let coal = id("coal_ore");
+    for (ore, count) in generator.ores.ores.iter().zip(ore_counts) {
+        if ore.block == iron || ore.block == coal {
+            assert!(count > 10, "only {count} blocks of ore {}", ore.block);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use nalgebra as na;
+
+use crate::{
+    aabb::Aabb,
+    player::{
+        MovementInput, MovementMode, Player, MAX_STEPS_PER_FRAME, PHYSICS_TIMESTEP, PLAYER_WIDTH,
+    },
+};
+
+/// Flat ground with its top at `y = 10`, and a wall along `x = 5` from the
+/// ground up to `y = 12`
+fn world(block: na::Vector3<i32>) -> bool {
+    block.y < 10 || block.x == 5 && block.y < 12
+}
+
+fn walking(position: na::Vector3<f32>) -> Player {
+    let mut player = Player::new(position);
+    player.set_mode(MovementMode::Walk);
+    player
+}
+
+fn run(player: &mut Player, input: MovementInput, steps: usize) {
+    for _ in 0..steps {
+        player.step(input, world);
+    }
+}
+
+#[test]
+fn test_player_falls_and_lands() {
+    let mut player = walking(na::Vector3::new(0.5, 20.0, 0.5));
+    run(&mut player, MovementInput::default(), 120);
+    assert_eq!(player.position.y, 10.0);
+    assert!(player.on_ground);
+    assert_eq!(player.velocity.y, 0.0);
+
+    // Standing still keeps the player on the ground
+    run(&mut player, MovementInput::default(), 10);
+    assert_eq!(player.position.y, 10.0);
+    assert!(player.on_ground);
+}
+
+#[test]
+fn test_jump_clears_one_block() {
+    let mut player = walking(na::Vector3::new(0.5, 10.0, 0.5));
+    run(&mut player, MovementInput::default(), 1);
+    assert!(player.on_ground);
+
+    let jump = MovementInput {
+        jump: true,
+        ..Default::default()
+    };
+    player.step(jump, world);
+    assert!(!player.on_ground);
+
+    // Holding jump in the air doesn't jump again
+    let rising = player.velocity.y;
+    player.step(jump, world);
+    assert!(player.velocity.y < rising);
+
+    let mut highest = player.position.y;
+    for _ in 0..60 {
+        player.step(MovementInput::default(), world);
+        highest = highest.max(player.position.y);
+    }
+    assert!(highest > 11.0 && highest < 11.5, "jumped to {highest}");
+    assert_eq!(player.position.y, 10.0);
+}
+
+#[test]
+fn test_walls_stop_the_player() {
+    let mut player = walking(na::Vector3::new(2.5, 10.0, 0.5));
+    let east = MovementInput {
+        direction: na::Vector2::new(1.0, 0.0),
+        jump: false,
+    };
+    run(&mut player, east, 120);
+    let against_wall = 5.0 - PLAYER_WIDTH / 2.0;
+    assert!((player.position.x - against_wall).abs() < 1e-4);
+    assert_eq!(player.position.y, 10.0);
+
+    // Walking along the wall still works
+    let north_east = MovementInput {
+        direction: na::Vector2::new(1.0, 1.0),
+        jump: false,
+    };
+    run(&mut player, north_east, 30);
+    assert!((player.position.x - against_wall).abs() < 1e-4);
+    assert!(player.position.z > 2.0);
+}
+
+#[test]
+fn test_fast_movement_does_not_tunnel() {
+    let aabb = Aabb {
+        min: na::Vector3::new(0.2, 30.0, 0.2),
+        max: na::Vector3::new(0.8, 31.8, 0.8),
+    };
+    let thin_floor = |block: na::Vector3<i32>| block.y == 3;
+    assert_eq!(aabb.sweep(1, -100.0, thin_floor), -26.0);
+    assert_eq!(aabb.sweep(1, -20.0, thin_floor), -20.0);
+    assert!((aabb.sweep(0, 50.0, |block| block.x == 9) - 8.2).abs() < 1e-5);
+
+    // A box that already overlaps a block can move out of it
+    let inside = |block: na::Vector3<i32>| block == na::Vector3::new(0, 30, 0);
+    assert_eq!(aabb.sweep(1, 2.0, inside), 2.0);
+}
+
+#[test]
+fn test_physics_steps_do_not_depend_on_frame_rate() {
+    let input = MovementInput {
+        direction: na::Vector2::new(0.0, 1.0),
+        jump: true,
+    };
+    let start = na::Vector3::new(0.5, 14.0, 0.5);
+
+    let frames = |frame_time: Duration, count: u32| {
+        let mut player = walking(start);
+        for _ in 0..count {
+            player.update(frame_time, input, world);
+        }
+        player.position
+    };
+    let steps = |count: u32| frames(PHYSICS_TIMESTEP, count);
+
+    // A single slow frame runs as many steps as many fast ones
+    assert_eq!(frames(PHYSICS_TIMESTEP * 5, 6), steps(30));
+    assert_eq!(frames(PHYSICS_TIMESTEP / 2, 60), steps(30));
+    assert_eq!(frames(PHYSICS_TIMESTEP * 3 / 2, 20), steps(30));
+
+    // The time left over is how far the camera is into the next step
+    let mut player = walking(start);
+    let alpha = player.update(PHYSICS_TIMESTEP * 5 / 2, input, world);
+    assert!((alpha - 0.5).abs() < 1e-3);
+
+    // Long hitches are dropped instead of caught up on
+    assert_eq!(
+        frames(PHYSICS_TIMESTEP * 100, 1),
+        steps(MAX_STEPS_PER_FRAME)
+    );
+}
+
+#[test]
+fn test_player_box_blocks_placement() {
+    let player = Player::new(na::Vector3::new(0.5, 10.0, 0.5));
+    let intersects = |x, y, z| {
+        let block = Aabb::block(na::Vector3::new(x, y, z));
+        player.shared_aabb().get().intersects(&block)
+    };
+    assert!(intersects(0, 10, 0));
+    assert!(intersects(0, 11, 0));
+    // Blocks the player only touches can be placed
+    assert!(!intersects(0, 9, 0));
+    assert!(!intersects(0, 12, 0));
+    assert!(!intersects(1, 10, 0));
+    assert!(!intersects(-1, 11, 0));
+}
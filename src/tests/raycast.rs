@@ -0,0 +1,85 @@
+use nalgebra as na;
+
+use crate::{camera::look_direction, chunk::Chunk, raycast::raycast};
+
+#[test]
+fn test_raycast_hits_first_solid_block() {
+    let wall = |block: na::Vector3<i32>| block.x == 5;
+
+    let hit = raycast(
+        na::Vector3::new(0.5, 10.5, 0.5),
+        na::Vector3::new(1.0, 0.0, 0.0),
+        8.0,
+        wall,
+    )
+    .unwrap();
+    assert_eq!(hit.block, na::Vector3::new(5, 10, 0));
+    assert_eq!(hit.normal, na::Vector3::new(-1, 0, 0));
+    assert!((hit.distance - 4.5).abs() < 1e-5);
+
+    // Out of reach
+    assert!(raycast(
+        na::Vector3::new(0.5, 10.5, 0.5),
+        na::Vector3::new(1.0, 0.0, 0.0),
+        4.0,
+        wall
+    )
+    .is_none());
+
+    // Looking away from the wall
+    assert!(raycast(
+        na::Vector3::new(0.5, 10.5, 0.5),
+        na::Vector3::new(-1.0, 0.0, 0.0),
+        100.0,
+        wall
+    )
+    .is_none());
+}
+
+#[test]
+fn test_raycast_visits_every_crossed_block() {
+    // A diagonal ray has to step through a side neighbor between two
+    // diagonal blocks, so it can't slip through corners
+    let mut visited = Vec::new();
+    raycast(
+        na::Vector3::new(0.5, 0.5, 0.5),
+        na::Vector3::new(1.0, 0.0, 0.7),
+        3.0,
+        |block| {
+            visited.push(block);
+            false
+        },
+    );
+    for pair in visited.windows(2) {
+        let step = pair[1] - pair[0];
+        assert_eq!(step.abs().sum(), 1, "{:?} -> {:?}", pair[0], pair[1]);
+    }
+    assert_eq!(visited[0], na::Vector3::new(0, 0, 0));
+}
+
+#[test]
+fn test_raycast_negative_coordinates() {
+    let floor = |block: na::Vector3<i32>| block.y <= -3;
+    let hit = raycast(
+        na::Vector3::new(-7.2, 0.5, -0.1),
+        na::Vector3::new(0.0, -1.0, 0.0),
+        10.0,
+        floor,
+    )
+    .unwrap();
+    assert_eq!(hit.block, na::Vector3::new(-8, -3, -1));
+    assert_eq!(hit.normal, na::Vector3::new(0, 1, 0));
+
+    let (chunk, local) = Chunk::split_world_position(na::Vector3::new(-1, 64, -17)).unwrap();
+    assert_eq!(chunk, na::Vector2::new(-1, -2));
+    assert_eq!(local, (15, 64, 15));
+    assert!(Chunk::split_world_position(na::Vector3::new(0, -1, 0)).is_none());
+}
+
+#[test]
+fn test_look_direction() {
+    let forward = look_direction(0.0, 0.0);
+    assert!((forward - na::Vector3::new(1.0, 0.0, 0.0)).norm() < 1e-6);
+    let up = look_direction(0.0, std::f32::consts::FRAC_PI_2);
+    assert!((up - na::Vector3::new(0.0, 1.0, 0.0)).norm() < 1e-6);
+}
@@ -0,0 +1,257 @@
+use std::{fs, io::Write, path::PathBuf};
+
+use flate2::{write::ZlibEncoder, Compression};
+use nalgebra as na;
+
+use crate::{
+    block_storage::BlockArray,
+    chunk::{Chunk, BLOCK_COUNT},
+    chunk_loader::load_chunk,
+    region::{RegionStorage, RegionWriter, SavedChunk, REGION_SIZE},
+    terrain::TERRAIN_GENERATOR,
+    tick::SavedTick,
+};
+
+fn test_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("mycraft_{name}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    directory
+}
+
+// Block arrays are kept on the heap, a few of them on the stack overflow the
+// test thread
+fn generate(position: na::Vector2<i32>) -> Vec<u16> {
+    TERRAIN_GENERATOR.generate(position).to_vec()
+}
+
+fn save(storage: &RegionStorage, position: na::Vector2<i32>, blocks: &[u16]) {
+    let chunk = SavedChunk {
+        blocks: BlockArray::from_ids(blocks),
+        ticks: vec![],
+    };
+    storage.save_chunks(&[(position, chunk)]).unwrap();
+}
+
+fn load(storage: &RegionStorage, position: na::Vector2<i32>) -> Option<Vec<u16>> {
+    storage
+        .load_chunk(position)
+        .unwrap()
+        .map(|chunk| chunk.blocks.to_vec())
+}
+
+#[test]
+fn test_region_round_trip() {
+    let directory = test_directory("region_round_trip");
+    let storage = RegionStorage::new(&directory);
+
+    // Two chunks sharing a region, one in a negative region
+    let positions = [
+        na::Vector2::new(0, 0),
+        na::Vector2::new(REGION_SIZE - 1, 3),
+        na::Vector2::new(-1, -REGION_SIZE - 2),
+    ];
+    assert_eq!(
+        RegionStorage::region_position(positions[0]),
+        RegionStorage::region_position(positions[1])
+    );
+    assert_eq!(
+        RegionStorage::region_position(positions[2]),
+        na::Vector2::new(-1, -2)
+    );
+
+    for position in positions {
+        assert!(load(&storage, position).is_none());
+    }
+
+    let mut edited = generate(positions[1]);
+    edited[Chunk::block_index(4, 100, 9)] = 3;
+    edited[BLOCK_COUNT - 1] = 2;
+
+    save(&storage, positions[0], &generate(positions[0]));
+    save(&storage, positions[1], &edited);
+    save(&storage, positions[2], &generate(positions[2]));
+
+    assert!(load(&storage, positions[0]) == Some(generate(positions[0])));
+    assert!(load(&storage, positions[1]) == Some(edited));
+    assert!(load(&storage, positions[2]) == Some(generate(positions[2])));
+    assert!(load(&storage, na::Vector2::new(1, 0)).is_none());
+
+    // Saving again replaces the chunk without touching its neighbors
+    save(&storage, positions[1], &generate(positions[1]));
+    assert!(load(&storage, positions[1]) == Some(generate(positions[1])));
+    assert!(load(&storage, positions[0]) == Some(generate(positions[0])));
+
+    // Region files are compressed well below the raw block data
+    let region_file = fs::read_dir(&directory).unwrap().next().unwrap().unwrap();
+    assert!(region_file.metadata().unwrap().len() < (BLOCK_COUNT * 2) as u64);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_region_rejects_invalid_files() {
+    let directory = test_directory("region_invalid");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("r.0.0.region"), b"not a region").unwrap();
+
+    let storage = RegionStorage::new(&directory);
+    assert!(storage.load_chunk(na::Vector2::new(0, 0)).is_err());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_version_1_regions_load_without_ticks() {
+    let directory = test_directory("region_version_1");
+    fs::create_dir_all(&directory).unwrap();
+    let position = na::Vector2::new(0, 1);
+    let blocks = generate(position);
+
+    // Version 1 stored only the blocks of each chunk
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for block in &blocks {
+        encoder.write_all(&block.to_le_bytes()).unwrap();
+    }
+    let data = encoder.finish().unwrap();
+    let header_size = 8 + (REGION_SIZE * REGION_SIZE) as usize * 8;
+    let mut file = vec![0; header_size];
+    file[..4].copy_from_slice(b"MCRG");
+    file[4..8].copy_from_slice(&1u32.to_le_bytes());
+    file[16..20].copy_from_slice(&(header_size as u32).to_le_bytes());
+    file[20..24].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(&data);
+    fs::write(directory.join("r.0.0.region"), file).unwrap();
+
+    let storage = RegionStorage::new(&directory);
+    let chunk = storage.load_chunk(position).unwrap().unwrap();
+    assert!(chunk.blocks.to_vec() == blocks);
+    assert!(chunk.ticks.is_empty());
+
+    // Saving a neighbor rewrites the region in the current version, which
+    // still reads the old chunk
+    let neighbor = na::Vector2::new(0, 2);
+    save(&storage, neighbor, &generate(neighbor));
+    let chunk = storage.load_chunk(position).unwrap().unwrap();
+    assert!(chunk.blocks.to_vec() == blocks);
+    assert!(chunk.ticks.is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_unreadable_chunks_are_generated() {
+    let directory = test_directory("region_unreadable");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("r.0.0.region"), b"not a region").unwrap();
+    let writer = RegionWriter::new(RegionStorage::new(&directory));
+
+    let position = na::Vector2::new(1, 2);
+    let (chunk, unreadable) = load_chunk(&writer, position);
+    assert!(unreadable);
+    assert!(chunk.blocks.to_vec() == generate(position));
+    // Chunks that were never saved aren't unreadable
+    let (_, unreadable) = load_chunk(&writer, na::Vector2::new(-1, 2));
+    assert!(!unreadable);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_region_keeps_scheduled_ticks() {
+    let directory = test_directory("region_ticks");
+    let storage = RegionStorage::new(&directory);
+    let position = na::Vector2::new(2, -3);
+    let ticks = [
+        SavedTick {
+            block_index: 0,
+            delay: 5,
+        },
+        SavedTick {
+            block_index: BLOCK_COUNT - 1,
+            delay: 30,
+        },
+    ];
+
+    let chunk = SavedChunk {
+        blocks: BlockArray::from_ids(&generate(position)),
+        ticks: ticks.to_vec(),
+    };
+    storage.save_chunks(&[(position, chunk)]).unwrap();
+    let chunk = storage.load_chunk(position).unwrap().unwrap();
+    assert_eq!(chunk.ticks, ticks);
+    assert!(chunk.blocks.to_vec() == generate(position));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_region_writer_saves_in_the_background() {
+    let directory = test_directory("region_writer");
+    let writer = RegionWriter::new(RegionStorage::new(&directory));
+    let positions = [
+        na::Vector2::new(0, 0),
+        na::Vector2::new(1, 0),
+        na::Vector2::new(-1, 0),
+    ];
+    let saved = |position| SavedChunk {
+        blocks: BlockArray::from_ids(&generate(position)),
+        ticks: vec![SavedTick {
+            block_index: 7,
+            delay: (position.x + 2) as u64,
+        }],
+    };
+    writer.save_chunks(
+        positions
+            .map(|position| (position, saved(position)))
+            .to_vec(),
+    );
+
+    // Queued chunks load before they are written
+    let mut edited = generate(positions[1]);
+    edited[Chunk::block_index(2, 80, 2)] = 3;
+    writer.save_chunks(vec![(
+        positions[1],
+        SavedChunk {
+            blocks: BlockArray::from_ids(&edited),
+            ticks: vec![],
+        },
+    )]);
+    let loaded = writer.load_chunk(positions[1]).unwrap().unwrap();
+    assert!(loaded.blocks.to_vec() == edited);
+
+    assert!(writer.flush().is_empty());
+    let storage = RegionStorage::new(&directory);
+    assert!(load(&storage, positions[0]) == Some(generate(positions[0])));
+    assert!(load(&storage, positions[1]) == Some(edited));
+    assert!(load(&storage, positions[2]) == Some(generate(positions[2])));
+    let chunk = storage.load_chunk(positions[2]).unwrap().unwrap();
+    assert_eq!(chunk.ticks, saved(positions[2]).ticks);
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_region_writer_retries_failed_saves() {
+    let directory = test_directory("region_writer_retry");
+    // A file in place of the world directory makes every save fail
+    fs::write(&directory, b"in the way").unwrap();
+    let writer = RegionWriter::new(RegionStorage::new(&directory));
+    let position = na::Vector2::new(3, 4);
+    writer.save_chunks(vec![(
+        position,
+        SavedChunk {
+            blocks: BlockArray::from_ids(&generate(position)),
+            ticks: vec![],
+        },
+    )]);
+    assert_eq!(writer.flush(), vec![position]);
+
+    // The chunk stays queued and is written once the directory can be made
+    fs::remove_file(&directory).unwrap();
+    assert!(writer.flush().is_empty());
+    let storage = RegionStorage::new(&directory);
+    assert!(load(&storage, position) == Some(generate(position)));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,108 @@
+use nalgebra as na;
+
+use crate::{
+    block_storage::{sections_around, BlockArray, ALL_SECTIONS, SECTION_COUNT, SECTION_HEIGHT},
+    chunk::{Chunk, Lod, MeshingMode, UnloadedNeighbors, X_SIZE, Y_SIZE, Z_SIZE},
+    terrain::TERRAIN_GENERATOR,
+    tests::common::{id, neighborhood},
+};
+
+#[test]
+fn test_remeshing_sections_matches_full_remesh() {
+    let mut blocks = Box::new(TERRAIN_GENERATOR.generate(na::Vector2::new(5, 1)));
+    let stone = id("stone");
+    let air = id("air");
+    let edits = [
+        ([3, 2 * SECTION_HEIGHT, 4], stone),
+        ([3, 2 * SECTION_HEIGHT - 1, 4], air),
+        ([8, 40, 8], air),
+        ([X_SIZE - 1, Y_SIZE - 1, 0], stone),
+    ];
+
+    for meshing_mode in [MeshingMode::Naive, MeshingMode::Greedy] {
+        let mut chunk = Chunk::new(na::Vector2::new(5, 1), (0, 0));
+        chunk.replace_sections(
+            Chunk::build_sections(
+                neighborhood(&blocks, None, UnloadedNeighbors::Hide),
+                ALL_SECTIONS,
+                meshing_mode,
+                Lod::Full,
+            )
+            .unwrap(),
+        );
+
+        for ([x, y, z], block) in edits {
+            blocks.set(Chunk::block_index(x, y, z), block);
+            let sections = sections_around(y);
+            let remeshed = Chunk::build_sections(
+                neighborhood(&blocks, None, UnloadedNeighbors::Hide),
+                sections,
+                meshing_mode,
+                Lod::Full,
+            )
+            .unwrap();
+            assert_eq!(remeshed.len(), sections.count_ones() as usize);
+            chunk.replace_sections(remeshed);
+
+            let full = Chunk::build_mesh(
+                neighborhood(&blocks, None, UnloadedNeighbors::Hide),
+                meshing_mode,
+            )
+            .unwrap();
+            let meshes = chunk.meshes();
+            assert!(
+                bytemuck::cast_slice::<_, u8>(&meshes.opaque.vertices)
+                    == bytemuck::cast_slice::<_, u8>(&full.opaque.vertices)
+                    && meshes.opaque.indices == full.opaque.indices,
+                "{meshing_mode:?} after setting ({x}, {y}, {z})"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_hidden_sections_are_skipped() {
+    let air_id = id("air");
+    let blocks = BlockArray::filled(id("stone"));
+    let stone = BlockArray::filled(id("stone"));
+    let surrounded = neighborhood(&blocks, Some(&stone), UnloadedNeighbors::Show);
+
+    // Only the top of the world can be seen
+    for section in 0..SECTION_COUNT - 1 {
+        let faces =
+            Chunk::build_section_faces(surrounded, section, MeshingMode::Naive, Lod::Full).unwrap();
+        assert!(faces.opaque.is_empty(), "section {section}");
+    }
+    let faces = Chunk::build_faces(surrounded, MeshingMode::Naive).unwrap();
+    assert_eq!(faces.opaque.len(), X_SIZE * Z_SIZE);
+    assert!(faces
+        .opaque
+        .iter()
+        .all(|face| face[0].normal == [0.0, 1.0, 0.0]));
+
+    // Unloaded neighbors that are shown open up the sides of every section
+    let open = neighborhood(&blocks, None, UnloadedNeighbors::Show);
+    for section in 0..SECTION_COUNT {
+        let faces =
+            Chunk::build_section_faces(open, section, MeshingMode::Naive, Lod::Full).unwrap();
+        assert!(faces.opaque.len() >= 4 * X_SIZE * SECTION_HEIGHT);
+    }
+
+    // Sections next to one with a hole in it are meshed
+    let mut holed = stone.clone();
+    holed.set(Chunk::block_index(4, 3 * SECTION_HEIGHT + 2, 4), air_id);
+    let next_to_hole = neighborhood(&holed, Some(&stone), UnloadedNeighbors::Show);
+    for section in 2..=4 {
+        let faces =
+            Chunk::build_section_faces(next_to_hole, section, MeshingMode::Naive, Lod::Full)
+                .unwrap();
+        assert_eq!(faces.opaque.len(), if section == 3 { 6 } else { 0 });
+    }
+
+    let air = BlockArray::filled(air_id);
+    let empty = neighborhood(&air, Some(&stone), UnloadedNeighbors::Show);
+    assert!(Chunk::build_faces(empty, MeshingMode::Greedy)
+        .unwrap()
+        .opaque
+        .is_empty());
+}
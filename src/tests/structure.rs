@@ -0,0 +1,147 @@
+use nalgebra as na;
+
+use crate::{
+    biome::Biome,
+    block_registry::BLOCK_REGISTRY,
+    chunk::{Chunk, X_SIZE, Y_SIZE, Z_SIZE},
+    structure::{
+        place_structure, StructureError, StructureRegistry, STRUCTURE_DEFINITIONS_PATH,
+        STRUCTURE_REGISTRY,
+    },
+    terrain::TerrainGenerator,
+    tests::common::id,
+};
+
+#[test]
+fn test_structure_registry() {
+    let registry = StructureRegistry::load(STRUCTURE_DEFINITIONS_PATH, &BLOCK_REGISTRY).unwrap();
+    let log = id("log");
+    let tree = registry.get("oak_tree").unwrap();
+    assert!(tree
+        .blocks
+        .iter()
+        .any(|block| block.offset == na::Vector3::zeros() && block.block == log));
+    assert!(matches!(
+        registry.get("not a structure"),
+        Err(StructureError::UnknownName(_))
+    ));
+
+    let parse = |source| StructureRegistry::parse(source, &BLOCK_REGISTRY);
+    let pillar = r#"[(name: "pillar", parts: [(from: (0, 0, 0), to: (0, 2, 0), block: "stone")])]"#;
+    assert_eq!(
+        parse(pillar).unwrap().get("pillar").unwrap().blocks.len(),
+        3
+    );
+    assert!(matches!(
+        parse(r#"[(name: "a", parts: []), (name: "a", parts: [])]"#),
+        Err(StructureError::DuplicateName(_))
+    ));
+    assert!(matches!(
+        parse(r#"[(name: "a", parts: [(from: (0, 0, 0), to: (0, 0, 0), block: "gold")])]"#),
+        Err(StructureError::UnknownBlock(_, _))
+    ));
+    assert!(matches!(
+        parse(r#"[(name: "a", parts: [(from: (0, 0, 0), to: (17, 0, 0), block: "stone")])]"#),
+        Err(StructureError::TooLarge(_))
+    ));
+    assert!(matches!(
+        parse("[(name: 1)]"),
+        Err(StructureError::Parse(_))
+    ));
+}
+
+#[test]
+fn test_structures_are_split_by_chunk() {
+    let tree = STRUCTURE_REGISTRY.get("oak_tree").unwrap();
+    let origin = na::Vector3::new(X_SIZE as i32 - 1, 70, 5);
+    let placed: Vec<_> = place_structure(tree, origin).collect();
+    assert_eq!(placed.len(), tree.blocks.len());
+
+    let east = na::Vector2::new(1, 0);
+    let in_east: Vec<_> = placed
+        .iter()
+        .filter(|(chunk, _)| *chunk == east)
+        .map(|(_, block)| block.index)
+        .collect();
+    assert!(in_east.contains(&Chunk::block_index(0, 73, 5)));
+    assert!(in_east.contains(&Chunk::block_index(1, 73, 5)));
+    assert!(placed
+        .iter()
+        .all(|(chunk, _)| *chunk == east || *chunk == na::Vector2::zeros()));
+
+    // Blocks that would be above the world are left out
+    let high = na::Vector3::new(0, Y_SIZE as i32 - 2, 0);
+    assert!(place_structure(tree, high).count() < tree.blocks.len());
+}
+
+/// Forest chunks with a tree trunk in their last column along x
+fn trees_on_east_border(generator: &TerrainGenerator) -> Vec<(na::Vector2<i32>, usize, usize)> {
+    let mut trees = vec![];
+    for x in (-60..60).step_by(6) {
+        for z in (-60..60).step_by(6) {
+            let position = na::Vector2::new(x, z);
+            let world_x = x * X_SIZE as i32 + X_SIZE as i32 - 1;
+            if generator.biome_at(world_x, z * Z_SIZE as i32) != Biome::Forest {
+                continue;
+            }
+            let blocks = generator.generate(position);
+            for border_z in 2..Z_SIZE - 2 {
+                let world_z = position.y * Z_SIZE as i32 + border_z as i32;
+                let height = generator.surface_height(world_x, world_z);
+                if blocks[Chunk::block_index(X_SIZE - 1, height + 1, border_z)] == id("log") {
+                    trees.push((position, border_z, height + 1));
+                }
+            }
+            if trees.len() >= 3 {
+                return trees;
+            }
+        }
+    }
+    trees
+}
+
+#[test]
+fn test_structures_reach_into_neighbors() {
+    let generator = TerrainGenerator::new(42);
+    let trees = trees_on_east_border(&generator);
+    assert!(!trees.is_empty(), "no tree grows on a chunk border");
+
+    for (position, z, base) in trees {
+        // The east chunk gets the leaves even when generated by itself
+        let east = &TerrainGenerator::new(42).generate(position + na::Vector2::new(1, 0));
+        let crown = (base + 3..base + 5)
+            .flat_map(|y| (0..2).map(move |x| east[Chunk::block_index(x, y, z)]))
+            .filter(|&block| block == id("leaves") || block == id("log"))
+            .count();
+        assert!(
+            crown >= 3,
+            "tree at {position:?} z {z} has {crown} leaves across the border"
+        );
+    }
+}
+
+#[test]
+fn test_structures_ignore_generation_order() {
+    let positions: Vec<na::Vector2<i32>> = (0..3)
+        .flat_map(|x| (0..3).map(move |z| na::Vector2::new(x - 20, z + 11)))
+        .collect();
+
+    let forward = TerrainGenerator::new(42);
+    let forward_chunks: Vec<_> = positions.iter().map(|&p| forward.generate(p)).collect();
+    let backward = TerrainGenerator::new(42);
+    let mut backward_chunks: Vec<_> = positions
+        .iter()
+        .rev()
+        .map(|&p| backward.generate(p))
+        .collect();
+    backward_chunks.reverse();
+    assert!(forward_chunks == backward_chunks);
+
+    // Forgetting queued blocks plans them again the same way
+    forward
+        .structures
+        .forget_outside(na::Vector2::new(1000, 1000), 2);
+    for (position, blocks) in positions.iter().zip(&forward_chunks) {
+        assert!(forward.generate(*position) == *blocks);
+    }
+}
@@ -0,0 +1,79 @@
+use nalgebra as na;
+
+use crate::{
+    chunk::{Chunk, X_SIZE, Y_SIZE, Z_SIZE},
+    terrain::{TerrainGenerator, FILLER_DEPTH, SEA_LEVEL},
+    tests::common::id,
+};
+
+#[test]
+fn test_terrain_is_deterministic() {
+    let positions = [
+        na::Vector2::new(0, 0),
+        na::Vector2::new(-3, 7),
+        na::Vector2::new(1024, -512),
+    ];
+
+    for position in positions {
+        let first = TerrainGenerator::new(42).generate(position);
+        let second = TerrainGenerator::new(42).generate(position);
+        assert!(first == second, "chunk {position:?} differs between runs");
+    }
+
+    let origin = na::Vector2::new(0, 0);
+    assert!(
+        TerrainGenerator::new(42).generate(origin) != TerrainGenerator::new(43).generate(origin),
+        "different seeds produced the same chunk"
+    );
+}
+
+#[test]
+fn test_terrain_layers() {
+    let generator = TerrainGenerator::new(42);
+    let structure_blocks = ["log", "leaves", "cactus", "stone", "glass"].map(id);
+
+    for position in [na::Vector2::new(2, -5), na::Vector2::new(-70, 31)] {
+        let blocks = generator.generate(position);
+        for x in 0..X_SIZE {
+            for z in 0..Z_SIZE {
+                let world_x = position.x * X_SIZE as i32 + x as i32;
+                let world_z = position.y * Z_SIZE as i32 + z as i32;
+                let height = generator.surface_height(world_x, world_z);
+                let biome_blocks = generator.biome_blocks(generator.biome_at(world_x, world_z));
+
+                for y in 0..Y_SIZE {
+                    let block = blocks[Chunk::block_index(x, y, z)];
+                    if y > height.max(SEA_LEVEL) {
+                        assert!(
+                            block == generator.air || structure_blocks.contains(&block),
+                            "({world_x}, {y}, {world_z}) above the surface is {block}"
+                        );
+                        continue;
+                    }
+                    let carved = generator.caves.carves([world_x, y as i32, world_z], height);
+                    let expected = if y > height {
+                        generator.water
+                    } else if carved {
+                        generator.air
+                    } else if y == height && height <= SEA_LEVEL {
+                        biome_blocks.shore
+                    } else if y == height {
+                        biome_blocks.surface
+                    } else if y + FILLER_DEPTH >= height {
+                        biome_blocks.filler
+                    } else {
+                        generator.stone
+                    };
+                    // Stone may have been replaced by ore veins
+                    let ore = generator.ores.ores.iter().find(|ore| ore.block == block);
+                    if expected == generator.stone
+                        && ore.is_some_and(|ore| (ore.min_height..=ore.max_height).contains(&y))
+                    {
+                        continue;
+                    }
+                    assert_eq!(block, expected, "({world_x}, {y}, {world_z})");
+                }
+            }
+        }
+    }
+}
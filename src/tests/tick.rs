@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use nalgebra as na;
+
+use crate::{
+    block_storage::BlockArray,
+    chunk::{Chunk, X_SIZE, Z_SIZE},
+    fluid::FLUID_REGISTRY,
+    tests::common::{floor, id, place},
+    tick::{
+        SavedTick, TickScheduler, TickThread, TickWorld, MAX_CACTUS_HEIGHT, MAX_TICKS_PER_UPDATE,
+        TICK_LENGTH,
+    },
+};
+
+fn block_at(blocks: &BlockArray, position: [i32; 3]) -> u16 {
+    blocks.block_at(na::Vector3::from(position)).unwrap()
+}
+
+fn count(blocks: &BlockArray, name: &str) -> usize {
+    blocks.iter().filter(|&block| block == id(name)).count()
+}
+
+fn run(blocks: &mut BlockArray, ticks: &mut TickScheduler, tick_count: u64) {
+    for _ in 0..tick_count {
+        ticks.tick(blocks);
+    }
+}
+
+#[test]
+fn test_grass_spreads_onto_uncovered_dirt() {
+    let mut blocks = floor("dirt");
+    let mut ticks = TickScheduler::new(1);
+    blocks.replace_block(na::Vector3::new(8, 1, 8), id("grass"));
+    blocks.replace_block(na::Vector3::new(9, 2, 8), id("stone"));
+    run(&mut blocks, &mut ticks, 20_000);
+
+    assert!(count(&blocks, "grass") > 10);
+    assert_eq!(block_at(&blocks, [9, 1, 8]), id("dirt"));
+}
+
+#[test]
+fn test_covered_grass_turns_into_dirt() {
+    let mut blocks = floor("grass");
+    let mut ticks = TickScheduler::new(2);
+    blocks.replace_block(na::Vector3::new(8, 2, 8), id("stone"));
+    run(&mut blocks, &mut ticks, 20_000);
+
+    assert_eq!(block_at(&blocks, [8, 1, 8]), id("dirt"));
+    assert_eq!(count(&blocks, "grass"), X_SIZE * Z_SIZE - 1);
+}
+
+#[test]
+fn test_cactus_grows_to_its_height() {
+    let mut blocks = floor("sand");
+    let mut ticks = TickScheduler::new(3);
+    blocks.replace_block(na::Vector3::new(8, 2, 8), id("cactus"));
+    run(&mut blocks, &mut ticks, 40_000);
+
+    let top = 1 + MAX_CACTUS_HEIGHT as i32;
+    for y in 2..=top {
+        assert_eq!(block_at(&blocks, [8, y, 8]), id("cactus"));
+    }
+    assert_eq!(block_at(&blocks, [8, top + 1, 8]), id("air"));
+    assert_eq!(count(&blocks, "cactus"), MAX_CACTUS_HEIGHT);
+}
+
+#[test]
+fn test_sand_falls_into_air_and_water() {
+    let mut blocks = floor("stone");
+    let mut ticks = TickScheduler::new(4);
+    place(&mut blocks, &mut ticks, [4, 10, 4], "sand");
+    ticks.settle(&mut *blocks, 1000);
+    assert_eq!(block_at(&blocks, [4, 2, 4]), id("sand"));
+    assert_eq!(count(&blocks, "sand"), 1);
+
+    // Sand replaces the water source it lands on, so the water dries up
+    place(&mut blocks, &mut ticks, [10, 2, 10], "water");
+    ticks.settle(&mut *blocks, 1000);
+    place(&mut blocks, &mut ticks, [10, 6, 10], "sand");
+    assert!(ticks.settle(&mut *blocks, 1000) < 1000);
+    assert_eq!(block_at(&blocks, [10, 2, 10]), id("sand"));
+    assert_eq!(block_at(&blocks, [10, 3, 10]), id("air"));
+    assert!(blocks
+        .iter()
+        .all(|block| FLUID_REGISTRY.get(block).is_none()));
+}
+
+#[test]
+fn test_scheduled_ticks_survive_unloading() {
+    let mut blocks = floor("stone");
+    let mut ticks = TickScheduler::new(5);
+    place(&mut blocks, &mut ticks, [8, 2, 8], "lava");
+    run(&mut blocks, &mut ticks, 10);
+
+    let chunk = na::Vector2::zeros();
+    let saved = ticks.unload_chunk(chunk);
+    assert_eq!(
+        saved,
+        [SavedTick {
+            block_index: Chunk::block_index(8, 2, 8),
+            delay: 20,
+        }]
+    );
+    assert!(ticks.saved_ticks(chunk).is_empty());
+    run(&mut blocks, &mut ticks, 30);
+    assert_eq!(count(&blocks, "lava_3"), 0);
+
+    // Loading the chunk again keeps the time the tick had left
+    let mut ticks = TickScheduler::new(5);
+    ticks.load_chunk(chunk, &saved);
+    run(&mut blocks, &mut ticks, 19);
+    assert_eq!(count(&blocks, "lava_3"), 0);
+    run(&mut blocks, &mut ticks, 1);
+    assert_eq!(count(&blocks, "lava_3"), 4);
+}
+
+#[test]
+fn test_saved_ticks_are_relative_to_their_chunk() {
+    let chunk = na::Vector2::new(-1, 2);
+    let mut ticks = TickScheduler::new(6);
+    ticks.schedule_in(na::Vector3::new(-3, 40, 37), 7);
+    let saved = ticks.unload_chunk(chunk);
+    assert_eq!(
+        saved,
+        [SavedTick {
+            block_index: Chunk::block_index(13, 40, 5),
+            delay: 7,
+        }]
+    );
+
+    let mut loaded = TickScheduler::new(6);
+    loaded.load_chunk(chunk, &saved);
+    assert_eq!(loaded.saved_ticks(chunk), saved);
+    assert!(loaded.saved_ticks(na::Vector2::zeros()).is_empty());
+}
+
+#[test]
+fn test_ticks_run_at_a_fixed_rate() {
+    let mut blocks = floor("stone");
+    let mut ticks = TickScheduler::new(7);
+
+    ticks.update(TICK_LENGTH * 2 + TICK_LENGTH / 2, &mut *blocks);
+    assert_eq!(ticks.tick, 2);
+    ticks.update(TICK_LENGTH / 2, &mut *blocks);
+    assert_eq!(ticks.tick, 3);
+    // A long pause runs the ticks it missed a few at a time
+    ticks.update(TICK_LENGTH * 10, &mut *blocks);
+    assert_eq!(ticks.tick, 3 + MAX_TICKS_PER_UPDATE as u64);
+    assert_eq!(ticks.until_next_tick(), Duration::ZERO);
+    ticks.update(Duration::ZERO, &mut *blocks);
+    ticks.update(Duration::ZERO, &mut *blocks);
+    assert_eq!(ticks.tick, 13);
+    assert_eq!(ticks.until_next_tick(), TICK_LENGTH);
+}
+
+#[test]
+fn test_tick_thread_keeps_the_ticks_of_chunks() {
+    let ticks = TickThread::spawn(8);
+    // Far away from the chunks other tests load, so the tick stays scheduled
+    let chunk = na::Vector2::new(1000, -1000);
+    let block_index = Chunk::block_index(1, 2, 3);
+    ticks.load_chunk(
+        chunk,
+        vec![SavedTick {
+            block_index,
+            delay: 1000,
+        }],
+    );
+
+    let saved = ticks.saved_ticks(chunk);
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].block_index, block_index);
+    assert!(saved[0].delay <= 1000);
+    let unloaded = ticks.unload_chunk(chunk);
+    assert_eq!(unloaded.len(), 1);
+    assert!(ticks.saved_ticks(chunk).is_empty());
+}
@@ -0,0 +1,118 @@
+use nalgebra as na;
+
+use crate::{
+    block_storage::BlockArray,
+    chunk::{Chunk, ChunkFaces, ChunkMesh, MeshingMode, UnloadedNeighbors},
+    cube::Face,
+    tests::common::{filled, id, isolated},
+};
+
+fn set(blocks: &mut BlockArray, x: usize, y: usize, z: usize, name: &str) {
+    blocks.set(Chunk::block_index(x, y, z), id(name));
+}
+
+fn build(blocks: &BlockArray, meshing_mode: MeshingMode) -> ChunkFaces {
+    let neighborhood = isolated(blocks, UnloadedNeighbors::Hide);
+    Chunk::build_faces(neighborhood, meshing_mode).unwrap()
+}
+
+fn faces_along(faces: &[Face], normal: [f32; 3]) -> usize {
+    faces.iter().filter(|face| face[0].normal == normal).count()
+}
+
+#[test]
+fn test_transparent_faces_are_culled_between_identical_blocks() {
+    // A 2x1x1 pool of water on a stone floor
+    let mut blocks = filled("air");
+    set(&mut blocks, 4, 1, 4, "stone");
+    set(&mut blocks, 5, 1, 4, "stone");
+    set(&mut blocks, 4, 2, 4, "water");
+    set(&mut blocks, 5, 2, 4, "water");
+
+    let faces = build(&blocks, MeshingMode::Naive);
+    // Both water blocks lose the face between them, and neither draws a
+    // bottom face onto the stone
+    assert_eq!(faces.transparent.len(), 2 * 6 - 2 - 2);
+    assert_eq!(faces_along(&faces.transparent, [1.0, 0.0, 0.0]), 1);
+    assert_eq!(faces_along(&faces.transparent, [-1.0, 0.0, 0.0]), 1);
+    // The stone is still seen through the water
+    assert_eq!(faces_along(&faces.opaque, [0.0, 1.0, 0.0]), 2);
+
+    let greedy = build(&blocks, MeshingMode::Greedy);
+    assert_eq!(faces_along(&greedy.transparent, [0.0, 1.0, 0.0]), 1);
+    assert_eq!(faces_along(&greedy.opaque, [0.0, 1.0, 0.0]), 1);
+}
+
+#[test]
+fn test_different_transparent_blocks_show_each_other() {
+    let mut blocks = filled("air");
+    set(&mut blocks, 4, 1, 4, "glass");
+    set(&mut blocks, 5, 1, 4, "water");
+
+    let faces = build(&blocks, MeshingMode::Naive);
+    assert_eq!(faces.transparent.len(), 12);
+    assert!(faces.opaque.is_empty());
+}
+
+#[test]
+fn test_cutout_blocks_have_their_own_layer() {
+    let mut blocks = filled("air");
+    set(&mut blocks, 4, 1, 4, "leaves");
+    set(&mut blocks, 5, 1, 4, "leaves");
+
+    // Leaves keep the faces between them, the holes show them
+    let faces = build(&blocks, MeshingMode::Naive);
+    assert_eq!(faces.cutout.len(), 12);
+    assert!(faces.opaque.is_empty());
+    assert!(faces.transparent.is_empty());
+}
+
+#[test]
+fn test_transparent_faces_sort_back_to_front() {
+    let mut blocks = filled("air");
+    for x in 0..3 {
+        set(&mut blocks, 4 + 2 * x, 1, 4, "glass");
+    }
+    let faces = build(&blocks, MeshingMode::Naive);
+    let mut mesh = ChunkMesh::from_faces(&faces.transparent);
+    let eye = na::Vector3::new(0.0, 1.5, 4.5);
+    mesh.sort_back_to_front(eye);
+
+    let distances: Vec<f32> = mesh
+        .vertices
+        .chunks_exact(4)
+        .map(|face| {
+            let center = face
+                .iter()
+                .map(|vertex| na::Vector3::from(vertex.position))
+                .sum::<na::Vector3<f32>>()
+                / 4.0;
+            (center - eye).norm()
+        })
+        .collect();
+    assert!(distances.windows(2).all(|pair| pair[0] >= pair[1]));
+
+    // Every face's indices still point at its own vertices
+    for (face, indices) in mesh.indices.chunks_exact(6).enumerate() {
+        assert!(indices.iter().all(|&index| index as usize / 4 == face));
+    }
+}
+
+#[test]
+fn test_sorting_reports_whether_faces_moved() {
+    let mut blocks = filled("air");
+    for x in 0..3 {
+        set(&mut blocks, 4 + 2 * x, 1, 4, "glass");
+    }
+    let faces = build(&blocks, MeshingMode::Naive);
+    let mut mesh = ChunkMesh::from_faces(&faces.transparent);
+
+    assert!(mesh.sort_back_to_front(na::Vector3::new(0.0, 1.5, 4.5)));
+    let sorted = mesh.clone();
+    // Moving along the row keeps the faces in order, so nothing is uploaded
+    assert!(!mesh.sort_back_to_front(na::Vector3::new(0.0, 1.5, 4.5)));
+    assert!(!mesh.sort_back_to_front(na::Vector3::new(-1.0, 1.5, 4.5)));
+    assert_eq!(mesh.indices, sorted.indices);
+    // Crossing to the other end of the row reverses it
+    assert!(mesh.sort_back_to_front(na::Vector3::new(20.0, 1.5, 4.5)));
+}
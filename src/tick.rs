@@ -0,0 +1,598 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use nalgebra as na;
+
+use crate::{
+    block_registry::{BlockRegistry, BlockRegistryError, BLOCK_REGISTRY},
+    block_storage::{BlockArray, SECTION_COUNT, SECTION_HEIGHT},
+    chunk::{Chunk, HORIZONTAL_SLICE_SIZE, X_SIZE, Y_SIZE, Z_SIZE},
+    chunk_loader::{loaded_block, LOADED_CHUNKS},
+    fluid::{update_fluid, FLUIDS, FLUID_REGISTRY},
+    noise::{hash_3d, ChunkRandom},
+};
+
+/// Rate the world is ticked at, independent of the frame rate
+pub const TICKS_PER_SECOND: u64 = 20;
+pub const TICK_LENGTH: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND);
+/// Ticks run in a single update at most, so the [TickThread] keeps answering
+/// the chunk loader while it catches up. The rest are carried over to the
+/// next update.
+pub const MAX_TICKS_PER_UPDATE: usize = 4;
+/// Blocks picked for a random tick in every section of a loaded chunk each
+/// tick
+pub const RANDOM_TICKS_PER_SECTION: usize = 3;
+/// Ticks before a block in [FALLING_BLOCKS] drops by one block
+pub const FALL_DELAY: u64 = 2;
+/// Blocks that fall when there is air or a fluid below them
+pub const FALLING_BLOCKS: [&str; 1] = ["sand"];
+/// Blocks a random tick of grass tries to spread to
+pub const GRASS_SPREAD_ATTEMPTS: usize = 4;
+/// Height a cactus grows to on its own
+pub const MAX_CACTUS_HEIGHT: usize = 3;
+
+lazy_static! {
+    pub static ref TICK_BLOCKS: TickBlocks = TickBlocks::new(&BLOCK_REGISTRY)
+        .unwrap_or_else(|err| panic!("Ticks need their blocks: {err}"));
+}
+
+/// Ids of the blocks that do something when they are ticked
+#[derive(Debug)]
+pub struct TickBlocks {
+    pub air: u16,
+    pub grass: u16,
+    pub dirt: u16,
+    pub cactus: u16,
+    pub falling: HashSet<u16>,
+}
+
+impl TickBlocks {
+    pub fn new(blocks: &BlockRegistry) -> Result<Self, BlockRegistryError> {
+        Ok(TickBlocks {
+            air: blocks.id_of("air")?,
+            grass: blocks.id_of("grass")?,
+            dirt: blocks.id_of("dirt")?,
+            cactus: blocks.id_of("cactus")?,
+            falling: FALLING_BLOCKS
+                .iter()
+                .map(|name| blocks.id_of(name))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Blocks the world ticks run on
+pub trait TickWorld {
+    /// Block at a position, `None` if it is outside of the ticked blocks.
+    /// Nothing moves there.
+    fn block_at(&self, position: na::Vector3<i32>) -> Option<u16>;
+    fn replace_block(&mut self, position: na::Vector3<i32>, block: u16);
+    /// Chunks that get random ticks
+    fn loaded_chunks(&self) -> Vec<na::Vector2<i32>>;
+}
+
+/// A single chunk at the origin, with positions relative to it
+impl TickWorld for BlockArray {
+    fn block_at(&self, position: na::Vector3<i32>) -> Option<u16> {
+        let inside = (0..X_SIZE as i32).contains(&position.x)
+            && (0..Y_SIZE as i32).contains(&position.y)
+            && (0..Z_SIZE as i32).contains(&position.z);
+        inside.then(|| {
+            self[Chunk::block_index(
+                position.x as usize,
+                position.y as usize,
+                position.z as usize,
+            )]
+        })
+    }
+
+    fn replace_block(&mut self, position: na::Vector3<i32>, block: u16) {
+        self.set(
+            Chunk::block_index(
+                position.x as usize,
+                position.y as usize,
+                position.z as usize,
+            ),
+            block,
+        );
+    }
+
+    fn loaded_chunks(&self) -> Vec<na::Vector2<i32>> {
+        vec![na::Vector2::zeros()]
+    }
+}
+
+/// A scheduled tick of a chunk that is saved with its blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedTick {
+    /// Position of the ticked block, see [Chunk::block_index]
+    pub block_index: usize,
+    /// Ticks left until it is due
+    pub delay: u64,
+}
+
+const NEIGHBOR_OFFSETS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+pub fn offset(position: na::Vector3<i32>, offset: [i32; 3]) -> na::Vector3<i32> {
+    position + na::Vector3::from(offset)
+}
+
+fn chunk_of(position: [i32; 3]) -> na::Vector2<i32> {
+    na::Vector2::new(
+        position[0].div_euclid(X_SIZE as i32),
+        position[2].div_euclid(Z_SIZE as i32),
+    )
+}
+
+/// Scheduled ticks of a single chunk
+#[derive(Debug, Default)]
+struct ChunkTicks {
+    /// Due tick and world position of every scheduled block, soonest first
+    queue: BTreeSet<(u64, [i32; 3])>,
+    scheduled: HashSet<[i32; 3]>,
+}
+
+/// Changes the world at a fixed rate of [TICKS_PER_SECOND], apart from how
+/// fast frames are drawn. The game runs it on a [TickThread].
+///
+/// Every tick has two kinds of block updates:
+/// - Random ticks pick [RANDOM_TICKS_PER_SECTION] blocks in every section of
+///   every loaded chunk. Grass turns into dirt under opaque blocks and
+///   spreads onto dirt up to a block around and three blocks below it that
+///   isn't covered, and cacti grow up to
+///   [MAX_CACTUS_HEIGHT].
+/// - Scheduled ticks update the blocks they were scheduled for once they are
+///   due. Changing a block schedules the blocks at and next to it that react
+///   to changes: fluids flow, see [update_fluid], and [FALLING_BLOCKS] drop
+///   into air and fluids below them.
+///
+/// Scheduled ticks are kept per chunk, so they can be saved with the chunk
+/// when it unloads and picked up again when it loads.
+#[derive(Debug, Default)]
+pub struct TickScheduler {
+    /// Number of ticks run so far
+    pub tick: u64,
+    seed: u64,
+    /// Time that hasn't been ticked yet
+    accumulator: Duration,
+    /// Chunks with scheduled ticks
+    chunks: HashMap<na::Vector2<i32>, ChunkTicks>,
+    /// Blocks replaced by the current tick
+    changed: Vec<na::Vector3<i32>>,
+}
+
+impl TickScheduler {
+    pub fn new(seed: u64) -> Self {
+        TickScheduler {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    /// Updates the block at `position` `delay` ticks from now. Blocks that
+    /// are already scheduled aren't scheduled twice.
+    pub fn schedule_in(&mut self, position: na::Vector3<i32>, delay: u64) {
+        let key: [i32; 3] = position.into();
+        let ticks = self.chunks.entry(chunk_of(key)).or_default();
+        if ticks.scheduled.insert(key) {
+            ticks.queue.insert((self.tick + delay, key));
+        }
+    }
+
+    /// Schedules the block at `position` if it reacts to changes around it
+    pub fn schedule(&mut self, world: &impl TickWorld, position: na::Vector3<i32>) {
+        let Some(block) = world.block_at(position) else {
+            return;
+        };
+        if let Some(fluid) = FLUID_REGISTRY.get(block) {
+            self.schedule_in(position, FLUIDS[fluid.fluid].tick_delay);
+        } else if TICK_BLOCKS.falling.contains(&block) {
+            self.schedule_in(position, FALL_DELAY);
+        }
+    }
+
+    /// Schedules the blocks at and around a block that changed
+    pub fn block_changed(&mut self, world: &impl TickWorld, position: na::Vector3<i32>) {
+        self.schedule(world, position);
+        for neighbor in NEIGHBOR_OFFSETS {
+            self.schedule(world, offset(position, neighbor));
+        }
+    }
+
+    /// Replaces a block during a tick and schedules the blocks around it
+    pub fn replace_block(
+        &mut self,
+        world: &mut impl TickWorld,
+        position: na::Vector3<i32>,
+        block: u16,
+    ) {
+        world.replace_block(position, block);
+        self.changed.push(position);
+        self.block_changed(world, position);
+    }
+
+    /// Runs as many ticks as fit into the time since the last update, up to
+    /// [MAX_TICKS_PER_UPDATE]. Ticks that didn't fit are run by the next
+    /// updates. Returns the positions of the blocks that changed.
+    pub fn update(
+        &mut self,
+        elapsed: Duration,
+        world: &mut impl TickWorld,
+    ) -> Vec<na::Vector3<i32>> {
+        self.accumulator += elapsed;
+        let mut changed = vec![];
+        for _ in 0..MAX_TICKS_PER_UPDATE {
+            if self.accumulator < TICK_LENGTH {
+                break;
+            }
+            changed.extend(self.tick(world));
+            self.accumulator -= TICK_LENGTH;
+        }
+        changed
+    }
+
+    /// Time until the next tick is due, zero while ticks are behind
+    pub fn until_next_tick(&self) -> Duration {
+        TICK_LENGTH.saturating_sub(self.accumulator)
+    }
+
+    /// Advances by one tick, runs the random ticks of every loaded chunk and
+    /// then every scheduled tick that is due. Returns the positions of the
+    /// blocks that changed.
+    pub fn tick(&mut self, world: &mut impl TickWorld) -> Vec<na::Vector3<i32>> {
+        self.tick += 1;
+        for chunk in world.loaded_chunks() {
+            self.random_ticks(world, chunk);
+        }
+
+        let mut due = vec![];
+        for ticks in self.chunks.values_mut() {
+            while let Some(&(tick, position)) = ticks.queue.first() {
+                if tick > self.tick {
+                    break;
+                }
+                ticks.queue.pop_first();
+                ticks.scheduled.remove(&position);
+                due.push((tick, position));
+            }
+        }
+        self.chunks.retain(|_, ticks| !ticks.queue.is_empty());
+        due.sort_unstable();
+        for (_, position) in due {
+            self.scheduled_tick(world, position.into());
+        }
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Runs ticks until nothing is scheduled anymore, at most `max_ticks`.
+    /// Returns the number of ticks run.
+    #[allow(unused)]
+    pub fn settle(&mut self, world: &mut impl TickWorld, max_ticks: u64) -> u64 {
+        let start = self.tick;
+        while !self.chunks.is_empty() && self.tick - start < max_ticks {
+            self.tick(world);
+        }
+        self.tick - start
+    }
+
+    /// Scheduled ticks of a chunk, to save them with its blocks
+    pub fn saved_ticks(&self, chunk: na::Vector2<i32>) -> Vec<SavedTick> {
+        let Some(ticks) = self.chunks.get(&chunk) else {
+            return vec![];
+        };
+        ticks
+            .queue
+            .iter()
+            .map(|&(tick, [x, y, z])| SavedTick {
+                block_index: Chunk::block_index(
+                    x.rem_euclid(X_SIZE as i32) as usize,
+                    y as usize,
+                    z.rem_euclid(Z_SIZE as i32) as usize,
+                ),
+                delay: tick.saturating_sub(self.tick),
+            })
+            .collect()
+    }
+
+    /// Forgets the scheduled ticks of a chunk that unloads, returning them
+    /// to be saved
+    pub fn unload_chunk(&mut self, chunk: na::Vector2<i32>) -> Vec<SavedTick> {
+        let ticks = self.saved_ticks(chunk);
+        self.chunks.remove(&chunk);
+        ticks
+    }
+
+    /// Schedules the saved ticks of a chunk that loaded
+    pub fn load_chunk(&mut self, chunk: na::Vector2<i32>, ticks: &[SavedTick]) {
+        for tick in ticks {
+            let position = na::Vector3::new(
+                chunk.x * X_SIZE as i32
+                    + (tick.block_index % HORIZONTAL_SLICE_SIZE / X_SIZE) as i32,
+                (tick.block_index / HORIZONTAL_SLICE_SIZE) as i32,
+                chunk.y * Z_SIZE as i32 + (tick.block_index % X_SIZE) as i32,
+            );
+            self.schedule_in(position, tick.delay);
+        }
+    }
+
+    fn random_ticks(&mut self, world: &mut impl TickWorld, chunk: na::Vector2<i32>) {
+        let mut random = ChunkRandom(hash_3d(self.seed, chunk.x, self.tick as i32, chunk.y));
+        for section in 0..SECTION_COUNT {
+            for _ in 0..RANDOM_TICKS_PER_SECTION {
+                let position = na::Vector3::new(
+                    chunk.x * X_SIZE as i32 + random.below(X_SIZE) as i32,
+                    (section * SECTION_HEIGHT + random.below(SECTION_HEIGHT)) as i32,
+                    chunk.y * Z_SIZE as i32 + random.below(Z_SIZE) as i32,
+                );
+                self.random_tick(world, position, &mut random);
+            }
+        }
+    }
+
+    fn random_tick(
+        &mut self,
+        world: &mut impl TickWorld,
+        position: na::Vector3<i32>,
+        random: &mut ChunkRandom,
+    ) {
+        let Some(block) = world.block_at(position) else {
+            return;
+        };
+        let above = offset(position, [0, 1, 0]);
+        if block == TICK_BLOCKS.grass {
+            if !uncovered(world, position) {
+                self.replace_block(world, position, TICK_BLOCKS.dirt);
+                return;
+            }
+            for _ in 0..GRASS_SPREAD_ATTEMPTS {
+                let target = offset(
+                    position,
+                    [
+                        random.below(3) as i32 - 1,
+                        random.below(5) as i32 - 3,
+                        random.below(3) as i32 - 1,
+                    ],
+                );
+                if world.block_at(target) == Some(TICK_BLOCKS.dirt) && uncovered(world, target) {
+                    self.replace_block(world, target, TICK_BLOCKS.grass);
+                }
+            }
+        } else if block == TICK_BLOCKS.cactus && world.block_at(above) == Some(TICK_BLOCKS.air) {
+            let height = (0..MAX_CACTUS_HEIGHT as i32)
+                .take_while(|&depth| {
+                    world.block_at(offset(position, [0, -depth, 0])) == Some(TICK_BLOCKS.cactus)
+                })
+                .count();
+            if height < MAX_CACTUS_HEIGHT {
+                self.replace_block(world, above, TICK_BLOCKS.cactus);
+            }
+        }
+    }
+
+    fn scheduled_tick(&mut self, world: &mut impl TickWorld, position: na::Vector3<i32>) {
+        let Some(block) = world.block_at(position) else {
+            return;
+        };
+        if FLUID_REGISTRY.get(block).is_some() {
+            update_fluid(self, world, position);
+        } else if TICK_BLOCKS.falling.contains(&block) {
+            let below = offset(position, [0, -1, 0]);
+            let falls_into = world.block_at(below).is_some_and(|below| {
+                below == TICK_BLOCKS.air || FLUID_REGISTRY.get(below).is_some()
+            });
+            if falls_into {
+                self.replace_block(world, position, TICK_BLOCKS.air);
+                self.replace_block(world, below, block);
+            }
+        }
+    }
+}
+
+/// Messages from the chunk loader to the [TickThread]
+enum TickMessage {
+    /// Schedule the blocks at and around a block changed outside of ticks
+    BlockChanged(na::Vector3<i32>),
+    /// Schedule the saved ticks of a chunk that loaded
+    LoadChunk(na::Vector2<i32>, Vec<SavedTick>),
+    /// Answer with the scheduled ticks of a chunk, forgetting them if it
+    /// unloads
+    SavedTicks {
+        chunk: na::Vector2<i32>,
+        unload: bool,
+        answer: Sender<Vec<SavedTick>>,
+    },
+    /// The changes of every batch up to this one are in the world
+    Applied(u64),
+}
+
+/// Blocks replaced by the ticks of a single update, each with the block it
+/// ended up as
+#[derive(Debug)]
+pub struct TickChanges {
+    pub batch: u64,
+    pub blocks: Vec<(na::Vector3<i32>, u16)>,
+}
+
+/// Runs a [TickScheduler] on its own thread, so the world ticks at
+/// [TICKS_PER_SECOND] no matter how long frames take.
+///
+/// The thread reads the blocks in [LOADED_CHUNKS] but never writes them.
+/// Blocks replaced by ticks are sent back as [TickChanges], which the chunk
+/// loader applies with
+/// [set_block](crate::chunk_loader::ChunkLoader::set_block) before reporting
+/// them [applied](TickThread::applied). Until then the thread sees its own
+/// changes on top of the loaded blocks.
+pub struct TickThread {
+    sender: Sender<TickMessage>,
+    changes: Receiver<TickChanges>,
+}
+
+impl TickThread {
+    /// Starts ticking, until the [TickThread] is dropped
+    pub fn spawn(seed: u64) -> Self {
+        let (sender, messages) = mpsc::channel();
+        let (changes_sender, changes) = mpsc::channel();
+        thread::Builder::new()
+            .name("ticks".to_string())
+            .spawn(move || run_ticks(TickScheduler::new(seed), messages, changes_sender))
+            .expect("Failed to start the tick thread");
+        TickThread { sender, changes }
+    }
+
+    /// See [TickScheduler::block_changed]
+    pub fn block_changed(&self, position: na::Vector3<i32>) {
+        let _ = self.sender.send(TickMessage::BlockChanged(position));
+    }
+
+    /// See [TickScheduler::load_chunk]
+    pub fn load_chunk(&self, chunk: na::Vector2<i32>, ticks: Vec<SavedTick>) {
+        if !ticks.is_empty() {
+            let _ = self.sender.send(TickMessage::LoadChunk(chunk, ticks));
+        }
+    }
+
+    /// See [TickScheduler::saved_ticks]
+    pub fn saved_ticks(&self, chunk: na::Vector2<i32>) -> Vec<SavedTick> {
+        self.request_ticks(chunk, false)
+    }
+
+    /// See [TickScheduler::unload_chunk]
+    pub fn unload_chunk(&self, chunk: na::Vector2<i32>) -> Vec<SavedTick> {
+        self.request_ticks(chunk, true)
+    }
+
+    fn request_ticks(&self, chunk: na::Vector2<i32>, unload: bool) -> Vec<SavedTick> {
+        let (answer, receiver) = mpsc::channel();
+        let message = TickMessage::SavedTicks {
+            chunk,
+            unload,
+            answer,
+        };
+        if self.sender.send(message).is_err() {
+            return vec![];
+        }
+        receiver.recv().unwrap_or_default()
+    }
+
+    /// Changes of the updates since the last call, oldest first
+    pub fn take_changes(&self) -> Vec<TickChanges> {
+        self.changes.try_iter().collect()
+    }
+
+    /// Lets the thread know the changes of every batch up to `batch` are in
+    /// the world
+    pub fn applied(&self, batch: u64) {
+        let _ = self.sender.send(TickMessage::Applied(batch));
+    }
+}
+
+/// The loaded chunks as the tick thread sees them
+#[derive(Debug, Default)]
+struct PendingWorld {
+    /// Batch the next changes are sent in
+    batch: u64,
+    /// Blocks replaced by ticks that aren't applied to the loaded chunks yet,
+    /// with the batch they were sent in
+    pending: HashMap<na::Vector3<i32>, (u64, u16)>,
+}
+
+impl TickWorld for PendingWorld {
+    fn block_at(&self, position: na::Vector3<i32>) -> Option<u16> {
+        let loaded = loaded_block(position)?;
+        Some(
+            self.pending
+                .get(&position)
+                .map_or(loaded, |&(_, pending)| pending),
+        )
+    }
+
+    fn replace_block(&mut self, position: na::Vector3<i32>, block: u16) {
+        self.pending.insert(position, (self.batch, block));
+    }
+
+    fn loaded_chunks(&self) -> Vec<na::Vector2<i32>> {
+        LOADED_CHUNKS.read().unwrap().keys().copied().collect()
+    }
+}
+
+/// Body of the [TickThread]: answers messages between ticks and sends the
+/// changes of every update, until the thread is dropped
+fn run_ticks(
+    mut scheduler: TickScheduler,
+    messages: Receiver<TickMessage>,
+    changes: Sender<TickChanges>,
+) {
+    let mut world = PendingWorld::default();
+    let mut last_update = Instant::now();
+    loop {
+        match messages.recv_timeout(scheduler.until_next_tick()) {
+            Ok(TickMessage::BlockChanged(position)) => scheduler.block_changed(&world, position),
+            Ok(TickMessage::LoadChunk(chunk, ticks)) => scheduler.load_chunk(chunk, &ticks),
+            Ok(TickMessage::SavedTicks {
+                chunk,
+                unload,
+                answer,
+            }) => {
+                let ticks = if unload {
+                    scheduler.unload_chunk(chunk)
+                } else {
+                    scheduler.saved_ticks(chunk)
+                };
+                let _ = answer.send(ticks);
+            }
+            Ok(TickMessage::Applied(batch)) => {
+                world.pending.retain(|_, (pending, _)| *pending > batch);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let now = Instant::now();
+        let changed = scheduler.update(now - last_update, &mut world);
+        last_update = now;
+        if changed.is_empty() {
+            continue;
+        }
+        // Blocks replaced more than once are sent once, with their last block
+        let mut sent = HashSet::new();
+        let blocks = changed
+            .into_iter()
+            .filter(|position| sent.insert(*position))
+            .filter_map(|position| Some((position, world.pending.get(&position)?.1)))
+            .collect();
+        let batch = TickChanges {
+            batch: world.batch,
+            blocks,
+        };
+        if changes.send(batch).is_err() {
+            return;
+        }
+        world.batch += 1;
+    }
+}
+
+/// Whether the block above `position` lets light through and isn't a fluid,
+/// which grass needs to grow
+fn uncovered(world: &impl TickWorld, position: na::Vector3<i32>) -> bool {
+    match world.block_at(offset(position, [0, 1, 0])) {
+        Some(above) => {
+            FLUID_REGISTRY.get(above).is_none()
+                && BLOCK_REGISTRY
+                    .get(above)
+                    .is_ok_and(|definition| definition.transparent)
+        }
+        None => true,
+    }
+}